@@ -1,56 +1,66 @@
+#[cfg(feature = "tls")]
+use crate::{
+    connection::{Connection, TlsStream},
+    tls::{CipherSuite, ClientProvider, SharedRng, TlsOptions},
+    transport::Transport,
+};
 use crate::{
     error::Error,
     header::{
         HttpHeader,
-        headers::{CONTENT_LENGTH, CONTENT_TYPE},
+        headers::{CONTENT_LENGTH, CONTENT_TYPE, LOCATION},
     },
     method::HttpMethod,
     options::HttpClientOptions,
+    parser::{BodyFraming, Message, Parser, parse_field_line, parse_status_line},
     protocol::{
-        self, CHUNKED, CHUNKED_END_MARKER, CONNECTION_CLOSE_END, CRLF_LEN, CRLF_STR,
-        DEFAULT_HTTP_PORT, DEFAULT_HTTPS_PORT, DOUBLE_CRLF_LEN, HEADER_SEPARATOR,
-        HTTP_VERSION_LINE_SUFFIX, MAX_HEADERS, TRANSFER_ENCODING,
+        CHUNKED, CONNECTION_CLOSE_END, CONNECTION_KEEP_ALIVE_END, CRLF_STR, DEFAULT_HTTP_PORT,
+        DEFAULT_HTTPS_PORT, DOUBLE_CRLF_LEN, HEADER_SEPARATOR, HTTP_VERSION_LINE_SUFFIX,
+        MAX_HEADERS, TRANSFER_ENCODING,
     },
+    redirect::{self, MAX_REDIRECT_URL_LEN, RedirectPolicy},
     response::{HttpResponse, ResponseBody},
+    session::SessionBuffers,
     status_code::StatusCode,
+    transport::{Connector, TcpConnector},
+    url::Url,
 };
-use embassy_net::{
-    Stack,
-    dns::{self, DnsSocket},
-    tcp::TcpSocket,
-};
-#[cfg(feature = "tls")]
-use embassy_time::Instant;
+#[cfg(any(feature = "tls", feature = "websocket"))]
+use core::cell::RefCell;
+use core::marker::PhantomData;
+use embassy_net::Stack;
 use embassy_time::Timer;
-use embedded_io_async::Write as EmbeddedWrite;
-#[cfg(feature = "tls")]
-use embedded_tls::{Aes128GcmSha256, TlsConfig, TlsConnection, TlsContext, UnsecureProvider};
-use heapless::Vec;
+use embedded_io_async::{Read as EmbeddedRead, Write as EmbeddedWrite};
 #[cfg(feature = "tls")]
-use rand_core::{CryptoRng, RngCore};
+use embedded_tls::{TlsCipherSuite, TlsConfig, TlsConnection, TlsContext};
+use heapless::{String, Vec};
+#[cfg(any(feature = "tls", feature = "websocket"))]
+use rand_core::CryptoRngCore;
 
 const REQUEST_SIZE: usize = 1024;
 const SMALL_BUFFER_SIZE: usize = 1024;
 const MEDIUM_BUFFER_SIZE: usize = 4096;
 
 /// Type alias for `HttpClient` with default buffer sizes
-pub type DefaultHttpClient<'a> = HttpClient<
+pub type DefaultHttpClient<'a, C = TcpConnector<'a>> = HttpClient<
     'a,
     MEDIUM_BUFFER_SIZE, // TCP_RX: 4KB
     MEDIUM_BUFFER_SIZE, // TCP_TX: 4KB
     MEDIUM_BUFFER_SIZE, // TLS_READ: 4KB
     MEDIUM_BUFFER_SIZE, // TLS_WRITE: 4KB
     REQUEST_SIZE,       // RQ: 1KB
+    C,
 >;
 
 /// Type alias for `HttpClient` with small buffer sizes for memory-constrained environments
-pub type SmallHttpClient<'a> = HttpClient<
+pub type SmallHttpClient<'a, C = TcpConnector<'a>> = HttpClient<
     'a,
     SMALL_BUFFER_SIZE, // TCP_RX: 1KB
     SMALL_BUFFER_SIZE, // TCP_TX: 1KB
     SMALL_BUFFER_SIZE, // TLS_READ: 1KB
     SMALL_BUFFER_SIZE, // TLS_WRITE: 1KB
     REQUEST_SIZE,      // RQ: 1KB
+    C,
 >;
 
 macro_rules! try_push {
@@ -69,7 +79,8 @@ macro_rules! try_push {
 ///
 /// The client is designed to work with Embassy's networking stack and requires
 /// users to provide their own response buffers, ensuring maximum memory efficiency
-/// and control while maintaining `no_std` compatibility.
+/// and control while maintaining `no_std` compatibility. Other transports can be
+/// plugged in with a [`Connector`].
 ///
 /// # Type Parameters
 ///
@@ -78,6 +89,7 @@ macro_rules! try_push {
 /// * `TLS_READ` - TLS read record buffer size (default: 4096 bytes, when TLS feature is enabled)
 /// * `TLS_WRITE` - TLS write record buffer size (default: 4096 bytes, when TLS feature is enabled)
 /// * `RQ` - HTTP request buffer size for building requests (default: 1024 bytes)
+/// * `C` - The [`Connector`] opening connections (default: [`TcpConnector`])
 pub struct HttpClient<
     'a,
     const TCP_RX: usize = MEDIUM_BUFFER_SIZE,
@@ -85,11 +97,20 @@ pub struct HttpClient<
     const TLS_READ: usize = MEDIUM_BUFFER_SIZE,
     const TLS_WRITE: usize = MEDIUM_BUFFER_SIZE,
     const RQ: usize = REQUEST_SIZE,
+    C = TcpConnector<'a>,
 > {
-    /// Reference to the Embassy network stack
-    stack: &'a Stack<'a>,
+    /// Opens the connections requests are made on
+    pub(crate) connector: C,
+    /// Ties the client to `'a` when no option borrows from it
+    lifetime: PhantomData<&'a ()>,
     /// HTTP client options
-    options: HttpClientOptions,
+    pub(crate) options: HttpClientOptions,
+    /// TLS options for `https` requests
+    #[cfg(feature = "tls")]
+    pub(crate) tls: TlsOptions<'a>,
+    /// Random number generator for TLS handshakes and WebSocket keys
+    #[cfg(any(feature = "tls", feature = "websocket"))]
+    pub(crate) rng: Option<&'a RefCell<dyn CryptoRngCore>>,
 }
 
 impl<
@@ -99,29 +120,148 @@ impl<
     const TLS_READ: usize,
     const TLS_WRITE: usize,
     const RQ: usize,
-> HttpClient<'a, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ>
+> HttpClient<'a, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, TcpConnector<'a>>
 {
     /// Create a new HTTP client with custom buffer sizes and default options
     #[must_use]
     pub fn new(stack: &'a Stack<'a>) -> Self {
-        Self {
-            stack,
-            options: HttpClientOptions::default(),
-        }
+        Self::with_options(stack, HttpClientOptions::default())
     }
 
     /// Create a new HTTP client with custom buffer sizes and custom options
     #[must_use]
     pub const fn with_options(stack: &'a Stack<'a>, options: HttpClientOptions) -> Self {
-        Self { stack, options }
+        Self::with_connector(TcpConnector::new(stack), options)
     }
+}
 
+#[cfg_attr(
+    not(any(feature = "tls", feature = "websocket")),
+    expect(clippy::elidable_lifetime_names)
+)]
+impl<
+    'a,
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C,
+> HttpClient<'a, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
+    /// Create a new HTTP client that opens its connections with `connector`
+    ///
+    /// The `TCP_RX` and `TCP_TX` buffers are lent to the connector for each
+    /// connection, and TLS runs on top of whatever transport it returns.
+    #[must_use]
+    pub const fn with_connector(connector: C, options: HttpClientOptions) -> Self {
+        Self {
+            connector,
+            lifetime: PhantomData,
+            options,
+            #[cfg(feature = "tls")]
+            tls: TlsOptions {
+                verification: crate::tls::ServerVerification::RootCertificates(&[]),
+                client_identity: None,
+                pre_shared_key: None,
+                cipher_suite: crate::tls::CipherSuite::Aes128GcmSha256,
+                alpn_protocols: &[],
+                max_fragment_length: None,
+            },
+            #[cfg(any(feature = "tls", feature = "websocket"))]
+            rng: None,
+        }
+    }
+
+    /// Set the TLS options used for `https` requests
+    ///
+    /// By default no trust anchors are configured and every server certificate
+    /// is rejected with [`Error::InvalidCertificate`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use nanofish::{DefaultHttpClient, ServerVerification, TlsIdentity, TlsOptions};
+    /// use embassy_net::Stack;
+    ///
+    /// const ROOT_CA: &[u8] = &[/* DER-encoded root CA certificate */];
+    /// const DEVICE_CERT: &[u8] = &[/* DER-encoded client certificate */];
+    /// const DEVICE_KEY: [u8; 32] = [/* P-256 private key */ 0; 32];
+    ///
+    /// fn example<'a>(stack: &'a Stack<'a>) -> DefaultHttpClient<'a> {
+    ///     DefaultHttpClient::new(stack).with_tls(TlsOptions {
+    ///         verification: ServerVerification::RootCertificates(&[ROOT_CA]),
+    ///         // Presented to servers that require client certificates
+    ///         client_identity: Some(TlsIdentity {
+    ///             certificate_chain: &[DEVICE_CERT],
+    ///             private_key: &DEVICE_KEY,
+    ///         }),
+    ///         ..TlsOptions::default()
+    ///     })
+    /// }
+    /// ```
+    #[cfg(feature = "tls")]
+    #[must_use]
+    pub const fn with_tls(mut self, tls: TlsOptions<'a>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Set the random number generator used for TLS handshakes
+    ///
+    /// HTTPS requests and WebSocket connections fail with
+    /// [`Error::MissingRng`] until a generator is set.
+    /// Use a cryptographically secure source such as a hardware TRNG; the
+    /// generator is borrowed only for the duration of each call into it.
+    /// Devices without one can opt in to the predictable
+    /// [`InsecureTimeSeededRng`](crate::tls::InsecureTimeSeededRng).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use core::cell::RefCell;
+    /// use nanofish::DefaultHttpClient;
+    /// use embassy_net::Stack;
+    /// use rand_core::CryptoRngCore;
+    ///
+    /// // `trng` wraps the hardware TRNG of your chip
+    /// async fn example<'a, R: CryptoRngCore + 'static>(
+    ///     stack: &'a Stack<'a>,
+    ///     trng: &'a RefCell<R>,
+    /// ) -> Result<(), nanofish::Error> {
+    ///     let client = DefaultHttpClient::new(stack).with_rng(trng);
+    ///     let mut buffer = [0u8; 4096];
+    ///     client.get("https://example.com", &[], &mut buffer).await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[cfg(any(feature = "tls", feature = "websocket"))]
+    #[must_use]
+    pub const fn with_rng(mut self, rng: &'a RefCell<dyn CryptoRngCore>) -> Self {
+        self.rng = Some(rng);
+        self
+    }
+}
+
+impl<
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector,
+> HttpClient<'_, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
     /// Make an HTTP request with zero-copy response handling
     ///
     /// This is the core method for making HTTP requests using zero-copy approach.
     /// The caller provides a buffer where the response will be stored, and the
     /// returned `HttpResponse` will contain references to data within that buffer.
     ///
+    /// Redirects are followed as configured by
+    /// [`HttpClientOptions::redirect_policy`]; by default they are returned
+    /// like any other response.
+    ///
     /// # Arguments
     ///
     /// * `method` - The HTTP method to use (GET, POST, etc.)
@@ -138,12 +278,15 @@ impl<
     /// # Errors
     ///
     /// This function will return an error if:
-    /// * The URL is malformed or cannot be parsed
+    /// * The URL is malformed or cannot be parsed ([`Error::Url`])
     /// * DNS resolution fails for the hostname
     /// * Network connection cannot be established
     /// * The request times out
     /// * The response cannot be parsed
     /// * The response buffer is too small for the response data
+    ///   ([`Error::BufferOverflow`]); use [`HttpClient::request_streaming`] for
+    ///   large bodies
+    /// * The redirect policy's hop limit is exceeded ([`Error::TooManyRedirects`])
     ///
     /// # Examples
     ///
@@ -180,245 +323,381 @@ impl<
         body: Option<&[u8]>,
         response_buffer: &'b mut [u8],
     ) -> Result<(HttpResponse<'b>, usize), Error> {
-        let (scheme, host_port) = if let Some(rest) = endpoint.strip_prefix("http://") {
-            ("http", rest)
-        } else if let Some(rest) = endpoint.strip_prefix("https://") {
-            ("https", rest)
-        } else {
-            return Err(Error::InvalidUrl);
-        };
-
-        let host = host_port.split('/').next().ok_or(Error::InvalidUrl)?;
-        let path = &host_port[host.len()..];
-        let path = if path.is_empty() { "/" } else { path };
-
-        let default_port = if scheme == "https" {
-            DEFAULT_HTTPS_PORT
-        } else {
-            DEFAULT_HTTP_PORT
-        };
-        let (host, port) = host.rfind(':').map_or((host, default_port), |colon_pos| {
-            host[colon_pos + 1..]
-                .parse::<u16>()
-                .map_or((host, default_port), |port| (&host[..colon_pos], port))
-        });
+        let url = Url::parse(endpoint)?;
+        self.request_url(method, &url, headers, body, response_buffer)
+            .await
+    }
 
-        let total_read = match scheme {
-            #[cfg(feature = "tls")]
-            "https" => {
-                self.make_https_request(method, (host, port), path, headers, body, response_buffer)
+    /// Make an HTTP request to an already parsed [`Url`]
+    ///
+    /// This behaves exactly like [`HttpClient::request`], but lets a URL be
+    /// parsed and validated once and then reused for many requests.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`HttpClient::request`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use nanofish::{DefaultHttpClient, HttpMethod, Url};
+    /// use embassy_net::Stack;
+    ///
+    /// async fn example(stack: &Stack<'_>) -> Result<(), nanofish::Error> {
+    ///     let client = DefaultHttpClient::new(stack);
+    ///     let url = Url::parse("http://[fe80::1]:8080/sensor")?;
+    ///     let mut buffer = [0u8; 1024];
+    ///     for _ in 0..3 {
+    ///         let (response, _) = client
+    ///             .request_url(HttpMethod::GET, &url, &[], None, &mut buffer)
+    ///             .await?;
+    ///         assert!(response.is_success());
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[expect(clippy::future_not_send)]
+    pub async fn request_url<'b>(
+        &self,
+        method: HttpMethod,
+        url: &Url<'_>,
+        headers: &[HttpHeader<'_>],
+        body: Option<&[u8]>,
+        response_buffer: &'b mut [u8],
+    ) -> Result<(HttpResponse<'b>, usize), Error> {
+        let total_read = match self.options.redirect_policy {
+            RedirectPolicy::None => {
+                self.send_request(method, url, headers, body, response_buffer)
                     .await?
             }
-            #[cfg(not(feature = "tls"))]
-            "https" => return Err(Error::UnsupportedScheme("https (TLS support not enabled)")),
-            "http" => {
-                self.make_http_request(method, (host, port), path, headers, body, response_buffer)
+            policy => {
+                self.follow_redirects(policy, method, url, headers, body, response_buffer)
                     .await?
             }
-            _ => return Err(Error::UnsupportedScheme(scheme)),
         };
 
-        // Decode chunked transfer-encoding in-place if present
-        let total_read = Self::dechunk(response_buffer, total_read)?;
-
         let response = Self::parse_http_response_zero_copy(&response_buffer[..total_read])?;
         Ok((response, total_read))
     }
 
-    /// Resolve a hostname to an IP address, trying IPv4 (A) first then IPv6 (AAAA).
+    /// Send a single request and read its complete, dechunked response into
+    /// `response_buffer`, returning the response length.
     #[expect(clippy::future_not_send)]
-    async fn resolve_host(stack: Stack<'_>, host: &str) -> Result<embassy_net::IpAddress, Error> {
-        let dns_socket = DnsSocket::new(stack);
-
-        // Try A (IPv4) first — most common on embedded networks
-        if let Ok(addrs) = dns_socket.query(host, dns::DnsQueryType::A).await
-            && let Some(&addr) = addrs.first()
-        {
-            return Ok(addr);
-        }
-
-        // Fall back to AAAA (IPv6)
-        let addrs = dns_socket.query(host, dns::DnsQueryType::Aaaa).await?;
-        addrs.first().copied().ok_or(Error::IpAddressEmpty)
+    async fn send_request(
+        &self,
+        method: HttpMethod,
+        url: &Url<'_>,
+        headers: &[HttpHeader<'_>],
+        body: Option<&[u8]>,
+        response_buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let (scheme, (host, port)) = Self::connection_target(url)?;
+        let path = url.path_and_query();
+
+        self.make_request(
+            method,
+            scheme,
+            (host, port),
+            path,
+            headers,
+            body,
+            response_buffer,
+        )
+        .await
     }
 
-    /// Make HTTPS request over TLS with zero-copy response handling
-    #[cfg(feature = "tls")]
+    /// Send a request and follow the redirects `policy` allows, returning the
+    /// length of the final response in `response_buffer`.
     #[expect(clippy::future_not_send)]
-    async fn make_https_request(
+    async fn follow_redirects(
         &self,
-        method: HttpMethod,
-        host_port: (&str, u16),
-        path: &str,
+        policy: RedirectPolicy,
+        mut method: HttpMethod,
+        url: &Url<'_>,
         headers: &[HttpHeader<'_>],
-        body: Option<&[u8]>,
+        mut body: Option<&[u8]>,
         response_buffer: &mut [u8],
     ) -> Result<usize, Error> {
-        let (host, port) = host_port;
-        let mut rx_buffer = [0; TCP_RX];
-        let mut tx_buffer = [0; TCP_TX];
-        let mut socket = TcpSocket::new(*self.stack, &mut rx_buffer, &mut tx_buffer);
-        socket.set_timeout(Some(self.options.socket_timeout));
+        let (max_hops, any_origin) = match policy {
+            RedirectPolicy::None => (0, false),
+            RedirectPolicy::SameOrigin { max_hops } => (max_hops, false),
+            RedirectPolicy::AnyOrigin { max_hops } => (max_hops, true),
+        };
 
-        let ip_addr = Self::resolve_host(*self.stack, host).await?;
-        let remote_endpoint = (ip_addr, port);
+        // The target of the last redirect followed, if any
+        let mut current: Option<String<MAX_REDIRECT_URL_LEN>> = None;
+        // Set once a redirect leaves the original origin
+        let mut cross_origin_seen = false;
+        // Set once a redirect drops the request body
+        let mut body_dropped = false;
+        let mut stripped_headers = None;
+        let mut hops = 0;
+
+        loop {
+            let parsed;
+            let url = match &current {
+                Some(current) => {
+                    parsed = Url::parse(current)?;
+                    &parsed
+                }
+                None => url,
+            };
+            let request_headers = stripped_headers.as_deref().unwrap_or(headers);
+            let total_read = self
+                .send_request(method, url, request_headers, body, response_buffer)
+                .await?;
+
+            let response = Self::parse_http_response_zero_copy(&response_buffer[..total_read])?;
+            let Some((next_method, keep_body)) =
+                redirect::redirect_method(response.status_code, method)
+            else {
+                return Ok(total_read);
+            };
+            let Some(location) = response.get_header(LOCATION) else {
+                return Ok(total_read);
+            };
+            let next_url = redirect::resolve_location(url, location)?;
 
-        socket
-            .connect(remote_endpoint)
-            .await
-            .map_err(|e: embassy_net::tcp::ConnectError| {
-                socket.abort();
-                Error::from(e)
-            })?;
-
-        let mut read_record_buffer = [0; TLS_READ];
-        let mut write_record_buffer = [0; TLS_WRITE];
-
-        let tls_config = TlsConfig::new().with_server_name(host);
-        let mut tls = TlsConnection::new(socket, &mut read_record_buffer, &mut write_record_buffer);
-        let timeseed_bytes = timeseed();
-        let seed = u32::from_be_bytes([
-            timeseed_bytes[0],
-            timeseed_bytes[1],
-            timeseed_bytes[2],
-            timeseed_bytes[3],
-        ]);
-        let rng = XorShift32Rng::new(seed);
-
-        tls.open(TlsContext::new(
-            &tls_config,
-            UnsecureProvider::new::<Aes128GcmSha256>(rng),
-        ))
-        .await?;
-
-        let http_request = Self::build_http_request(method, host, path, headers, body)?;
-
-        tls.write_all(http_request.as_bytes()).await?;
-
-        if let Some(body_data) = body {
-            tls.write_all(body_data).await?;
+            let cross_origin = !url.same_origin(&Url::parse(&next_url)?);
+            if cross_origin && !any_origin {
+                return Ok(total_read);
+            }
+            if hops == max_hops {
+                return Err(Error::TooManyRedirects);
+            }
+            hops += 1;
+
+            debug!(
+                "Following {} redirect to {}",
+                response.status_code.as_u16(),
+                next_url.as_str()
+            );
+            let drop_body = !keep_body;
+            if (cross_origin && !cross_origin_seen) || (drop_body && !body_dropped) {
+                cross_origin_seen |= cross_origin;
+                body_dropped |= drop_body;
+                stripped_headers = Some(redirect::strip_headers(
+                    headers,
+                    cross_origin_seen,
+                    body_dropped,
+                )?);
+            }
+            method = next_method;
+            if !keep_body {
+                body = None;
+            }
+            current = Some(next_url);
         }
+    }
 
-        tls.flush().await?;
-
-        let mut total_read = 0;
-        let mut retries = self.options.max_retries;
+    /// The scheme and host/port to connect to for `url`.
+    ///
+    /// The port defaults to 80 for `http` and 443 for `https`; other schemes
+    /// are rejected.
+    pub(crate) fn connection_target<'u>(
+        url: &Url<'u>,
+    ) -> Result<(&'static str, (&'u str, u16)), Error> {
+        let (scheme, default_port) = if url.scheme().eq_ignore_ascii_case("http") {
+            ("http", DEFAULT_HTTP_PORT)
+        } else if url.scheme().eq_ignore_ascii_case("https") {
+            ("https", DEFAULT_HTTPS_PORT)
+        } else {
+            return Err(Error::UnsupportedScheme(
+                "only http and https are supported",
+            ));
+        };
+        Ok((scheme, (url.host(), url.port().unwrap_or(default_port))))
+    }
 
-        while total_read < response_buffer.len() && retries > 0 {
-            match tls.read(&mut response_buffer[total_read..]).await {
-                Ok(0) => {
-                    break;
-                }
-                Ok(n) => {
-                    total_read += n;
-                    if Self::is_response_complete(&response_buffer[..total_read]) {
-                        break;
-                    }
-                }
-                Err(e) => {
-                    retries -= 1;
-                    if retries > 0 {
-                        Timer::after(self.options.retry_delay).await;
-                    } else {
-                        return Err(Error::TlsError(e));
-                    }
-                }
+    /// Wrap a connected transport in TLS and perform the handshake.
+    ///
+    /// The server certificate is checked according to the client's
+    /// [`TlsOptions`]; a rejected server yields [`Error::InvalidCertificate`].
+    /// The client identity is presented if the server asks for a certificate.
+    #[cfg(feature = "tls")]
+    #[expect(clippy::future_not_send)]
+    pub(crate) async fn open_tls<'s, T: Transport + 's>(
+        &self,
+        transport: T,
+        host: &str,
+        read_record_buffer: &'s mut [u8],
+        write_record_buffer: &'s mut [u8],
+    ) -> Result<Connection<'s, T>, Error> {
+        let rng = self.rng.ok_or(Error::MissingRng)?;
+        let config = self.tls.config(host);
+        let (tls, client_auth_pending) = match self.tls.cipher_suite {
+            CipherSuite::Aes128GcmSha256 => {
+                let (tls, pending) = self
+                    .handshake(
+                        transport,
+                        &config,
+                        rng,
+                        read_record_buffer,
+                        write_record_buffer,
+                    )
+                    .await?;
+                (TlsStream::Aes128GcmSha256(tls), pending)
             }
-        }
+            CipherSuite::Aes256GcmSha384 => {
+                let (tls, pending) = self
+                    .handshake(
+                        transport,
+                        &config,
+                        rng,
+                        read_record_buffer,
+                        write_record_buffer,
+                    )
+                    .await?;
+                (TlsStream::Aes256GcmSha384(tls), pending)
+            }
+            CipherSuite::ChaCha20Poly1305Sha256 => {
+                let (tls, pending) = self
+                    .handshake(
+                        transport,
+                        &config,
+                        rng,
+                        read_record_buffer,
+                        write_record_buffer,
+                    )
+                    .await?;
+                (TlsStream::ChaCha20Poly1305Sha256(tls), pending)
+            }
+        };
+        Ok(Connection::Tls {
+            tls,
+            client_auth_pending,
+        })
+    }
 
-        if let Err((_, e)) = tls.close().await {
-            debug!("Error closing TLS connection: {:?}", Error::from(e));
+    /// Perform a TLS handshake with `CS` as the only offered cipher suite.
+    ///
+    /// Also returns whether the server asked for a client certificate.
+    #[cfg(feature = "tls")]
+    #[expect(clippy::future_not_send)]
+    async fn handshake<'s, T: Transport + 's, CS: TlsCipherSuite + 'static>(
+        &self,
+        transport: T,
+        config: &TlsConfig<'_>,
+        rng: &RefCell<dyn CryptoRngCore>,
+        read_record_buffer: &'s mut [u8],
+        write_record_buffer: &'s mut [u8],
+    ) -> Result<(TlsConnection<'s, T, CS>, bool), Error> {
+        let mut tls = TlsConnection::new(transport, read_record_buffer, write_record_buffer);
+
+        // The chain is retained between handshake messages, and it cannot be
+        // larger than the record that carried it
+        let mut provider = ClientProvider::<CS, _, TLS_READ>::new(SharedRng(rng), &self.tls);
+        let opened = tls.open(TlsContext::new(config, &mut provider)).await;
+
+        if provider.verifier.failed() {
+            return Err(Error::InvalidCertificate);
         }
-
-        Timer::after(self.options.socket_close_delay).await;
-
-        if total_read == 0 {
-            return Err(Error::NoResponse);
+        opened?;
+
+        // A server that skipped its certificate never proved its identity,
+        // unless it did so by knowing the pre-shared key
+        if !provider.verifier.verified() && self.tls.pre_shared_key.is_none() {
+            let transport = match tls.close().await {
+                Ok(transport) => transport,
+                Err((transport, e)) => {
+                    debug!("Error closing TLS connection: {:?}", Error::from(e));
+                    transport
+                }
+            };
+            transport.close().await;
+            return Err(Error::InvalidCertificate);
         }
 
-        Ok(total_read)
+        Ok((tls, provider.certificate_requested))
     }
 
-    /// Make HTTP request with zero-copy response handling
-    #[expect(clippy::future_not_send)]
-    async fn make_http_request(
+    /// Make a request on a new connection with zero-copy response handling
+    #[expect(clippy::future_not_send, clippy::too_many_arguments)]
+    async fn make_request(
         &self,
         method: HttpMethod,
+        scheme: &'static str,
         host_port: (&str, u16),
         path: &str,
         headers: &[HttpHeader<'_>],
         body: Option<&[u8]>,
         response_buffer: &mut [u8],
     ) -> Result<usize, Error> {
-        let (host, port) = host_port;
-        let mut rx_buffer = [0; TCP_RX];
-        let mut tx_buffer = [0; TCP_TX];
-        let mut socket = TcpSocket::new(*self.stack, &mut rx_buffer, &mut tx_buffer);
-        socket.set_timeout(Some(self.options.socket_timeout));
-
-        let ip_addr = Self::resolve_host(*self.stack, host).await?;
-        let remote_endpoint = (ip_addr, port);
-
-        socket
-            .connect(remote_endpoint)
-            .await
-            .map_err(|e: embassy_net::tcp::ConnectError| {
-                socket.abort();
-                Error::from(e)
-            })?;
+        let http_request = Self::build_http_request(
+            method,
+            scheme,
+            host_port,
+            path,
+            headers,
+            body.map(|b| BodyFraming::Length(b.len())),
+            false,
+        )?;
+
+        let mut buffers = SessionBuffers::<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>::new();
+        let mut connection = self
+            .open_connection(scheme, host_port, &mut buffers)
+            .await?;
+
+        let result = async {
+            connection.write_all(http_request.as_bytes()).await?;
+            if let Some(body_data) = body {
+                connection.write_all(body_data).await?;
+            }
+            connection.flush().await?;
+            self.read_with_retries(&mut connection, method, response_buffer)
+                .await
+        }
+        .await;
 
-        let http_request = Self::build_http_request(method, host, path, headers, body)?;
+        connection.close().await;
+        Timer::after(self.options.socket_close_delay).await;
 
-        socket
-            .write_all(http_request.as_bytes())
-            .await
-            .map_err(|e| {
-                socket.abort();
-                Error::from(e)
-            })?;
-
-        if let Some(body_data) = body {
-            socket.write_all(body_data).await.map_err(|e| {
-                socket.abort();
-                Error::from(e)
-            })?;
-        }
+        // Decode chunked transfer-encoding in-place if present
+        Ok(result?.decode_body(response_buffer))
+    }
 
+    /// Read the response to a `method` request into `response_buffer` until
+    /// it is complete or the connection closes, retrying failed reads.
+    ///
+    /// A response that does not fit is reported instead of being silently
+    /// truncated.
+    #[expect(clippy::future_not_send)]
+    async fn read_with_retries<R: EmbeddedRead<Error = Error>>(
+        &self,
+        connection: &mut R,
+        method: HttpMethod,
+        response_buffer: &mut [u8],
+    ) -> Result<Message, Error> {
+        let mut parser = Parser::response(method);
         let mut total_read = 0;
         let mut retries = self.options.max_retries;
 
-        while total_read < response_buffer.len() && retries > 0 {
-            match socket.read(&mut response_buffer[total_read..]).await {
-                Ok(0) => {
-                    break;
-                }
+        loop {
+            if total_read == response_buffer.len() {
+                return Err(Error::BufferOverflow);
+            }
+            match connection.read(&mut response_buffer[total_read..]).await {
+                Ok(0) if total_read == 0 => return Err(Error::NoResponse),
+                Ok(0) => return parser.finish(),
                 Ok(n) => {
                     total_read += n;
-                    if Self::is_response_complete(&response_buffer[..total_read]) {
-                        break;
+                    if let Some(message) = parser.parse(&response_buffer[..total_read])? {
+                        return Ok(message);
                     }
                 }
+                #[cfg(feature = "tls")]
+                Err(e @ Error::ClientCertificateRejected) => return Err(e),
                 Err(e) => {
-                    error!("Socket read error: {:?}", e);
-                    retries -= 1;
+                    error!("Read error: {:?}", e);
+                    retries = retries.saturating_sub(1);
                     if retries > 0 {
                         Timer::after(self.options.retry_delay).await;
                     } else {
-                        socket.close();
-                        return Err(Error::from(e));
+                        return Err(e);
                     }
                 }
             }
         }
-
-        socket.close();
-        Timer::after(self.options.socket_close_delay).await;
-
-        if total_read == 0 {
-            return Err(Error::NoResponse);
-        }
-
-        Ok(total_read)
     }
 
     /// Convenience method for making a PATCH request
@@ -674,41 +953,25 @@ impl<
     }
 
     /// Parse HTTP response from raw data with zero-copy handling
-    fn parse_http_response_zero_copy(data: &[u8]) -> Result<HttpResponse<'_>, Error> {
-        // Find the end of headers delimiter in raw bytes to avoid
-        // requiring the entire response (including binary body) to be valid UTF-8.
-        let headers_end = protocol::find_double_crlf(data)
+    pub(crate) fn parse_http_response_zero_copy(data: &[u8]) -> Result<HttpResponse<'_>, Error> {
+        // Only the head has to be text; the body may be binary
+        let headers_end = Parser::response(HttpMethod::GET)
+            .parse_head(data)?
             .ok_or(Error::InvalidResponse("Invalid HTTP response format"))?
-            + DOUBLE_CRLF_LEN;
-
-        let header_bytes = &data[..headers_end];
-        let response_str = core::str::from_utf8(header_bytes)
+            .len;
+        let head = core::str::from_utf8(&data[..headers_end - DOUBLE_CRLF_LEN])
             .map_err(|_| Error::InvalidResponse("Invalid HTTP response encoding"))?;
 
-        let status_line_end = protocol::find_crlf(header_bytes)
-            .ok_or(Error::InvalidResponse("Invalid HTTP response format"))?;
+        let mut lines = head.split(CRLF_STR);
+        let (_, status) =
+            parse_status_line(lines.next().unwrap_or_default()).map_err(Error::InvalidResponse)?;
+        let status_code = StatusCode::from(status);
 
-        let status_line = &response_str[..status_line_end];
-        let status_code_str = status_line
-            .split_whitespace()
-            .nth(1)
-            .ok_or(Error::InvalidResponse("Invalid HTTP status line"))?;
-
-        let status_code: StatusCode = status_code_str.try_into()?;
-
-        let headers_section =
-            &response_str[status_line_end + CRLF_LEN..headers_end - DOUBLE_CRLF_LEN];
         let mut headers = Vec::<HttpHeader<'_>, MAX_HEADERS>::new();
-
-        for header_line in headers_section.split(CRLF_STR) {
-            if let Some(colon_pos) = header_line.find(':') {
-                let name = header_line[..colon_pos].trim();
-                let value = header_line[colon_pos + 1..].trim();
-
-                let header = HttpHeader::new(name, value);
-                if headers.push(header).is_err() {
-                    break;
-                }
+        for header_line in lines {
+            let (name, value) = parse_field_line(header_line).map_err(Error::InvalidResponse)?;
+            if headers.push(HttpHeader::new(name, value)).is_err() {
+                break;
             }
         }
 
@@ -778,24 +1041,74 @@ impl<
     }
 
     /// Build HTTP request string
-    fn build_http_request(
+    ///
+    /// With `keep_alive` set the request asks the server to keep the
+    /// connection open; otherwise it sends `Connection: close`.
+    pub(crate) fn build_http_request(
         method: HttpMethod,
-        host: &str,
+        scheme: &str,
+        host_port: (&str, u16),
         path: &str,
         headers: &[HttpHeader<'_>],
-        body: Option<&[u8]>,
-    ) -> Result<heapless::String<RQ>, Error> {
-        let mut http_request = heapless::String::<RQ>::new();
+        body: Option<BodyFraming>,
+        keep_alive: bool,
+    ) -> Result<String<RQ>, Error> {
+        let mut http_request =
+            Self::build_request_head(method, scheme, host_port, path, headers, body)?;
+
+        if keep_alive {
+            try_push!(http_request.push_str(CONNECTION_KEEP_ALIVE_END));
+        } else {
+            try_push!(http_request.push_str(CONNECTION_CLOSE_END));
+        }
+
+        Ok(http_request)
+    }
+
+    /// Build the request line and headers, leaving out the `Connection`
+    /// header and the blank line that ends the head.
+    ///
+    /// The `Host` header names the port unless it is the default of `scheme`.
+    pub(crate) fn build_request_head(
+        method: HttpMethod,
+        scheme: &str,
+        (host, port): (&str, u16),
+        path: &str,
+        headers: &[HttpHeader<'_>],
+        body: Option<BodyFraming>,
+    ) -> Result<String<RQ>, Error> {
+        let mut http_request = String::<RQ>::new();
 
         try_push!(http_request.push_str(method.as_str()));
         try_push!(http_request.push_str(" "));
+        // A URL without a path still requests the root
+        if path.is_empty() || path.starts_with('?') {
+            try_push!(http_request.push_str("/"));
+        }
         try_push!(http_request.push_str(path));
         try_push!(http_request.push_str(HTTP_VERSION_LINE_SUFFIX));
         try_push!(http_request.push_str("Host: "));
-        try_push!(http_request.push_str(host));
+        // IPv6 literals keep their brackets in the Host header
+        if host.contains(':') {
+            try_push!(http_request.push_str("["));
+            try_push!(http_request.push_str(host));
+            try_push!(http_request.push_str("]"));
+        } else {
+            try_push!(http_request.push_str(host));
+        }
+        let default_port = if scheme == "https" {
+            DEFAULT_HTTPS_PORT
+        } else {
+            DEFAULT_HTTP_PORT
+        };
+        if port != default_port
+            && core::fmt::write(&mut http_request, format_args!(":{port}")).is_err()
+        {
+            return Err(Error::BufferOverflow);
+        }
         try_push!(http_request.push_str(CRLF_STR));
 
-        let mut content_length_present = false;
+        let mut framing_present = false;
 
         for header in headers {
             try_push!(http_request.push_str(header.name));
@@ -803,224 +1116,84 @@ impl<
             try_push!(http_request.push_str(header.value));
             try_push!(http_request.push_str(CRLF_STR));
 
-            if header.name.eq_ignore_ascii_case(CONTENT_LENGTH) {
-                content_length_present = true;
-            }
-        }
-
-        // Add Content-Length header if body is present and not already specified
-        if !content_length_present && body.is_some() {
-            try_push!(http_request.push_str(CONTENT_LENGTH));
-            try_push!(http_request.push_str(HEADER_SEPARATOR));
-            let mut len_str = heapless::String::<8>::new();
-            if core::fmt::write(
-                &mut len_str,
-                format_args!("{}", body.unwrap_or_default().len()),
-            )
-            .is_err()
+            if header.name.eq_ignore_ascii_case(CONTENT_LENGTH)
+                || header.name.eq_ignore_ascii_case(TRANSFER_ENCODING)
             {
-                return Err(Error::BufferOverflow);
+                framing_present = true;
             }
-            try_push!(http_request.push_str(&len_str));
-            try_push!(http_request.push_str(CRLF_STR));
-        }
-
-        try_push!(http_request.push_str(CONNECTION_CLOSE_END));
-
-        Ok(http_request)
-    }
-
-    /// Check if HTTP response is complete
-    fn is_response_complete(data: &[u8]) -> bool {
-        if protocol::find_double_crlf(data).is_none() {
-            return false;
         }
 
-        // Check for chunked transfer encoding
-        if Self::has_chunked_transfer_encoding(data) {
-            return data
-                .windows(CHUNKED_END_MARKER.len())
-                .any(|w| w == CHUNKED_END_MARKER);
-        }
-
-        // Check for Content-Length header to determine if we have the full body
-        let headers_end = match protocol::find_double_crlf(data) {
-            Some(pos) => pos + DOUBLE_CRLF_LEN,
-            None => return true,
-        };
-        let header_bytes = &data[..headers_end];
-        if let Ok(headers_str) = core::str::from_utf8(header_bytes)
-            && let Some(value) = protocol::find_header_value(headers_str, CONTENT_LENGTH)
-            && let Ok(content_length) = value.parse::<usize>()
-        {
-            let body_received = data.len().saturating_sub(headers_end);
-            return body_received >= content_length;
-        }
-
-        // No Content-Length and not chunked: keep reading until connection closes (Ok(0))
-        false
-    }
-
-    /// Check if the response uses chunked transfer encoding
-    fn has_chunked_transfer_encoding(data: &[u8]) -> bool {
-        let headers_end = match protocol::find_double_crlf(data) {
-            Some(pos) => pos + DOUBLE_CRLF_LEN,
-            None => return false,
-        };
-
-        let header_bytes = &data[..headers_end];
-        if let Ok(headers_str) = core::str::from_utf8(header_bytes)
-            && let Some(value) = protocol::find_header_value(headers_str, TRANSFER_ENCODING)
-        {
-            return value.eq_ignore_ascii_case(CHUNKED);
-        }
-        false
-    }
-
-    /// Decode chunked transfer-encoding in-place if the response uses it.
-    /// Returns the new total length after decoding.
-    fn dechunk(buffer: &mut [u8], total_read: usize) -> Result<usize, Error> {
-        let data = &buffer[..total_read];
-
-        if !Self::has_chunked_transfer_encoding(data) {
-            return Ok(total_read);
-        }
-
-        // Find end of headers
-        let headers_end = protocol::find_double_crlf(data)
-            .ok_or(Error::InvalidResponse("Invalid HTTP response format"))?
-            + DOUBLE_CRLF_LEN;
-
-        // Decode chunks in-place starting after headers
-        let mut read_pos = headers_end;
-        let mut write_pos = headers_end;
-
-        while read_pos < total_read {
-            // Find end of chunk size line
-            let chunk_line_end = match protocol::find_crlf(&buffer[read_pos..total_read]) {
-                Some(pos) => read_pos + pos,
-                None => break,
-            };
-
-            // Parse chunk size (hex)
-            let chunk_size_str = match core::str::from_utf8(&buffer[read_pos..chunk_line_end]) {
-                Ok(s) => s.trim(),
-                Err(_) => return Err(Error::InvalidResponse("Invalid chunk size encoding")),
-            };
-
-            // Chunk size may have extensions after a semicolon
-            let size_part = chunk_size_str.split(';').next().unwrap_or("0").trim();
-            let chunk_size = usize::from_str_radix(size_part, 16)
-                .map_err(|_| Error::InvalidResponse("Invalid chunk size"))?;
-
-            if chunk_size == 0 {
-                // Final chunk
-                break;
-            }
-
-            // Move past chunk size line (\r\n)
-            let chunk_data_start = chunk_line_end + CRLF_LEN;
-            let chunk_data_end = chunk_data_start + chunk_size;
-
-            if chunk_data_end > total_read {
-                return Err(Error::InvalidResponse("Incomplete chunked body"));
+        // Describe the body framing unless the caller already did
+        match body {
+            Some(BodyFraming::Length(len)) if !framing_present => {
+                try_push!(http_request.push_str(CONTENT_LENGTH));
+                try_push!(http_request.push_str(HEADER_SEPARATOR));
+                let mut len_str = String::<20>::new();
+                if core::fmt::write(&mut len_str, format_args!("{len}")).is_err() {
+                    return Err(Error::BufferOverflow);
+                }
+                try_push!(http_request.push_str(&len_str));
+                try_push!(http_request.push_str(CRLF_STR));
             }
-
-            // Copy chunk data in-place (memmove semantics)
-            if write_pos != chunk_data_start {
-                buffer.copy_within(chunk_data_start..chunk_data_end, write_pos);
+            Some(BodyFraming::Chunked) if !framing_present => {
+                try_push!(http_request.push_str(TRANSFER_ENCODING));
+                try_push!(http_request.push_str(HEADER_SEPARATOR));
+                try_push!(http_request.push_str(CHUNKED));
+                try_push!(http_request.push_str(CRLF_STR));
             }
-            write_pos += chunk_size;
-
-            // Skip past chunk data and trailing \r\n
-            read_pos = chunk_data_end + CRLF_LEN;
-        }
-
-        Ok(write_pos)
-    }
-}
-
-#[cfg(feature = "tls")]
-fn timeseed() -> [u8; 32] {
-    let bytes: [u8; 8] = Instant::now().as_ticks().to_be_bytes();
-    let mut result: [u8; 32] = [0; 32];
-    result[..8].copy_from_slice(&bytes);
-    result
-}
-
-/// Simple `XORShift32` PRNG for TLS seeding.
-/// Good enough for embedded TLS where the seed itself is time-based.
-#[cfg(feature = "tls")]
-struct XorShift32Rng(u32);
-
-#[cfg(feature = "tls")]
-impl XorShift32Rng {
-    const fn new(seed: u32) -> Self {
-        Self(seed)
-    }
-}
-
-#[cfg(feature = "tls")]
-impl RngCore for XorShift32Rng {
-    fn next_u32(&mut self) -> u32 {
-        let mut x = self.0;
-        x ^= x << 13;
-        x ^= x >> 17;
-        x ^= x << 5;
-        self.0 = x;
-        x
-    }
-
-    fn next_u64(&mut self) -> u64 {
-        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
-    }
-
-    fn fill_bytes(&mut self, dest: &mut [u8]) {
-        for chunk in dest.chunks_mut(4) {
-            let val = self.next_u32();
-            chunk.copy_from_slice(&val.to_le_bytes()[..chunk.len()]);
+            _ => {}
         }
-    }
 
-    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
-        self.fill_bytes(dest);
-        Ok(())
+        Ok(http_request)
     }
 }
 
-#[cfg(feature = "tls")]
-impl CryptoRng for XorShift32Rng {}
-
 #[cfg(test)]
 mod tests {
     use super::*;
     use embassy_net::Stack;
 
+    /// Whether the response in `data` is complete without the connection closing
+    fn is_response_complete(data: &[u8]) -> bool {
+        Parser::response(HttpMethod::GET)
+            .parse(data)
+            .unwrap()
+            .is_some()
+    }
+
+    /// Decode the chunked body of the complete response in `buffer`
+    fn dechunk(buffer: &mut [u8], total_read: usize) -> Result<usize, Error> {
+        let message = Parser::response(HttpMethod::GET)
+            .parse(&buffer[..total_read])?
+            .ok_or(Error::NoResponse)?;
+        Ok(message.decode_body(buffer))
+    }
+
     #[test]
     fn test_is_response_complete_no_content_length() {
         // Without Content-Length or chunked, response is never "complete" —
         // the read loop must rely on connection close (Ok(0))
         let data = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n";
-        assert!(!DefaultHttpClient::is_response_complete(data));
+        assert!(!is_response_complete(data));
     }
 
     #[test]
     fn test_is_response_complete_content_length_zero() {
         // Content-Length: 0 means empty body — complete once headers end
         let data = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
-        assert!(DefaultHttpClient::is_response_complete(data));
+        assert!(is_response_complete(data));
     }
 
     #[test]
     fn test_is_response_complete_with_content_length() {
         let data = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
-        assert!(DefaultHttpClient::is_response_complete(data));
+        assert!(is_response_complete(data));
     }
 
     #[test]
     fn test_is_response_complete_incomplete() {
         let data = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort";
-        assert!(!DefaultHttpClient::is_response_complete(data));
+        assert!(!is_response_complete(data));
     }
 
     #[test]
@@ -1034,6 +1207,8 @@ mod tests {
             socket_timeout: embassy_time::Duration::from_secs(1),
             retry_delay: embassy_time::Duration::from_millis(1),
             socket_close_delay: embassy_time::Duration::from_millis(1),
+            keep_alive_timeout: embassy_time::Duration::from_secs(30),
+            redirect_policy: crate::RedirectPolicy::None,
         };
         let client2 = DefaultHttpClient::with_options(unsafe { &*fake_stack }, opts);
         assert_eq!(client.options.max_retries, 5);
@@ -1053,6 +1228,8 @@ mod tests {
                 socket_timeout: embassy_time::Duration::from_secs(2),
                 retry_delay: embassy_time::Duration::from_millis(10),
                 socket_close_delay: embassy_time::Duration::from_millis(5),
+                keep_alive_timeout: embassy_time::Duration::from_secs(30),
+                redirect_policy: crate::RedirectPolicy::None,
             },
         );
         assert_eq!(client_custom.options.max_retries, 3);
@@ -1071,6 +1248,8 @@ mod tests {
                 socket_timeout: embassy_time::Duration::from_secs(1),
                 retry_delay: embassy_time::Duration::from_millis(5),
                 socket_close_delay: embassy_time::Duration::from_millis(2),
+                keep_alive_timeout: embassy_time::Duration::from_secs(30),
+                redirect_policy: crate::RedirectPolicy::None,
             },
         );
         assert_eq!(client_small_custom.options.max_retries, 2);
@@ -1086,7 +1265,7 @@ mod tests {
         data[header.len()..header.len() + binary_body.len()].copy_from_slice(&binary_body);
         let data = &data[..header.len() + binary_body.len()];
 
-        let response = DefaultHttpClient::parse_http_response_zero_copy(data)
+        let response = <DefaultHttpClient>::parse_http_response_zero_copy(data)
             .expect("should parse binary response");
 
         assert_eq!(response.status_code, StatusCode::Ok);
@@ -1097,7 +1276,7 @@ mod tests {
     fn test_parse_http_response_text_body() {
         let data = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello";
 
-        let response = DefaultHttpClient::parse_http_response_zero_copy(data)
+        let response = <DefaultHttpClient>::parse_http_response_zero_copy(data)
             .expect("should parse text response");
 
         assert_eq!(response.status_code, StatusCode::Ok);
@@ -1107,11 +1286,11 @@ mod tests {
     #[test]
     fn test_is_response_complete_chunked() {
         let incomplete = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n";
-        assert!(!DefaultHttpClient::is_response_complete(incomplete));
+        assert!(!is_response_complete(incomplete));
 
         let complete =
             b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
-        assert!(DefaultHttpClient::is_response_complete(complete));
+        assert!(is_response_complete(complete));
     }
 
     #[test]
@@ -1120,10 +1299,9 @@ mod tests {
         let mut buf = [0u8; 256];
         buf[..raw.len()].copy_from_slice(raw);
 
-        let new_len =
-            DefaultHttpClient::dechunk(&mut buf, raw.len()).expect("should decode chunked");
+        let new_len = dechunk(&mut buf, raw.len()).expect("should decode chunked");
 
-        let response = DefaultHttpClient::parse_http_response_zero_copy(&buf[..new_len])
+        let response = <DefaultHttpClient>::parse_http_response_zero_copy(&buf[..new_len])
             .expect("should parse dechunked response");
 
         assert_eq!(response.status_code, StatusCode::Ok);
@@ -1137,10 +1315,9 @@ mod tests {
         let mut buf = [0u8; 256];
         buf[..raw.len()].copy_from_slice(raw);
 
-        let new_len =
-            DefaultHttpClient::dechunk(&mut buf, raw.len()).expect("should decode chunked");
+        let new_len = dechunk(&mut buf, raw.len()).expect("should decode chunked");
 
-        let response = DefaultHttpClient::parse_http_response_zero_copy(&buf[..new_len])
+        let response = <DefaultHttpClient>::parse_http_response_zero_copy(&buf[..new_len])
             .expect("should parse dechunked response");
 
         assert_eq!(response.body.as_str(), Some("{\"temp\":23}"));
@@ -1152,7 +1329,155 @@ mod tests {
         let mut buf = [0u8; 128];
         buf[..raw.len()].copy_from_slice(raw);
 
-        let new_len = DefaultHttpClient::dechunk(&mut buf, raw.len()).expect("should pass through");
+        let new_len = dechunk(&mut buf, raw.len()).expect("should pass through");
         assert_eq!(new_len, raw.len());
     }
+
+    #[test]
+    fn test_build_http_request_connection_header() {
+        let close = <DefaultHttpClient>::build_http_request(
+            HttpMethod::GET,
+            "http",
+            ("example.com", 80),
+            "/",
+            &[],
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(close.ends_with("Connection: close\r\n\r\n"));
+
+        let keep_alive = <DefaultHttpClient>::build_http_request(
+            HttpMethod::GET,
+            "http",
+            ("example.com", 80),
+            "/",
+            &[],
+            None,
+            true,
+        )
+        .unwrap();
+        assert!(keep_alive.starts_with("GET / HTTP/1.1\r\nHost: example.com\r\n"));
+        assert!(keep_alive.ends_with("Connection: keep-alive\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_build_http_request_body_framing() {
+        let length = <DefaultHttpClient>::build_http_request(
+            HttpMethod::POST,
+            "http",
+            ("example.com", 80),
+            "/upload",
+            &[],
+            Some(BodyFraming::Length(1234)),
+            false,
+        )
+        .unwrap();
+        assert!(length.contains("Content-Length: 1234\r\n"));
+        assert!(!length.contains("Transfer-Encoding"));
+
+        let chunked = <DefaultHttpClient>::build_http_request(
+            HttpMethod::POST,
+            "http",
+            ("example.com", 80),
+            "/upload",
+            &[],
+            Some(BodyFraming::Chunked),
+            false,
+        )
+        .unwrap();
+        assert!(chunked.contains("Transfer-Encoding: chunked\r\n"));
+        assert!(!chunked.contains("Content-Length"));
+
+        // Framing supplied by the caller is left alone
+        let custom = <DefaultHttpClient>::build_http_request(
+            HttpMethod::POST,
+            "http",
+            ("example.com", 80),
+            "/upload",
+            &[HttpHeader::new("Content-Length", "5")],
+            Some(BodyFraming::Chunked),
+            false,
+        )
+        .unwrap();
+        assert!(custom.contains("Content-Length: 5\r\n"));
+        assert!(!custom.contains("Transfer-Encoding"));
+    }
+
+    #[test]
+    fn test_connection_target() {
+        let url = Url::parse("http://example.com").unwrap();
+        assert_eq!(
+            <DefaultHttpClient>::connection_target(&url).unwrap(),
+            ("http", ("example.com", 80))
+        );
+        let url = Url::parse("HTTPS://example.com:8443/api?x=1").unwrap();
+        assert_eq!(
+            <DefaultHttpClient>::connection_target(&url).unwrap(),
+            ("https", ("example.com", 8443))
+        );
+        let url = Url::parse("http://[fe80::1]/").unwrap();
+        assert_eq!(
+            <DefaultHttpClient>::connection_target(&url).unwrap(),
+            ("http", ("fe80::1", 80))
+        );
+        let url = Url::parse("ftp://example.com").unwrap();
+        assert!(matches!(
+            <DefaultHttpClient>::connection_target(&url),
+            Err(Error::UnsupportedScheme(_))
+        ));
+    }
+
+    #[test]
+    fn test_build_http_request_host_port() {
+        let host = |scheme, host_port| {
+            let request = <DefaultHttpClient>::build_http_request(
+                HttpMethod::GET,
+                scheme,
+                host_port,
+                "/",
+                &[],
+                None,
+                false,
+            )
+            .unwrap();
+            let host = request.split("\r\n").nth(1).unwrap();
+            std::string::String::from(host)
+        };
+        assert_eq!(host("http", ("example.com", 80)), "Host: example.com");
+        assert_eq!(
+            host("http", ("example.com", 8080)),
+            "Host: example.com:8080"
+        );
+        assert_eq!(host("https", ("example.com", 443)), "Host: example.com");
+        assert_eq!(host("https", ("example.com", 80)), "Host: example.com:80");
+        assert_eq!(host("http", ("fe80::1", 8080)), "Host: [fe80::1]:8080");
+    }
+
+    #[test]
+    fn test_build_http_request_target() {
+        let request = <DefaultHttpClient>::build_http_request(
+            HttpMethod::GET,
+            "http",
+            ("fe80::1", 80),
+            "?x=1",
+            &[],
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(request.starts_with("GET /?x=1 HTTP/1.1\r\nHost: [fe80::1]\r\n"));
+
+        let request = <DefaultHttpClient>::build_http_request(
+            HttpMethod::GET,
+            "http",
+            ("example.com", 80),
+            "",
+            &[],
+            None,
+            false,
+        )
+        .unwrap();
+        assert!(request.starts_with("GET / HTTP/1.1\r\nHost: example.com\r\n"));
+    }
 }
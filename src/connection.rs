@@ -0,0 +1,212 @@
+#[cfg(feature = "tls")]
+use crate::tls::ChaCha20Poly1305Sha256;
+use crate::{
+    client::HttpClient,
+    error::Error,
+    session::SessionBuffers,
+    transport::{Connector, Transport},
+};
+use core::marker::PhantomData;
+use embedded_io_async::{ErrorType, Read, Write};
+#[cfg(feature = "tls")]
+use embedded_tls::{Aes128GcmSha256, Aes256GcmSha384, TlsConnection, TlsError};
+
+/// An open client connection, either plain or TLS over a [`Transport`]
+///
+/// The TLS variant is much larger, but boxing is not an option without an
+/// allocator and a caller only ever holds one connection at a time.
+#[cfg_attr(feature = "tls", expect(clippy::large_enum_variant))]
+pub enum Connection<'s, T: Transport + 's> {
+    /// The marker ties a plain connection to the buffers it was opened with
+    Plain(T, PhantomData<&'s mut [u8]>),
+    #[cfg(feature = "tls")]
+    Tls {
+        tls: TlsStream<'s, T>,
+        /// The server asked for a client certificate and has not sent any
+        /// data since, so it may still reject the certificate
+        client_auth_pending: bool,
+    },
+}
+
+/// A TLS connection over a transport, one variant per [`CipherSuite`](crate::CipherSuite)
+///
+/// The SHA-384 suite keeps a larger transcript hash than the others.
+#[cfg(feature = "tls")]
+#[expect(clippy::large_enum_variant)]
+pub enum TlsStream<'s, T: Transport + 's> {
+    Aes128GcmSha256(TlsConnection<'s, T, Aes128GcmSha256>),
+    Aes256GcmSha384(TlsConnection<'s, T, Aes256GcmSha384>),
+    ChaCha20Poly1305Sha256(TlsConnection<'s, T, ChaCha20Poly1305Sha256>),
+}
+
+/// Evaluate `$body` with `$tls` bound to the connection, whatever its suite
+#[cfg(feature = "tls")]
+macro_rules! with_suite {
+    ($stream:expr, $tls:ident => $body:expr) => {
+        match $stream {
+            TlsStream::Aes128GcmSha256($tls) => $body,
+            TlsStream::Aes256GcmSha384($tls) => $body,
+            TlsStream::ChaCha20Poly1305Sha256($tls) => $body,
+        }
+    };
+}
+
+#[cfg(feature = "tls")]
+impl<T: Transport> TlsStream<'_, T> {
+    #[expect(clippy::future_not_send)]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, TlsError> {
+        with_suite!(self, tls => tls.read(buf).await)
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, TlsError> {
+        with_suite!(self, tls => tls.write(buf).await)
+    }
+
+    async fn flush(&mut self) -> Result<(), TlsError> {
+        with_suite!(self, tls => tls.flush().await)
+    }
+}
+
+impl<T: Transport> Connection<'_, T> {
+    /// Shut down TLS (if used) and hand back the transport, which releases
+    /// the TLS record buffers
+    #[cfg_attr(not(feature = "tls"), expect(clippy::unused_async))]
+    pub async fn into_transport(self) -> T {
+        match self {
+            Self::Plain(transport, _) => transport,
+            #[cfg(feature = "tls")]
+            Self::Tls { tls, .. } => match with_suite!(tls, tls => tls.close().await) {
+                Ok(transport) => transport,
+                Err((transport, e)) => {
+                    debug!("Error closing TLS connection: {:?}", Error::from(e));
+                    transport
+                }
+            },
+        }
+    }
+
+    /// Shut down TLS (if used) and close the transport
+    pub async fn close(self) {
+        self.into_transport().await.close().await;
+    }
+}
+
+impl<T: Transport> ErrorType for Connection<'_, T> {
+    type Error = Error;
+}
+
+#[cfg_attr(feature = "tls", expect(clippy::future_not_send))]
+impl<T: Transport> Read for Connection<'_, T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        match self {
+            Self::Plain(transport, _) => transport.read(buf).await,
+            #[cfg(feature = "tls")]
+            Self::Tls {
+                tls,
+                client_auth_pending,
+            } => match tls.read(buf).await {
+                Ok(n) => {
+                    *client_auth_pending = false;
+                    Ok(n)
+                }
+                // A TLS 1.3 server checks the client certificate after the
+                // handshake, and embedded-tls reports the alert it sends as an
+                // internal error
+                Err(TlsError::InternalError) if *client_auth_pending => {
+                    Err(Error::ClientCertificateRejected)
+                }
+                Err(e) => Err(e.into()),
+            },
+        }
+    }
+}
+
+impl<T: Transport> Write for Connection<'_, T> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match self {
+            Self::Plain(transport, _) => transport.write(buf).await,
+            #[cfg(feature = "tls")]
+            Self::Tls { tls, .. } => Ok(tls.write(buf).await?),
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            Self::Plain(transport, _) => transport.flush().await,
+            #[cfg(feature = "tls")]
+            Self::Tls { tls, .. } => Ok(tls.flush().await?),
+        }
+    }
+}
+
+impl<
+    'a,
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector,
+> HttpClient<'a, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
+    /// Connect to `host_port`, performing the TLS handshake for `https`.
+    ///
+    /// The transport and TLS record buffers are borrowed from `buffers` for
+    /// as long as the connection lives.
+    #[expect(clippy::future_not_send)]
+    pub(crate) async fn open_connection<'s>(
+        &'s self,
+        scheme: &'static str,
+        host_port: (&str, u16),
+        buffers: &'s mut SessionBuffers<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>,
+    ) -> Result<Connection<'s, C::Transport<'s>>, Error>
+    where
+        'a: 's,
+    {
+        let transport = self
+            .open_transport(scheme, host_port, &mut buffers.tcp_rx, &mut buffers.tcp_tx)
+            .await?;
+
+        #[cfg(feature = "tls")]
+        if scheme == "https" {
+            return self
+                .open_tls(
+                    transport,
+                    host_port.0,
+                    &mut buffers.tls_read,
+                    &mut buffers.tls_write,
+                )
+                .await;
+        }
+
+        Ok(Connection::Plain(transport, PhantomData))
+    }
+
+    /// Open the transport to `host_port` that a connection for `scheme`
+    /// runs over, lending it the socket buffers.
+    #[expect(clippy::future_not_send)]
+    pub(crate) async fn open_transport<'s>(
+        &'s self,
+        scheme: &'static str,
+        host_port: (&str, u16),
+        tcp_rx: &'s mut [u8],
+        tcp_tx: &'s mut [u8],
+    ) -> Result<C::Transport<'s>, Error>
+    where
+        'a: 's,
+    {
+        match scheme {
+            "http" => {}
+            #[cfg(feature = "tls")]
+            "https" => {}
+            #[cfg(not(feature = "tls"))]
+            "https" => return Err(Error::UnsupportedScheme("https (TLS support not enabled)")),
+            _ => return Err(Error::UnsupportedScheme(scheme)),
+        }
+
+        let (host, port) = host_port;
+        self.connector
+            .connect(host, port, self.options.socket_timeout, tcp_rx, tcp_tx)
+            .await
+    }
+}
@@ -1,3 +1,5 @@
+use crate::url::UrlError;
+
 /// Errors that can occur during HTTP operations
 ///
 /// This enum represents all possible errors that can be returned by the HTTP client
@@ -14,15 +16,36 @@ pub enum Error {
     IpAddressEmpty,
     /// Failed to establish a TCP connection
     ConnectionError(embassy_net::tcp::ConnectError),
+    /// Failed to accept an incoming TCP connection
+    AcceptError(embassy_net::tcp::AcceptError),
     /// TCP communication error
     TcpError(embassy_net::tcp::Error),
+    /// A host socket operation failed or timed out
+    #[cfg(feature = "std")]
+    Io(embedded_io_async::ErrorKind),
     /// No response was received from the server
     NoResponse,
     /// The server's response could not be parsed
     InvalidResponse(&'static str),
+    /// A request received by the server could not be parsed while it was read
+    /// from the connection. [`HttpRequest`](crate::HttpRequest) and
+    /// [`percent_decode`](crate::percent_decode) report malformed input as
+    /// [`Error::InvalidResponse`].
+    InvalidRequest(&'static str),
     /// This error occurs when there is an issue with the TLS handshake or communication.
     #[cfg(feature = "tls")]
     TlsError(embedded_tls::TlsError),
+    /// The server certificate was rejected by the configured verification
+    #[cfg(feature = "tls")]
+    InvalidCertificate,
+    /// The server asked for a client certificate and rejected the one that
+    /// was presented, or none was configured
+    #[cfg(feature = "tls")]
+    ClientCertificateRejected,
+    /// An HTTPS request or WebSocket connection was made without a random
+    /// number generator configured
+    #[cfg(any(feature = "tls", feature = "websocket"))]
+    MissingRng,
     /// Scheme not supported
     UnsupportedScheme(&'static str),
     /// Header error, e.g. too long name or value
@@ -31,6 +54,17 @@ pub enum Error {
     InvalidStatusCode,
     /// Buffer overflow when building a request or response
     BufferOverflow,
+    /// The persistent connection was closed by the server or has expired
+    ConnectionClosed,
+    /// Reading a streamed request body failed, or it ended before its announced length
+    BodySource(embedded_io_async::ErrorKind),
+    /// The redirect policy's hop limit was reached before a final response
+    TooManyRedirects,
+    /// The URL could not be parsed
+    Url(UrlError),
+    /// The WebSocket handshake failed or the peer violated the protocol
+    #[cfg(feature = "websocket")]
+    WebSocket(&'static str),
 }
 
 #[cfg(feature = "defmt")]
@@ -40,6 +74,12 @@ impl defmt::Format for Error {
     }
 }
 
+impl From<UrlError> for Error {
+    fn from(err: UrlError) -> Self {
+        Self::Url(err)
+    }
+}
+
 impl From<embassy_net::dns::Error> for Error {
     fn from(err: embassy_net::dns::Error) -> Self {
         Self::DnsError(err)
@@ -52,12 +92,25 @@ impl From<embassy_net::tcp::ConnectError> for Error {
     }
 }
 
+impl From<embassy_net::tcp::AcceptError> for Error {
+    fn from(err: embassy_net::tcp::AcceptError) -> Self {
+        Self::AcceptError(err)
+    }
+}
+
 impl From<embassy_net::tcp::Error> for Error {
     fn from(err: embassy_net::tcp::Error) -> Self {
         Self::TcpError(err)
     }
 }
 
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(embedded_io_async::Error::kind(&err))
+    }
+}
+
 #[cfg(feature = "tls")]
 impl From<embedded_tls::TlsError> for Error {
     fn from(err: embedded_tls::TlsError) -> Self {
@@ -72,15 +125,59 @@ impl core::fmt::Display for Error {
             Self::DnsError(_) => write!(f, "DNS resolution failed"),
             Self::IpAddressEmpty => write!(f, "No IP addresses returned by DNS"),
             Self::ConnectionError(_) => write!(f, "Failed to establish TCP connection"),
+            Self::AcceptError(_) => write!(f, "Failed to accept TCP connection"),
             Self::TcpError(_) => write!(f, "TCP communication error"),
+            #[cfg(feature = "std")]
+            Self::Io(kind) => write!(f, "I/O error: {kind:?}"),
             Self::NoResponse => write!(f, "No response received from server"),
             Self::InvalidResponse(msg) => write!(f, "Invalid response: {msg}"),
+            Self::InvalidRequest(msg) => write!(f, "Invalid request: {msg}"),
             #[cfg(feature = "tls")]
             Self::TlsError(_) => write!(f, "TLS error occurred"),
+            #[cfg(feature = "tls")]
+            Self::InvalidCertificate => write!(f, "Server certificate verification failed"),
+            #[cfg(feature = "tls")]
+            Self::ClientCertificateRejected => write!(f, "Client certificate rejected by server"),
+            #[cfg(any(feature = "tls", feature = "websocket"))]
+            Self::MissingRng => write!(f, "No random number generator configured"),
             Self::UnsupportedScheme(scheme) => write!(f, "Unsupported scheme: {scheme}"),
             Self::HeaderError(msg) => write!(f, "Header error: {msg}"),
             Self::InvalidStatusCode => write!(f, "Invalid status code"),
             Self::BufferOverflow => write!(f, "Buffer overflow"),
+            Self::ConnectionClosed => write!(f, "Connection closed"),
+            Self::BodySource(kind) => write!(f, "Failed to read request body: {kind:?}"),
+            Self::TooManyRedirects => write!(f, "Too many redirects"),
+            Self::Url(e) => write!(f, "Invalid URL: {e}"),
+            #[cfg(feature = "websocket")]
+            Self::WebSocket(msg) => write!(f, "WebSocket error: {msg}"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl embedded_io_async::Error for Error {
+    fn kind(&self) -> embedded_io_async::ErrorKind {
+        use embedded_io_async::ErrorKind;
+
+        match self {
+            Self::TcpError(e) => e.kind(),
+            #[cfg(feature = "std")]
+            Self::Io(kind) => *kind,
+            #[cfg(feature = "tls")]
+            Self::TlsError(e) => embedded_io_async::Error::kind(e),
+            #[cfg(feature = "tls")]
+            Self::ClientCertificateRejected => ErrorKind::PermissionDenied,
+            Self::ConnectionError(_) => ErrorKind::ConnectionRefused,
+            Self::ConnectionClosed => ErrorKind::NotConnected,
+            Self::InvalidResponse(_) | Self::InvalidRequest(_) | Self::InvalidStatusCode => {
+                ErrorKind::InvalidData
+            }
+            #[cfg(feature = "websocket")]
+            Self::WebSocket(_) => ErrorKind::InvalidData,
+            Self::BufferOverflow => ErrorKind::OutOfMemory,
+            Self::BodySource(kind) => *kind,
+            _ => ErrorKind::Other,
         }
     }
 }
@@ -99,14 +196,68 @@ mod tests {
         assert_eq!(format!("{e}"), "No IP addresses returned by DNS");
         let e = Error::NoResponse;
         assert_eq!(format!("{e}"), "No response received from server");
+        let e = Error::from(tcp::AcceptError::InvalidPort);
+        assert_eq!(format!("{e}"), "Failed to accept TCP connection");
         let e = Error::InvalidResponse("bad");
         assert_eq!(format!("{e}"), "Invalid response: bad");
+        let e = Error::InvalidRequest("bad");
+        assert_eq!(format!("{e}"), "Invalid request: bad");
         let e = Error::UnsupportedScheme("ftp");
         assert_eq!(format!("{e}"), "Unsupported scheme: ftp");
         let e = Error::HeaderError("too long");
         assert_eq!(format!("{e}"), "Header error: too long");
         let e = Error::InvalidStatusCode;
         assert_eq!(format!("{e}"), "Invalid status code");
+        let e = Error::ConnectionClosed;
+        assert_eq!(format!("{e}"), "Connection closed");
+        let e = Error::BodySource(embedded_io_async::ErrorKind::InvalidInput);
+        assert_eq!(format!("{e}"), "Failed to read request body: InvalidInput");
+        let e = Error::TooManyRedirects;
+        assert_eq!(format!("{e}"), "Too many redirects");
+        let e = Error::from(UrlError::InvalidPort);
+        assert_eq!(format!("{e}"), "Invalid URL: invalid port");
+        #[cfg(feature = "tls")]
+        {
+            let e = Error::InvalidCertificate;
+            assert_eq!(format!("{e}"), "Server certificate verification failed");
+            let e = Error::ClientCertificateRejected;
+            assert_eq!(format!("{e}"), "Client certificate rejected by server");
+        }
+        #[cfg(any(feature = "tls", feature = "websocket"))]
+        {
+            let e = Error::MissingRng;
+            assert_eq!(format!("{e}"), "No random number generator configured");
+        }
+        #[cfg(feature = "websocket")]
+        {
+            let e = Error::WebSocket("bad frame");
+            assert_eq!(format!("{e}"), "WebSocket error: bad frame");
+        }
+        #[cfg(feature = "std")]
+        {
+            let e = Error::from(std::io::Error::from(std::io::ErrorKind::TimedOut));
+            assert_eq!(format!("{e}"), "I/O error: TimedOut");
+        }
+    }
+
+    #[test]
+    fn test_io_error_kind() {
+        use embedded_io_async::{Error as _, ErrorKind};
+
+        assert_eq!(
+            Error::TcpError(tcp::Error::ConnectionReset).kind(),
+            ErrorKind::ConnectionReset
+        );
+        assert_eq!(Error::ConnectionClosed.kind(), ErrorKind::NotConnected);
+        assert_eq!(Error::InvalidResponse("bad").kind(), ErrorKind::InvalidData);
+        assert_eq!(Error::InvalidRequest("bad").kind(), ErrorKind::InvalidData);
+        assert_eq!(Error::BufferOverflow.kind(), ErrorKind::OutOfMemory);
+        assert_eq!(Error::InvalidUrl.kind(), ErrorKind::Other);
+        #[cfg(feature = "std")]
+        assert_eq!(
+            Error::from(std::io::Error::from(std::io::ErrorKind::ConnectionRefused)).kind(),
+            ErrorKind::ConnectionRefused
+        );
     }
 
     #[test]
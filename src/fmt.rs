@@ -68,3 +68,26 @@ macro_rules! error {
         }
     };
 }
+
+/// A logger that drops everything, so that tests which run code logging with
+/// `defmt` link on the host
+#[cfg(all(test, feature = "defmt"))]
+mod test_logger {
+    #[defmt::global_logger]
+    struct Logger;
+
+    // SAFETY: no state is shared, every call does nothing
+    unsafe impl defmt::Logger for Logger {
+        fn acquire() {}
+        unsafe fn flush() {}
+        unsafe fn release() {}
+        unsafe fn write(_bytes: &[u8]) {}
+    }
+
+    defmt::timestamp!("{=u64}", 0);
+
+    #[defmt::panic_handler]
+    fn panic() -> ! {
+        std::process::abort()
+    }
+}
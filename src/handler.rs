@@ -4,7 +4,9 @@ use crate::{
     request::HttpRequest,
     response::{HttpResponse, ResponseBody},
     status_code::StatusCode,
+    writer::ResponseWriter,
 };
+use embedded_io_async::{Read, Write};
 use heapless::Vec;
 
 /// Trait for handling HTTP requests
@@ -17,8 +19,75 @@ pub trait HttpHandler {
     ) -> Result<HttpResponse<'_>, Error>;
 }
 
+/// Trait for handling HTTP requests by writing the response to the connection
+///
+/// Unlike with [`HttpHandler`], the response is not buffered: the handler
+/// sends the status and headers and then writes the body in as many pieces
+/// as it likes, so responses can be larger than any buffer. Every
+/// [`HttpHandler`] is also a `StreamingHandler` that sends its response with
+/// [`ResponseWriter::send`].
+///
+/// # Examples
+///
+/// ```
+/// use core::fmt::Write as _;
+/// use embedded_io_async::{Read, Write};
+/// use nanofish::{
+///     Error, HttpHeader, HttpRequest, ResponseWriter, StatusCode, StreamingHandler, mime_types,
+/// };
+///
+/// struct History<'a> {
+///     samples: &'a [f32],
+/// }
+///
+/// impl StreamingHandler for History<'_> {
+///     async fn handle_streaming<W: Read + Write<Error = Error>>(
+///         &mut self,
+///         _request: &HttpRequest<'_>,
+///         response: &mut ResponseWriter<'_, W>,
+///     ) -> Result<(), Error> {
+///         let headers = [HttpHeader::content_type(mime_types::TEXT)];
+///         // No length given, so the body is sent with chunked encoding
+///         let mut body = response.start(StatusCode::Ok, &headers, None).await?;
+///         for sample in self.samples {
+///             let mut line = heapless::String::<16>::new();
+///             writeln!(line, "{sample:.2}").map_err(|_| Error::BufferOverflow)?;
+///             body.write_all(line.as_bytes()).await?;
+///         }
+///         body.finish().await
+///     }
+/// }
+/// ```
+#[allow(async_fn_in_trait)]
+pub trait StreamingHandler {
+    /// Handle an incoming HTTP request by writing a response to `response`
+    ///
+    /// The response is complete once [`BodyWriter::finish`](crate::BodyWriter::finish)
+    /// returns. If the handler fails before starting the response the server
+    /// answers with 500 Internal Server Error; a response that was started
+    /// but not finished closes the connection. The connection `W` is also
+    /// readable, so long-lived responses such as event streams can notice the
+    /// client closing it.
+    async fn handle_streaming<W: Read + Write<Error = Error>>(
+        &mut self,
+        request: &HttpRequest<'_>,
+        response: &mut ResponseWriter<'_, W>,
+    ) -> Result<(), Error>;
+}
+
+impl<H: HttpHandler> StreamingHandler for H {
+    async fn handle_streaming<W: Read + Write<Error = Error>>(
+        &mut self,
+        request: &HttpRequest<'_>,
+        response: &mut ResponseWriter<'_, W>,
+    ) -> Result<(), Error> {
+        let http_response = self.handle_request(request).await?;
+        response.send(&http_response).await
+    }
+}
+
 /// A simple handler that serves basic endpoints for testing
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct SimpleHandler;
 
 impl HttpHandler for SimpleHandler {
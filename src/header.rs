@@ -20,6 +20,24 @@ pub mod headers {
     pub const X_API_KEY: &str = "X-API-Key";
     /// Accept-Encoding header
     pub const ACCEPT_ENCODING: &str = "Accept-Encoding";
+    /// Cookie header
+    pub const COOKIE: &str = "Cookie";
+    /// Location header, the target of a redirect
+    pub const LOCATION: &str = "Location";
+    /// Allow header, listing the methods a resource supports
+    pub const ALLOW: &str = "Allow";
+    /// Last-Event-ID header, sent by reconnecting event stream clients
+    pub const LAST_EVENT_ID: &str = "Last-Event-ID";
+    /// Upgrade header, naming the protocol to switch to
+    pub const UPGRADE: &str = "Upgrade";
+    /// Sec-WebSocket-Key header of a WebSocket handshake request
+    pub const SEC_WEBSOCKET_KEY: &str = "Sec-WebSocket-Key";
+    /// Sec-WebSocket-Accept header of a WebSocket handshake response
+    pub const SEC_WEBSOCKET_ACCEPT: &str = "Sec-WebSocket-Accept";
+    /// Sec-WebSocket-Version header of a WebSocket handshake
+    pub const SEC_WEBSOCKET_VERSION: &str = "Sec-WebSocket-Version";
+    /// Sec-WebSocket-Protocol header, negotiating a WebSocket subprotocol
+    pub const SEC_WEBSOCKET_PROTOCOL: &str = "Sec-WebSocket-Protocol";
 }
 
 /// Common MIME types for Content-Type header values
@@ -0,0 +1,236 @@
+use crate::{
+    error::Error,
+    transport::{Acceptor, Connector, Transport},
+};
+use embassy_time::{Duration, with_timeout};
+use embedded_io_adapters::tokio_1::FromTokio;
+use embedded_io_async::{ErrorKind, ErrorType, Read, Write};
+use std::net::SocketAddr;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+};
+
+/// A [`Connector`] opening TCP connections with tokio
+///
+/// Host names are resolved by the operating system. The client must be driven
+/// by a tokio runtime with I/O enabled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use nanofish::{HttpClientOptions, SmallHttpClient, TokioConnector};
+///
+/// async fn health() -> Result<bool, nanofish::Error> {
+///     let client = SmallHttpClient::with_connector(TokioConnector, HttpClientOptions::default());
+///     let mut buffer = [0u8; 4096];
+///     let (response, _) = client.get("http://localhost:8080/health", &[], &mut buffer).await?;
+///     Ok(response.is_success())
+/// }
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioConnector;
+
+impl Connector for TokioConnector {
+    type Transport<'b> = TokioTransport;
+
+    async fn connect<'b>(
+        &'b self,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+        _rx_buffer: &'b mut [u8],
+        _tx_buffer: &'b mut [u8],
+    ) -> Result<TokioTransport, Error> {
+        let stream = with_timeout(timeout, TcpStream::connect((host, port)))
+            .await
+            .map_err(|_| Error::Io(ErrorKind::TimedOut))??;
+        let remote = stream.peer_addr()?;
+        Ok(TokioTransport {
+            remote: Some(remote),
+            ..TokioTransport::new(stream, Some(timeout))
+        })
+    }
+}
+
+/// Accepts connections on a listener that is already bound
+///
+/// The listener's binding and blocking behavior take precedence over the
+/// server's settings: its own address is used, and the port the
+/// [`HttpServer`](crate::HttpServer) was created with is ignored. Accepting
+/// waits for the next client as long as it takes, so
+/// [`ServerTimeouts::accept_timeout`](crate::ServerTimeouts::accept_timeout)
+/// does not apply either.
+impl Acceptor for TcpListener {
+    type Transport<'b> = TokioTransport;
+
+    async fn accept<'b>(
+        &'b self,
+        _port: u16,
+        _timeout: Duration,
+        _rx_buffer: &'b mut [u8],
+        _tx_buffer: &'b mut [u8],
+    ) -> Result<TokioTransport, Error> {
+        let (stream, _) = Self::accept(self).await?;
+        Ok(TokioTransport::new(stream, None))
+    }
+}
+
+/// A tokio TCP stream, opened by [`TokioConnector`] or accepted on a
+/// [`TcpListener`]
+///
+/// The socket buffers lent by the client and server are not needed, as the
+/// operating system buffers the stream itself. Streams opened by
+/// [`TokioConnector`] can [`reconnect`](Transport::reconnect) to the address
+/// they were opened to.
+pub struct TokioTransport {
+    stream: FromTokio<TcpStream>,
+    timeout: Option<Duration>,
+    /// The address connected to, if the transport can reconnect to it
+    remote: Option<SocketAddr>,
+}
+
+impl TokioTransport {
+    /// Wrap a connected stream, bounding each read and write by `timeout`
+    #[must_use]
+    pub fn new(stream: TcpStream, timeout: Option<Duration>) -> Self {
+        Self {
+            stream: FromTokio::new(stream),
+            timeout,
+            remote: None,
+        }
+    }
+
+    /// Run an I/O operation under the transport's timeout
+    async fn io<T>(
+        timeout: Option<Duration>,
+        operation: impl Future<Output = Result<T, std::io::Error>>,
+    ) -> Result<T, Error> {
+        let result = match timeout {
+            Some(timeout) => with_timeout(timeout, operation)
+                .await
+                .map_err(|_| Error::Io(ErrorKind::TimedOut))?,
+            None => operation.await,
+        };
+        Ok(result?)
+    }
+}
+
+impl ErrorType for TokioTransport {
+    type Error = Error;
+}
+
+impl Read for TokioTransport {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        Self::io(self.timeout, self.stream.read(buf)).await
+    }
+}
+
+impl Write for TokioTransport {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        Self::io(self.timeout, self.stream.write(buf)).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        Self::io(self.timeout, self.stream.flush()).await
+    }
+}
+
+impl Transport for TokioTransport {
+    async fn close(mut self) {
+        let _ = self.stream.inner_mut().shutdown().await;
+    }
+
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        let remote = self.remote.ok_or(Error::ConnectionClosed)?;
+        let _ = self.stream.inner_mut().shutdown().await;
+        let stream = Self::io(self.timeout, TcpStream::connect(remote)).await?;
+        self.stream = FromTokio::new(stream);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HttpClientOptions, ResponseBody, SimpleHandler, SmallHttpClient, SmallHttpServer};
+    use embassy_futures::select::{Either, select};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    #[test]
+    fn test_client_and_server_over_loopback() {
+        block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}/health", listener.local_addr().unwrap());
+            let mut server = SmallHttpServer::new(0);
+            let client = SmallHttpClient::with_connector(
+                TokioConnector,
+                HttpClientOptions {
+                    socket_close_delay: Duration::from_ticks(0),
+                    ..HttpClientOptions::default()
+                },
+            );
+
+            let mut buffer = [0u8; 1024];
+            let result = select(
+                Box::pin(server.serve(&listener, SimpleHandler)),
+                Box::pin(client.get(&url, &[], &mut buffer)),
+            )
+            .await;
+            let Either::Second(result) = result;
+            let (response, _) = result.unwrap();
+            assert!(response.is_success());
+            assert_eq!(response.body, ResponseBody::Text("{\"status\":\"ok\"}"));
+        });
+    }
+
+    #[test]
+    fn test_connection_refused() {
+        block_on(async {
+            // Bind and drop a listener to find a port nobody is listening on
+            let port = TcpListener::bind("127.0.0.1:0")
+                .await
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let result = TokioConnector
+                .connect("127.0.0.1", port, Duration::from_secs(5), &mut [], &mut [])
+                .await;
+            assert!(matches!(
+                result,
+                Err(Error::Io(ErrorKind::ConnectionRefused))
+            ));
+        });
+    }
+
+    #[test]
+    fn test_reconnect() {
+        block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let mut transport = TokioConnector
+                .connect("127.0.0.1", port, Duration::from_secs(5), &mut [], &mut [])
+                .await
+                .unwrap();
+            let (first, _) = listener.accept().await.unwrap();
+            drop(first);
+
+            transport.reconnect().await.unwrap();
+            let (mut second, _) = listener.accept().await.unwrap();
+            transport.write_all(b"again").await.unwrap();
+            let mut received = [0u8; 5];
+            tokio::io::AsyncReadExt::read_exact(&mut second, &mut received)
+                .await
+                .unwrap();
+            assert_eq!(&received, b"again");
+        });
+    }
+}
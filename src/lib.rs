@@ -1,5 +1,18 @@
-#![cfg_attr(not(test), no_std)]
-#![doc = include_str!("../README.md")]
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+// The README examples use every optional API, so they are only compiled as
+// doc tests when all of the features are enabled
+#![cfg_attr(
+    any(
+        not(doctest),
+        all(
+            feature = "tls",
+            feature = "websocket",
+            feature = "std",
+            feature = "testing"
+        )
+    ),
+    doc = include_str!("../README.md")
+)]
 #![warn(missing_docs)]
 
 /// Logging macros
@@ -8,6 +21,9 @@ pub(crate) mod fmt;
 /// HTTP protocol constants and shared utilities.
 pub mod protocol;
 
+/// Client transport connections.
+pub(crate) mod connection;
+
 /// HTTP client implementation and request logic.
 pub mod client;
 /// Error types for HTTP operations.
@@ -16,26 +32,79 @@ pub mod error;
 pub mod handler;
 /// HTTP header types and helpers.
 pub mod header;
+/// Adapters for running the client and server on a host operating system.
+#[cfg(feature = "std")]
+pub mod host;
 /// HTTP method enum and helpers.
 pub mod method;
 /// HTTP client configuration options.
 pub mod options;
+/// Incremental HTTP/1.1 message parser.
+pub(crate) mod parser;
+/// Automatic redirect following.
+pub mod redirect;
 /// HTTP request types and parsing.
 pub mod request;
 /// HTTP response types and body handling.
 pub mod response;
+/// Request routing for the HTTP server.
+pub mod router;
 /// HTTP server implementation.
 pub mod server;
+/// Persistent keep-alive client connections.
+pub mod session;
+/// Server-sent events.
+pub mod sse;
 /// Predefined HTTP status codes as per RFC 2616.
 pub mod status_code;
+/// Streaming request and response bodies.
+pub mod stream;
+/// Scripted connections for testing without a network.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+/// TLS options and server certificate verification.
+#[cfg(feature = "tls")]
+pub mod tls;
+/// TLS 1.3 for the HTTP server.
+#[cfg(feature = "tls")]
+pub(crate) mod tls_server;
+/// Pluggable client and server transports.
+pub mod transport;
+/// URL parsing.
+pub mod url;
+/// WebSocket connections for the HTTP server.
+#[cfg(feature = "websocket")]
+pub mod websocket;
+/// Streaming responses for the HTTP server.
+pub mod writer;
 
 pub use client::{DefaultHttpClient, HttpClient, SmallHttpClient};
 pub use error::Error;
-pub use handler::{HttpHandler, SimpleHandler};
+pub use handler::{HttpHandler, SimpleHandler, StreamingHandler};
 pub use header::{HttpHeader, headers, mime_types};
+#[cfg(feature = "std")]
+pub use host::{TokioConnector, TokioTransport};
 pub use method::HttpMethod;
 pub use options::HttpClientOptions;
+pub use redirect::RedirectPolicy;
 pub use request::{HttpRequest, QueryPair, QueryPairs, QueryValues, percent_decode};
 pub use response::{HttpResponse, ResponseBody};
-pub use server::{DefaultHttpServer, HttpServer, ServerTimeouts, SmallHttpServer};
+pub use router::{PathParams, RouteFn, RouteHandler, Router};
+pub use server::{
+    DefaultHttpServer, DefaultServerBuffers, HttpServer, ServerBuffers, ServerTimeouts,
+    SmallHttpServer, SmallServerBuffers,
+};
+pub use session::{HttpSession, SessionBuffers};
+pub use sse::{Event, EventReader, EventSink};
 pub use status_code::StatusCode;
+pub use stream::{BodyReader, StreamingResponse};
+#[cfg(feature = "tls")]
+pub use tls::{
+    CipherSuite, InsecureTimeSeededRng, MaxFragmentLength, PreSharedKey, ServerVerification,
+    TimeSeededRng, TlsIdentity, TlsOptions,
+};
+pub use transport::{Acceptor, Connector, TcpConnector, TcpTransport, Transport};
+pub use url::{Url, UrlError};
+#[cfg(feature = "websocket")]
+pub use websocket::{ClientConnection, CloseFrame, Message, OpCode, WebSocket, close_codes};
+pub use writer::{BodyWriter, ResponseWriter};
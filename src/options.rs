@@ -1,3 +1,4 @@
+use crate::redirect::RedirectPolicy;
 use embassy_time::Duration;
 
 /// Options for configuring the HTTP client
@@ -10,6 +11,12 @@ pub struct HttpClientOptions {
     pub retry_delay: Duration,
     /// Delay after closing a socket before proceeding
     pub socket_close_delay: Duration,
+    /// How long an [`HttpSession`](crate::HttpSession) may sit idle before
+    /// its connection is considered expired
+    pub keep_alive_timeout: Duration,
+    /// Which redirects [`HttpClient::request`](crate::HttpClient::request)
+    /// follows automatically
+    pub redirect_policy: RedirectPolicy,
 }
 
 impl Default for HttpClientOptions {
@@ -19,6 +26,8 @@ impl Default for HttpClientOptions {
             socket_timeout: Duration::from_secs(60),
             retry_delay: Duration::from_millis(200),
             socket_close_delay: Duration::from_millis(100),
+            keep_alive_timeout: Duration::from_secs(30),
+            redirect_policy: RedirectPolicy::None,
         }
     }
 }
@@ -35,6 +44,8 @@ mod tests {
         assert_eq!(opts.socket_timeout, Duration::from_secs(60));
         assert_eq!(opts.retry_delay, Duration::from_millis(200));
         assert_eq!(opts.socket_close_delay, Duration::from_millis(100));
+        assert_eq!(opts.keep_alive_timeout, Duration::from_secs(30));
+        assert_eq!(opts.redirect_policy, RedirectPolicy::None);
     }
 
     #[test]
@@ -44,10 +55,17 @@ mod tests {
             socket_timeout: Duration::from_secs(10),
             retry_delay: Duration::from_millis(50),
             socket_close_delay: Duration::from_millis(20),
+            keep_alive_timeout: Duration::from_secs(5),
+            redirect_policy: RedirectPolicy::SameOrigin { max_hops: 3 },
         };
         assert_eq!(opts.max_retries, 2);
         assert_eq!(opts.socket_timeout, Duration::from_secs(10));
         assert_eq!(opts.retry_delay, Duration::from_millis(50));
         assert_eq!(opts.socket_close_delay, Duration::from_millis(20));
+        assert_eq!(opts.keep_alive_timeout, Duration::from_secs(5));
+        assert_eq!(
+            opts.redirect_policy,
+            RedirectPolicy::SameOrigin { max_hops: 3 }
+        );
     }
 }
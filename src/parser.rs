@@ -0,0 +1,750 @@
+//! Both the client and the server read HTTP/1.1 messages with this parser.
+//!
+//! Messages are parsed strictly as RFC 9112 describes them. The parser is
+//! fed the bytes of one message as they arrive and never looks at a byte
+//! twice: each call resumes where the previous one stopped. It does not copy
+//! anything; it validates the head, works out how the body is framed and
+//! reports where the message ends, leaving the bytes in the caller's buffer.
+
+use crate::{
+    error::Error,
+    header::headers::{CONNECTION, CONTENT_LENGTH},
+    method::HttpMethod,
+    protocol::{self, CHUNKED, CLOSE, HTTP_VERSION_1_0, KEEP_ALIVE, TRANSFER_ENCODING},
+};
+
+/// How the end of a message body is delimited on the wire
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    /// The body is exactly this many bytes long
+    Length(usize),
+    /// The body uses chunked transfer encoding
+    Chunked,
+    /// The body extends until the sender closes the connection
+    UntilClose,
+}
+
+/// Position within a message body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyState {
+    /// This many body bytes remain
+    Length(usize),
+    /// The body continues until the connection is closed
+    UntilClose,
+    /// Reading the hex digits of a chunk size
+    ChunkSize { size: usize, digits: bool },
+    /// Skipping chunk extensions after the size
+    ChunkExtension(usize),
+    /// Expecting the LF that ends a chunk size line
+    ChunkSizeLf(usize),
+    /// This many bytes of the current chunk remain
+    ChunkData(usize),
+    /// Expecting the CRLF that follows chunk data
+    ChunkDataCr,
+    /// Expecting the LF that follows chunk data
+    ChunkDataLf,
+    /// Skipping trailer fields after the last chunk
+    Trailer { line_start: bool },
+    /// Expecting the LF that ends a trailer line
+    TrailerLf { line_start: bool },
+    /// The whole body has been read
+    Done,
+}
+
+impl BodyState {
+    /// The state at the start of a body framed by `framing`
+    pub const fn new(framing: BodyFraming) -> Self {
+        match framing {
+            BodyFraming::Length(0) => Self::Done,
+            BodyFraming::Length(len) => Self::Length(len),
+            BodyFraming::Chunked => Self::ChunkSize {
+                size: 0,
+                digits: false,
+            },
+            BodyFraming::UntilClose => Self::UntilClose,
+        }
+    }
+
+    /// Advance the chunked framing state by one byte of framing data.
+    pub fn next(self, byte: u8) -> Result<Self, &'static str> {
+        const INVALID: &str = "Invalid chunked encoding";
+
+        Ok(match (self, byte) {
+            (Self::ChunkSize { size, .. }, b'0'..=b'9' | b'a'..=b'f' | b'A'..=b'F') => {
+                let digit = match byte {
+                    b'0'..=b'9' => byte - b'0',
+                    b'a'..=b'f' => byte - b'a' + 10,
+                    _ => byte - b'A' + 10,
+                };
+                let size = size
+                    .checked_mul(16)
+                    .and_then(|size| size.checked_add(usize::from(digit)))
+                    .ok_or("Chunk size too large")?;
+                Self::ChunkSize { size, digits: true }
+            }
+            (Self::ChunkSize { size, digits: true } | Self::ChunkExtension(size), b'\r') => {
+                Self::ChunkSizeLf(size)
+            }
+            // Lines end with CRLF, never with a bare LF
+            (Self::ChunkExtension(_) | Self::Trailer { .. }, b'\n') => return Err(INVALID),
+            (Self::ChunkSize { size, digits: true }, b';' | b' ' | b'\t')
+            | (Self::ChunkExtension(size), _) => Self::ChunkExtension(size),
+            // The last chunk, or the end of a trailer field, starts a new trailer line
+            (Self::ChunkSizeLf(0) | Self::TrailerLf { line_start: false }, b'\n') => {
+                Self::Trailer { line_start: true }
+            }
+            (Self::ChunkSizeLf(size), b'\n') => Self::ChunkData(size),
+            (Self::ChunkDataCr, b'\r') => Self::ChunkDataLf,
+            (Self::ChunkDataLf, b'\n') => Self::ChunkSize {
+                size: 0,
+                digits: false,
+            },
+            (Self::Trailer { line_start }, b'\r') => Self::TrailerLf { line_start },
+            (Self::Trailer { .. }, _) => Self::Trailer { line_start: false },
+            (Self::TrailerLf { line_start: true }, b'\n') => Self::Done,
+            _ => return Err(INVALID),
+        })
+    }
+}
+
+/// The start line and header section of a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Head {
+    /// Length of the head, including the blank line that ends it
+    pub len: usize,
+    /// How the body following the head is delimited
+    pub framing: BodyFraming,
+    /// Whether the connection may carry another message afterwards
+    pub keep_alive: bool,
+}
+
+/// A complete message found by [`Parser`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message {
+    /// The message head
+    pub head: Head,
+    /// Length of the whole message on the wire
+    pub len: usize,
+}
+
+impl Message {
+    /// Decode a chunked body in place.
+    ///
+    /// `buffer` holds the message at its start, as validated by the
+    /// [`Parser`] that found it. Returns the length of the head and the
+    /// decoded body, which is the message length for bodies that are not
+    /// chunked.
+    pub fn decode_body(&self, buffer: &mut [u8]) -> usize {
+        if self.head.framing != BodyFraming::Chunked {
+            return self.len;
+        }
+
+        let mut state = BodyState::new(BodyFraming::Chunked);
+        let mut read = self.head.len;
+        let mut write = self.head.len;
+        while read < self.len {
+            state = match state {
+                BodyState::ChunkData(remaining) => {
+                    let n = remaining.min(self.len - read);
+                    buffer.copy_within(read..read + n, write);
+                    read += n;
+                    write += n;
+                    match remaining - n {
+                        0 => BodyState::ChunkDataCr,
+                        left => BodyState::ChunkData(left),
+                    }
+                }
+                state => {
+                    // The parser has already checked the framing
+                    let Ok(next) = state.next(buffer[read]) else {
+                        break;
+                    };
+                    read += 1;
+                    next
+                }
+            };
+        }
+        write
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    StartLine,
+    FieldLine,
+    Body(BodyState),
+}
+
+/// Resumable parser for one HTTP/1.1 request or response
+///
+/// Each call to [`Parser::parse`] or [`Parser::parse_head`] is given all
+/// bytes of the message received so far, starting at the message start, and
+/// continues from where the previous call stopped.
+#[derive(Debug)]
+pub struct Parser {
+    /// The method of the request a response answers; `None` for requests
+    request_method: Option<HttpMethod>,
+    state: State,
+    /// Number of bytes examined so far
+    pos: usize,
+    /// Start of the head line being read
+    line_start: usize,
+    head: Option<Head>,
+    http_1_0: bool,
+    status: u16,
+    content_length: Option<usize>,
+    /// Whether the final transfer coding is chunked, once
+    /// `Transfer-Encoding` has been seen
+    transfer_encoding: Option<bool>,
+    close: bool,
+    keep_alive: bool,
+}
+
+impl Parser {
+    const fn new(request_method: Option<HttpMethod>) -> Self {
+        Self {
+            request_method,
+            state: State::StartLine,
+            pos: 0,
+            line_start: 0,
+            head: None,
+            http_1_0: false,
+            status: 0,
+            content_length: None,
+            transfer_encoding: None,
+            close: false,
+            keep_alive: false,
+        }
+    }
+
+    /// Create a parser for a request
+    pub const fn request() -> Self {
+        Self::new(None)
+    }
+
+    /// Create a parser for the response to a `method` request
+    pub const fn response(method: HttpMethod) -> Self {
+        Self::new(Some(method))
+    }
+
+    /// Parse the message head.
+    ///
+    /// Returns the head once its terminating blank line has arrived.
+    pub fn parse_head(&mut self, data: &[u8]) -> Result<Option<Head>, Error> {
+        while self.head.is_none() {
+            let Some(lf) = data[self.pos..].iter().position(|&byte| byte == b'\n') else {
+                self.pos = data.len();
+                return Ok(None);
+            };
+            let line_end = self.pos + lf;
+            self.pos = line_end + 1;
+
+            let line = data[self.line_start..line_end]
+                .strip_suffix(b"\r")
+                .filter(|line| !line.contains(&b'\r'))
+                .ok_or_else(|| self.error("Line not terminated by CRLF"))?;
+            self.line_start = self.pos;
+            self.line(line).map_err(|message| self.error(message))?;
+        }
+        Ok(self.head)
+    }
+
+    /// Parse the message.
+    ///
+    /// Returns the message once it is complete. A body that extends until
+    /// the connection closes is never complete; see [`Parser::finish`].
+    pub fn parse(&mut self, data: &[u8]) -> Result<Option<Message>, Error> {
+        let Some(head) = self.parse_head(data)? else {
+            return Ok(None);
+        };
+
+        while let State::Body(state) = self.state {
+            let available = data.len() - self.pos;
+            self.state = State::Body(match state {
+                BodyState::Done => {
+                    return Ok(Some(Message {
+                        head,
+                        len: self.pos,
+                    }));
+                }
+                _ if available == 0 => return Ok(None),
+                BodyState::UntilClose => {
+                    self.pos = data.len();
+                    return Ok(None);
+                }
+                BodyState::Length(remaining) | BodyState::ChunkData(remaining) => {
+                    let n = remaining.min(available);
+                    self.pos += n;
+                    match (state, remaining - n) {
+                        (BodyState::Length(_), 0) => BodyState::Done,
+                        (BodyState::Length(_), left) => BodyState::Length(left),
+                        (_, 0) => BodyState::ChunkDataCr,
+                        (_, left) => BodyState::ChunkData(left),
+                    }
+                }
+                state => {
+                    let byte = data[self.pos];
+                    self.pos += 1;
+                    state.next(byte).map_err(|message| self.error(message))?
+                }
+            });
+        }
+        Ok(None)
+    }
+
+    /// The sender closed the connection after the data last parsed.
+    ///
+    /// Returns the message if its body extends until the connection closes.
+    pub const fn finish(&self) -> Result<Message, Error> {
+        match (self.head, self.state) {
+            (Some(head), State::Body(BodyState::UntilClose)) => Ok(Message {
+                head,
+                len: self.pos,
+            }),
+            _ => Err(self.error("Connection closed before message was complete")),
+        }
+    }
+
+    /// The error for an invalid message, depending on whether it is a
+    /// request or a response
+    const fn error(&self, message: &'static str) -> Error {
+        if self.request_method.is_some() {
+            Error::InvalidResponse(message)
+        } else {
+            Error::InvalidRequest(message)
+        }
+    }
+
+    /// The framing of the message body, once the head is complete
+    pub fn framing(&self) -> Option<BodyFraming> {
+        self.head.map(|head| head.framing)
+    }
+
+    /// Handle a complete head line, without its CRLF.
+    fn line(&mut self, line: &[u8]) -> Result<(), &'static str> {
+        let line = core::str::from_utf8(line).map_err(|_| "Invalid UTF-8 in message head")?;
+
+        match self.state {
+            // Servers ignore empty lines before a request line
+            State::StartLine if line.is_empty() && self.request_method.is_none() => {}
+            State::StartLine => {
+                let version = if self.request_method.is_some() {
+                    let (version, status) = parse_status_line(line)?;
+                    self.status = status;
+                    version
+                } else {
+                    parse_request_line(line)?.2
+                };
+                self.http_1_0 = version == HTTP_VERSION_1_0;
+                self.state = State::FieldLine;
+            }
+            State::FieldLine if line.is_empty() => self.end_head()?,
+            State::FieldLine => {
+                let (name, value) = parse_field_line(line)?;
+                self.field(name, value)?;
+            }
+            State::Body(_) => {}
+        }
+        Ok(())
+    }
+
+    /// Record the header fields that frame the body or the connection.
+    fn field(&mut self, name: &str, value: &str) -> Result<(), &'static str> {
+        if name.eq_ignore_ascii_case(CONTENT_LENGTH) {
+            // A list of identical lengths is the same as a single length
+            for item in value.split(',') {
+                let item = item.trim_matches([' ', '\t']);
+                if item.is_empty() || !item.bytes().all(|byte| byte.is_ascii_digit()) {
+                    return Err("Invalid Content-Length");
+                }
+                let len = item.parse().map_err(|_| "Invalid Content-Length")?;
+                if self.content_length.is_some_and(|known| known != len) {
+                    return Err("Conflicting Content-Length");
+                }
+                self.content_length = Some(len);
+            }
+        } else if name.eq_ignore_ascii_case(TRANSFER_ENCODING) {
+            // Only the final transfer coding determines the framing
+            self.transfer_encoding = Some(value.rsplit(',').next().is_some_and(|coding| {
+                coding
+                    .trim_matches([' ', '\t'])
+                    .eq_ignore_ascii_case(CHUNKED)
+            }));
+        } else if name.eq_ignore_ascii_case(CONNECTION) {
+            self.close |= protocol::has_token(value, CLOSE);
+            self.keep_alive |= protocol::has_token(value, KEEP_ALIVE);
+        }
+        Ok(())
+    }
+
+    /// Work out the body framing at the end of the head.
+    fn end_head(&mut self) -> Result<(), &'static str> {
+        let is_response = self.request_method.is_some();
+        let framing = if self.request_method == Some(HttpMethod::HEAD)
+            || (100..200).contains(&self.status)
+            || self.status == 204
+            || self.status == 304
+        {
+            BodyFraming::Length(0)
+        } else if let Some(chunked) = self.transfer_encoding {
+            match (chunked, is_response) {
+                (true, _) => BodyFraming::Chunked,
+                (false, true) => BodyFraming::UntilClose,
+                (false, false) => {
+                    return Err("Unsupported transfer coding");
+                }
+            }
+        } else {
+            match (self.content_length, is_response) {
+                (Some(len), _) => BodyFraming::Length(len),
+                (None, true) => BodyFraming::UntilClose,
+                (None, false) => BodyFraming::Length(0),
+            }
+        };
+
+        let persistent = if self.http_1_0 {
+            self.keep_alive
+        } else {
+            !self.close
+        };
+        // A message with both framings may be an attempt at request
+        // smuggling, so the connection is not reused after it
+        let ambiguous = self.transfer_encoding.is_some() && self.content_length.is_some();
+
+        let head = Head {
+            len: self.pos,
+            framing,
+            keep_alive: persistent && !ambiguous,
+        };
+        self.head = Some(head);
+        self.state = State::Body(BodyState::new(framing));
+        Ok(())
+    }
+}
+
+/// Split a request line into method, request target and version.
+pub fn parse_request_line(line: &str) -> Result<(&str, &str, &str), &'static str> {
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err("Malformed request line");
+    };
+
+    if !is_token(method) {
+        return Err("Invalid method");
+    }
+    if target.is_empty() || !target.bytes().all(|byte| byte.is_ascii_graphic()) {
+        return Err("Invalid request target");
+    }
+    check_version(version)?;
+    Ok((method, target, version))
+}
+
+/// Split a status line into version and status code.
+///
+/// The reason phrase is checked but not returned.
+pub fn parse_status_line(line: &str) -> Result<(&str, u16), &'static str> {
+    const INVALID: &str = "Invalid HTTP status line";
+
+    let (version, rest) = line.split_once(' ').ok_or(INVALID)?;
+    check_version(version)?;
+    // Some servers leave out the space before an empty reason phrase
+    let (code, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+    if code.len() != 3 || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return Err(INVALID);
+    }
+    if !is_field_text(reason) {
+        return Err(INVALID);
+    }
+    let status = code.parse().map_err(|_| INVALID)?;
+    Ok((version, status))
+}
+
+/// Split a header field line into name and value.
+///
+/// Whitespace around the value is removed. Whitespace before the colon and
+/// obsolete line folding are rejected.
+pub fn parse_field_line(line: &str) -> Result<(&str, &str), &'static str> {
+    if line.starts_with([' ', '\t']) {
+        return Err("Obsolete header line folding");
+    }
+    let (name, value) = line.split_once(':').ok_or("Malformed header field")?;
+    if !is_token(name) {
+        return Err("Invalid header name");
+    }
+    let value = value.trim_matches([' ', '\t']);
+    if !is_field_text(value) {
+        return Err("Invalid header value");
+    }
+    Ok((name, value))
+}
+
+/// Check for `HTTP/1.x`; other major versions are not HTTP/1.1 messages.
+fn check_version(version: &str) -> Result<(), &'static str> {
+    match version.as_bytes() {
+        [b'H', b'T', b'T', b'P', b'/', b'1', b'.', minor] if minor.is_ascii_digit() => Ok(()),
+        _ => Err("Unsupported HTTP version"),
+    }
+}
+
+/// Check for a token: method names and header field names.
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// Check for text without control characters other than horizontal tab.
+fn is_field_text(value: &str) -> bool {
+    !value
+        .bytes()
+        .any(|byte| byte.is_ascii_control() && byte != b'\t')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse `data` fed in pieces of `step` bytes
+    fn parse_in_steps(mut parser: Parser, data: &[u8], step: usize) -> Option<Message> {
+        let mut len = 0;
+        loop {
+            len = (len + step).min(data.len());
+            if let Some(message) = parser.parse(&data[..len]).unwrap() {
+                return Some(message);
+            }
+            if len == data.len() {
+                return None;
+            }
+        }
+    }
+
+    fn response(data: &[u8]) -> Result<Option<Message>, Error> {
+        Parser::response(HttpMethod::GET).parse(data)
+    }
+
+    #[test]
+    fn test_response_content_length() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let message = response(data).unwrap().unwrap();
+        assert_eq!(message.head.len, data.len() - 5);
+        assert_eq!(message.head.framing, BodyFraming::Length(5));
+        assert_eq!(message.len, data.len());
+        assert!(message.head.keep_alive);
+
+        assert_eq!(response(&data[..data.len() - 1]).unwrap(), None);
+        let empty = b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(response(empty).unwrap().unwrap().len, empty.len());
+    }
+
+    #[test]
+    fn test_response_until_close() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\nsome";
+        let mut parser = Parser::response(HttpMethod::GET);
+        assert_eq!(parser.parse(data).unwrap(), None);
+        assert_eq!(parser.framing(), Some(BodyFraming::UntilClose));
+        assert_eq!(parser.finish().unwrap().len, data.len());
+
+        let mut parser = Parser::response(HttpMethod::GET);
+        parser
+            .parse(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhel")
+            .unwrap();
+        assert!(parser.finish().is_err());
+    }
+
+    #[test]
+    fn test_response_chunked() {
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                     5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nExpires: never\r\n\r\n";
+        for step in [1, 2, 7, data.len()] {
+            let message = parse_in_steps(Parser::response(HttpMethod::GET), data, step).unwrap();
+            assert_eq!(message.len, data.len());
+            assert_eq!(message.head.framing, BodyFraming::Chunked);
+        }
+
+        // Bytes of the next message are not part of this one
+        let mut pipelined = data.to_vec();
+        pipelined.extend_from_slice(b"HTTP/1.1 204 No Content\r\n\r\n");
+        assert_eq!(response(&pipelined).unwrap().unwrap().len, data.len());
+
+        let incomplete = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n";
+        assert_eq!(response(incomplete).unwrap(), None);
+
+        let invalid = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(response(invalid).is_err());
+    }
+
+    #[test]
+    fn test_decode_body() {
+        let raw = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+            5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nX-Checksum: 1\r\n\r\n";
+        let mut buf = *raw;
+        let message = response(&buf).unwrap().unwrap();
+        let len = message.decode_body(&mut buf);
+        assert_eq!(
+            &buf[..len],
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nhello world"
+        );
+
+        let raw = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        let mut buf = *raw;
+        let message = response(&buf).unwrap().unwrap();
+        assert_eq!(message.decode_body(&mut buf), raw.len());
+        assert_eq!(&buf, raw);
+    }
+
+    #[test]
+    fn test_response_framing() {
+        let framing = |method, head: &[u8]| {
+            let mut parser = Parser::response(method);
+            parser.parse_head(head).unwrap().unwrap().framing
+        };
+
+        assert_eq!(
+            framing(
+                HttpMethod::GET,
+                b"HTTP/1.1 200 OK\r\ncontent-length: 5\r\n\r\n"
+            ),
+            BodyFraming::Length(5)
+        );
+        assert_eq!(
+            framing(
+                HttpMethod::GET,
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip, chunked\r\nContent-Length: 5\r\n\r\n"
+            ),
+            BodyFraming::Chunked
+        );
+        assert_eq!(
+            framing(
+                HttpMethod::GET,
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: gzip\r\n\r\n"
+            ),
+            BodyFraming::UntilClose
+        );
+        assert_eq!(
+            framing(
+                HttpMethod::HEAD,
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n"
+            ),
+            BodyFraming::Length(0)
+        );
+        assert_eq!(
+            framing(HttpMethod::GET, b"HTTP/1.1 204 No Content\r\n\r\n"),
+            BodyFraming::Length(0)
+        );
+        assert_eq!(
+            framing(HttpMethod::GET, b"HTTP/1.1 304 Not Modified\r\n\r\n"),
+            BodyFraming::Length(0)
+        );
+        assert_eq!(
+            framing(HttpMethod::GET, b"HTTP/1.1 101 Switching Protocols\r\n\r\n"),
+            BodyFraming::Length(0)
+        );
+        assert_eq!(
+            framing(
+                HttpMethod::GET,
+                b"HTTP/1.1 200\r\nContent-Length: 1, 1\r\n\r\n"
+            ),
+            BodyFraming::Length(1)
+        );
+
+        for head in [
+            &b"HTTP/1.1 200 OK\r\nContent-Length: five\r\n\r\n"[..],
+            b"HTTP/1.1 200 OK\r\nContent-Length: +5\r\n\r\n",
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Length: 6\r\n\r\n",
+            b"HTTP/1.1 2000 OK\r\n\r\n",
+            b"HTTP/2 200 OK\r\n\r\n",
+            b"HTTP/1.1\r\n\r\n",
+        ] {
+            assert!(response(head).is_err());
+        }
+    }
+
+    #[test]
+    fn test_keep_alive() {
+        let keep_alive = |head: &[u8]| {
+            let mut parser = Parser::response(HttpMethod::GET);
+            parser.parse_head(head).unwrap().unwrap().keep_alive
+        };
+
+        assert!(keep_alive(b"HTTP/1.1 200 OK\r\n\r\n"));
+        assert!(!keep_alive(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n"));
+        assert!(!keep_alive(b"HTTP/1.0 200 OK\r\n\r\n"));
+        assert!(keep_alive(
+            b"HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\n\r\n"
+        ));
+        assert!(!keep_alive(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n"
+        ));
+    }
+
+    #[test]
+    fn test_request() {
+        let data = b"\r\nPOST /upload HTTP/1.1\r\nHost: device\r\nContent-Length: 3\r\n\r\nabcGET";
+        for step in [1, 5, data.len()] {
+            let message = parse_in_steps(Parser::request(), data, step).unwrap();
+            assert_eq!(message.len, data.len() - 3);
+            assert_eq!(message.head.framing, BodyFraming::Length(3));
+        }
+
+        let message = Parser::request()
+            .parse(b"GET / HTTP/1.1\r\n\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(message.head.framing, BodyFraming::Length(0));
+
+        let mut parser = Parser::request();
+        assert!(matches!(
+            parser.parse(b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n"),
+            Err(Error::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            Parser::request().parse(b"GET / HTTP/1.1\r\nHost : device\r\n\r\n"),
+            Err(Error::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            Parser::request().finish(),
+            Err(Error::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn test_strict_lines() {
+        for data in [
+            // Bare LF and bare CR
+            &b"GET / HTTP/1.1\nHost: device\r\n\r\n"[..],
+            b"GET / HTTP/1.1\r\nHost: device\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: dev\rice\r\n\r\n",
+            // Malformed request lines
+            b"GET  / HTTP/1.1\r\n\r\n",
+            b"GET /\r\n\r\n",
+            b"G(T / HTTP/1.1\r\n\r\n",
+            // Whitespace before the colon, folding and control characters
+            b"GET / HTTP/1.1\r\nHost : device\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: device\r\n folded\r\n\r\n",
+            b"GET / HTTP/1.1\r\nHost: dev\x00ice\r\n\r\n",
+            b"GET / HTTP/1.1\r\nno colon\r\n\r\n",
+        ] {
+            assert!(Parser::request().parse(data).is_err(), "{data:?}");
+        }
+
+        // A bare LF in chunked framing
+        let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n0\r\nX: y\n\r\n";
+        assert!(response(data).is_err());
+    }
+
+    #[test]
+    fn test_field_line() {
+        assert_eq!(
+            parse_field_line("Content-Type: \t text/plain \t").unwrap(),
+            ("Content-Type", "text/plain")
+        );
+        assert_eq!(parse_field_line("X-Empty:").unwrap(), ("X-Empty", ""));
+        assert_eq!(
+            parse_field_line("Location: http://a/b:c").unwrap(),
+            ("Location", "http://a/b:c")
+        );
+    }
+}
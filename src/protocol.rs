@@ -48,6 +48,21 @@ pub const HEADER_SEPARATOR: &str = ": ";
 /// Connection close header line with trailing CRLF and end-of-headers CRLF
 pub const CONNECTION_CLOSE_END: &str = "Connection: close\r\n\r\n";
 
+/// Connection keep-alive header line with trailing CRLF and end-of-headers CRLF
+pub const CONNECTION_KEEP_ALIVE_END: &str = "Connection: keep-alive\r\n\r\n";
+
+/// Connection upgrade header line with trailing CRLF and end-of-headers CRLF
+pub const CONNECTION_UPGRADE_END: &str = "Connection: Upgrade\r\n\r\n";
+
+/// `keep-alive` connection option
+pub const KEEP_ALIVE: &str = "keep-alive";
+
+/// `close` connection option
+pub const CLOSE: &str = "close";
+
+/// HTTP/1.0 version string
+pub const HTTP_VERSION_1_0: &str = "HTTP/1.0";
+
 /// Maximum number of headers allowed in requests and responses
 pub const MAX_HEADERS: usize = 16;
 
@@ -79,3 +94,12 @@ pub fn find_header_value<'a>(headers_str: &'a str, target_name: &str) -> Option<
     }
     None
 }
+
+/// Check if a comma-separated header value such as `Connection` contains
+/// `token` (case-insensitive).
+#[must_use]
+pub fn has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|item| item.trim().eq_ignore_ascii_case(token))
+}
@@ -0,0 +1,332 @@
+use crate::{
+    error::Error,
+    header::{
+        HttpHeader,
+        headers::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, X_API_KEY},
+    },
+    method::HttpMethod,
+    protocol::MAX_HEADERS,
+    status_code::StatusCode,
+    url::Url,
+};
+use heapless::{String, Vec};
+
+/// Maximum length of a URL the client follows through a redirect
+pub const MAX_REDIRECT_URL_LEN: usize = 512;
+
+/// Headers carrying credentials, dropped once a redirect leaves the original origin
+const CREDENTIAL_HEADERS: [&str; 4] = [AUTHORIZATION, "Proxy-Authorization", COOKIE, X_API_KEY];
+
+/// Headers describing the request body, dropped once a redirect turns the request into a `GET`
+const BODY_HEADERS: [&str; 2] = [CONTENT_TYPE, CONTENT_LENGTH];
+
+/// Which redirect responses [`HttpClient::request`](crate::HttpClient::request) follows automatically
+///
+/// Redirects are only followed when the response carries a `Location`
+/// header; the final response is returned as if it had been requested
+/// directly. 301 and 302 turn a `POST` into a `GET`, 303 turns every method
+/// except `HEAD` into a `GET`, and 307 and 308 repeat the request with the
+/// same method and body. A request that loses its body also loses its
+/// `Content-Type` and `Content-Length` headers. Redirect targets longer than
+/// [`MAX_REDIRECT_URL_LEN`] fail with [`Error::BufferOverflow`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedirectPolicy {
+    /// Return redirect responses to the caller
+    #[default]
+    None,
+    /// Follow redirects that stay on the same scheme, host and port
+    ///
+    /// A redirect to another origin is returned to the caller as is.
+    SameOrigin {
+        /// Maximum number of redirects followed for one request
+        max_hops: u8,
+    },
+    /// Follow redirects to any origin
+    ///
+    /// Credential headers such as `Authorization` and `Cookie` are dropped
+    /// once the request leaves its original origin.
+    AnyOrigin {
+        /// Maximum number of redirects followed for one request
+        max_hops: u8,
+    },
+}
+
+/// The method for the request following a redirect response, and whether it
+/// keeps the original body.
+///
+/// Returns `None` if `status` is not a redirect that can be followed.
+pub(crate) fn redirect_method(
+    status: StatusCode,
+    method: HttpMethod,
+) -> Option<(HttpMethod, bool)> {
+    match status.as_u16() {
+        301 | 302 if method == HttpMethod::POST => Some((HttpMethod::GET, false)),
+        301 | 302 | 307 | 308 => Some((method, true)),
+        303 if method == HttpMethod::HEAD => Some((HttpMethod::HEAD, false)),
+        303 => Some((HttpMethod::GET, false)),
+        _ => None,
+    }
+}
+
+/// Resolve a `Location` header value against the URL it was received for.
+///
+/// Handles absolute URLs, scheme-relative (`//host/path`), absolute-path,
+/// query-only and relative references, and removes `.` and `..` segments.
+/// Fragments are dropped since they are never sent to the server.
+pub(crate) fn resolve_location(
+    base: &Url<'_>,
+    location: &str,
+) -> Result<String<MAX_REDIRECT_URL_LEN>, Error> {
+    let location = location.trim();
+    let location = location.split('#').next().unwrap_or_default();
+    let base_path = if base.path().is_empty() {
+        "/"
+    } else {
+        base.path()
+    };
+
+    let mut url = String::new();
+    if has_scheme(location) {
+        push(&mut url, location)?;
+    } else if location.starts_with("//") {
+        push(&mut url, base.scheme())?;
+        push(&mut url, ":")?;
+        push(&mut url, location)?;
+    } else if location.is_empty() {
+        push(&mut url, base.origin())?;
+        push(&mut url, base.path_and_query())?;
+    } else if location.starts_with('?') {
+        push(&mut url, base.origin())?;
+        push(&mut url, base_path)?;
+        push(&mut url, location)?;
+    } else if location.starts_with('/') {
+        push(&mut url, base.origin())?;
+        push_normalized(&mut url, location)?;
+    } else {
+        // Relative to the directory of the current path
+        let directory = base_path.rfind('/').map_or("/", |pos| &base_path[..=pos]);
+        let mut merged = String::<MAX_REDIRECT_URL_LEN>::new();
+        push(&mut merged, directory)?;
+        push(&mut merged, location)?;
+        push(&mut url, base.origin())?;
+        push_normalized(&mut url, &merged)?;
+    }
+    Ok(url)
+}
+
+/// Copy `headers` without the ones carrying credentials if `credentials` is
+/// set, and without the ones describing the body if `body` is set.
+pub(crate) fn strip_headers<'h>(
+    headers: &[HttpHeader<'h>],
+    credentials: bool,
+    body: bool,
+) -> Result<Vec<HttpHeader<'h>, MAX_HEADERS>, Error> {
+    let listed = |names: &[&str], header: &HttpHeader<'_>| {
+        names
+            .iter()
+            .any(|name| header.name.eq_ignore_ascii_case(name))
+    };
+    let mut stripped = Vec::new();
+    for header in headers.iter().filter(|header| {
+        !(credentials && listed(&CREDENTIAL_HEADERS, header)
+            || body && listed(&BODY_HEADERS, header))
+    }) {
+        stripped
+            .push(header.clone())
+            .map_err(|_| Error::BufferOverflow)?;
+    }
+    Ok(stripped)
+}
+
+/// Whether `reference` starts with a URI scheme such as `https:`.
+fn has_scheme(reference: &str) -> bool {
+    reference.find(':').is_some_and(|colon| {
+        let scheme = &reference[..colon];
+        scheme
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic())
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+fn push(url: &mut String<MAX_REDIRECT_URL_LEN>, part: &str) -> Result<(), Error> {
+    url.push_str(part).map_err(|_| Error::BufferOverflow)
+}
+
+/// Append an absolute path with optional query, removing dot segments from the path.
+fn push_normalized(url: &mut String<MAX_REDIRECT_URL_LEN>, reference: &str) -> Result<(), Error> {
+    let (path, query) = reference
+        .split_once('?')
+        .map_or((reference, None), |(path, query)| (path, Some(query)));
+    let path_start = url.len();
+
+    let mut segments = path.split('/').skip(1).peekable();
+    while let Some(segment) = segments.next() {
+        let dot_segment = matches!(segment, "." | "..");
+        if segment == ".." {
+            if let Some(pos) = url[path_start..].rfind('/') {
+                url.truncate(path_start + pos);
+            }
+        } else if segment != "." {
+            push(url, "/")?;
+            push(url, segment)?;
+        }
+        // A path ending in a dot segment still denotes a directory
+        if dot_segment && segments.peek().is_none() {
+            push(url, "/")?;
+        }
+    }
+    if url.len() == path_start {
+        push(url, "/")?;
+    }
+
+    if let Some(query) = query {
+        push(url, "?")?;
+        push(url, query)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "http://example.com/api/v1/items?page=2";
+
+    fn resolve_location(base: &str, location: &str) -> Result<String<MAX_REDIRECT_URL_LEN>, Error> {
+        super::resolve_location(&Url::parse(base).unwrap(), location)
+    }
+
+    #[test]
+    fn test_default_policy() {
+        assert_eq!(RedirectPolicy::default(), RedirectPolicy::None);
+    }
+
+    #[test]
+    fn test_redirect_method() {
+        let post = HttpMethod::POST;
+        assert_eq!(
+            redirect_method(StatusCode::MovedPermanently, post),
+            Some((HttpMethod::GET, false))
+        );
+        assert_eq!(
+            redirect_method(StatusCode::Found, HttpMethod::PUT),
+            Some((HttpMethod::PUT, true))
+        );
+        assert_eq!(
+            redirect_method(StatusCode::SeeOther, HttpMethod::PUT),
+            Some((HttpMethod::GET, false))
+        );
+        assert_eq!(
+            redirect_method(StatusCode::SeeOther, HttpMethod::HEAD),
+            Some((HttpMethod::HEAD, false))
+        );
+        assert_eq!(
+            redirect_method(StatusCode::TemporaryRedirect, post),
+            Some((post, true))
+        );
+        assert_eq!(
+            redirect_method(StatusCode::Other(308), post),
+            Some((post, true))
+        );
+        assert_eq!(redirect_method(StatusCode::NotModified, post), None);
+        assert_eq!(redirect_method(StatusCode::Ok, post), None);
+    }
+
+    #[test]
+    fn test_resolve_absolute() {
+        assert_eq!(
+            resolve_location(BASE, "https://other.org/x#frag").unwrap(),
+            "https://other.org/x"
+        );
+        assert_eq!(
+            resolve_location("https://example.com/a", "//cdn.example.com/b").unwrap(),
+            "https://cdn.example.com/b"
+        );
+    }
+
+    #[test]
+    fn test_resolve_path() {
+        assert_eq!(
+            resolve_location(BASE, "/login?next=%2F").unwrap(),
+            "http://example.com/login?next=%2F"
+        );
+        assert_eq!(
+            resolve_location(BASE, "?page=3").unwrap(),
+            "http://example.com/api/v1/items?page=3"
+        );
+        assert_eq!(resolve_location(BASE, "").unwrap(), BASE);
+        assert_eq!(
+            resolve_location("http://example.com:8080", "/moved").unwrap(),
+            "http://example.com:8080/moved"
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative() {
+        assert_eq!(
+            resolve_location(BASE, "other").unwrap(),
+            "http://example.com/api/v1/other"
+        );
+        assert_eq!(
+            resolve_location(BASE, "../v2/items").unwrap(),
+            "http://example.com/api/v2/items"
+        );
+        assert_eq!(
+            resolve_location(BASE, "./").unwrap(),
+            "http://example.com/api/v1/"
+        );
+        assert_eq!(
+            resolve_location(BASE, "..").unwrap(),
+            "http://example.com/api/"
+        );
+        assert_eq!(
+            resolve_location(BASE, "../../../../top").unwrap(),
+            "http://example.com/top"
+        );
+        assert_eq!(
+            resolve_location("http://example.com", "next").unwrap(),
+            "http://example.com/next"
+        );
+        assert_eq!(
+            resolve_location("http://[fe80::1]:8080/a/b#frag", "c").unwrap(),
+            "http://[fe80::1]:8080/a/c"
+        );
+    }
+
+    #[test]
+    fn test_resolve_too_long() {
+        let mut long = std::string::String::from("/");
+        long.push_str(&"a".repeat(MAX_REDIRECT_URL_LEN));
+        assert!(matches!(
+            resolve_location(BASE, &long),
+            Err(Error::BufferOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_strip_headers() {
+        let headers = [
+            HttpHeader::authorization("Bearer secret"),
+            HttpHeader::new("cookie", "session=1"),
+            HttpHeader::api_key("key"),
+            HttpHeader::content_type("application/json"),
+            HttpHeader::new("content-length", "2"),
+            HttpHeader::user_agent("nanofish"),
+        ];
+        let stripped = strip_headers(&headers, true, false).unwrap();
+        assert_eq!(stripped.len(), 3);
+        assert_eq!(stripped[0].name, "Content-Type");
+
+        let stripped = strip_headers(&headers, false, true).unwrap();
+        assert_eq!(stripped.len(), 4);
+        assert_eq!(stripped[3].name, "User-Agent");
+
+        let stripped = strip_headers(&headers, true, true).unwrap();
+        assert_eq!(stripped.len(), 1);
+        assert_eq!(stripped[0].name, "User-Agent");
+    }
+}
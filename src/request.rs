@@ -2,10 +2,11 @@ use crate::{
     error::Error,
     header::{
         HttpHeader,
-        headers::{CONTENT_LENGTH, CONTENT_TYPE},
+        headers::{CONNECTION, CONTENT_LENGTH, CONTENT_TYPE},
     },
     method::HttpMethod,
-    protocol::{self, DOUBLE_CRLF_LEN, MAX_HEADERS},
+    parser::{Parser, parse_field_line, parse_request_line},
+    protocol::{self, CLOSE, CRLF_STR, HTTP_VERSION_1_0, KEEP_ALIVE, MAX_HEADERS},
 };
 use heapless::Vec;
 
@@ -84,6 +85,9 @@ pub struct HttpRequest<'a> {
     /// Request headers
     pub headers: Vec<HttpHeader<'a>, MAX_HEADERS>,
     /// Request body (if present)
+    ///
+    /// Bodies sent with `Transfer-Encoding: chunked` are decoded by the
+    /// server before the handler sees them.
     pub body: &'a [u8],
 }
 
@@ -199,6 +203,20 @@ impl<'a> HttpRequest<'a> {
         self.header(CONTENT_LENGTH)?.parse().ok()
     }
 
+    /// Check if the client wants to keep the connection open after this request.
+    ///
+    /// HTTP/1.1 connections are persistent unless `Connection: close` is sent;
+    /// HTTP/1.0 connections only with `Connection: keep-alive`.
+    #[must_use]
+    pub fn is_keep_alive(&self) -> bool {
+        let connection = self.header(CONNECTION);
+        if self.version.eq_ignore_ascii_case(HTTP_VERSION_1_0) {
+            connection.is_some_and(|value| protocol::has_token(value, KEEP_ALIVE))
+        } else {
+            !connection.is_some_and(|value| protocol::has_token(value, CLOSE))
+        }
+    }
+
     /// Parse an HTTP request from headers string and body bytes
     ///
     /// # Errors
@@ -207,43 +225,30 @@ impl<'a> HttpRequest<'a> {
     /// - The request line is missing or malformed
     /// - The HTTP method is invalid or unsupported  
     /// - Required parts (method, path, version) are missing
+    /// - A line is not terminated by CRLF or a header field is malformed
     /// - Too many headers are provided (exceeds `MAX_HEADERS`)
     pub fn parse_from(headers_str: &'a str, body: &'a [u8]) -> Result<Self, Error> {
-        let mut lines = headers_str.lines();
+        // Empty lines before the request line are ignored
+        let mut lines = headers_str
+            .split(CRLF_STR)
+            .skip_while(|line| line.is_empty());
 
-        // Parse request line
         let request_line = lines
             .next()
             .ok_or(Error::InvalidResponse("Missing request line"))?;
-        let mut parts = request_line.split_whitespace();
-
-        let method_str = parts
-            .next()
-            .ok_or(Error::InvalidResponse("Missing method"))?;
-        let path = parts.next().ok_or(Error::InvalidResponse("Missing path"))?;
-        let version = parts
-            .next()
-            .ok_or(Error::InvalidResponse("Missing version"))?;
+        let (method_str, path, version) =
+            parse_request_line(request_line).map_err(Error::InvalidResponse)?;
 
         let method = HttpMethod::try_from(method_str)
             .map_err(|_| Error::InvalidResponse("Unknown HTTP method"))?;
 
         // Parse headers
         let mut headers = Vec::new();
-        for line in lines {
-            if line.is_empty() {
-                break;
-            }
-
-            if let Some(colon_pos) = line.find(':') {
-                let name = line[..colon_pos].trim();
-                let value = line[colon_pos + 1..].trim();
-
-                let header = HttpHeader::new(name, value);
-                headers
-                    .push(header)
-                    .map_err(|_| Error::InvalidResponse("Too many headers"))?;
-            }
+        for line in lines.take_while(|line| !line.is_empty()) {
+            let (name, value) = parse_field_line(line).map_err(Error::InvalidResponse)?;
+            headers
+                .push(HttpHeader::new(name, value))
+                .map_err(|_| Error::InvalidResponse("Too many headers"))?;
         }
 
         Ok(HttpRequest {
@@ -260,16 +265,22 @@ impl<'a> TryFrom<&'a [u8]> for HttpRequest<'a> {
     type Error = Error;
 
     fn try_from(buffer: &'a [u8]) -> Result<Self, Self::Error> {
-        // Find the end of headers (double CRLF)
-        let end_of_headers = protocol::find_double_crlf(buffer)
-            .ok_or(Error::InvalidResponse("Incomplete request headers"))?;
-
-        // Parse the headers string
-        let headers_str = core::str::from_utf8(&buffer[..end_of_headers])
+        let head_len = Parser::request()
+            .parse_head(buffer)
+            // Parsed requests have always reported `InvalidResponse`
+            .map_err(|e| match e {
+                Error::InvalidRequest(message) => Error::InvalidResponse(message),
+                e => e,
+            })?
+            .ok_or(Error::InvalidResponse("Incomplete request headers"))?
+            .len;
+
+        // The parser has checked that the head is valid UTF-8
+        let headers_str = core::str::from_utf8(&buffer[..head_len])
             .map_err(|_| Error::InvalidResponse("Invalid UTF-8 in request"))?;
 
-        // Body starts after the double CRLF
-        let body = &buffer[end_of_headers + DOUBLE_CRLF_LEN..];
+        // Body starts after the blank line ending the head
+        let body = &buffer[head_len..];
 
         Self::parse_from(headers_str, body)
     }
@@ -421,6 +432,24 @@ mod tests {
         assert_eq!(request.body_str().unwrap(), "hello");
     }
 
+    #[test]
+    fn test_request_is_keep_alive() {
+        let request = HttpRequest::try_from(&b"GET / HTTP/1.1\r\n\r\n"[..]).unwrap();
+        assert!(request.is_keep_alive());
+
+        let request =
+            HttpRequest::try_from(&b"GET / HTTP/1.1\r\nConnection: Close\r\n\r\n"[..]).unwrap();
+        assert!(!request.is_keep_alive());
+
+        let request = HttpRequest::try_from(&b"GET / HTTP/1.0\r\n\r\n"[..]).unwrap();
+        assert!(!request.is_keep_alive());
+
+        let request =
+            HttpRequest::try_from(&b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\n"[..])
+                .unwrap();
+        assert!(request.is_keep_alive());
+    }
+
     #[test]
     fn test_percent_decode_query_component() {
         let mut out = [0u8; 32];
@@ -428,8 +457,14 @@ mod tests {
             percent_decode("hello+world%21%2F", &mut out).unwrap(),
             "hello world!/"
         );
-        assert!(percent_decode("bad%", &mut out).is_err());
-        assert!(percent_decode("bad%xx", &mut out).is_err());
+        assert!(matches!(
+            percent_decode("bad%", &mut out),
+            Err(Error::InvalidResponse(_))
+        ));
+        assert!(matches!(
+            percent_decode("bad%xx", &mut out),
+            Err(Error::InvalidResponse(_))
+        ));
         assert!(percent_decode("toolong", &mut [0u8; 3]).is_err());
     }
 
@@ -461,7 +496,7 @@ mod tests {
         let body = b"";
 
         let result = HttpRequest::parse_from(request_str, body);
-        assert!(result.is_err());
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
     }
 
     #[test]
@@ -577,6 +612,6 @@ mod tests {
         let _ = buffer.extend_from_slice(b"\r\n\r\n");
 
         let result = HttpRequest::try_from(buffer.as_slice());
-        assert!(result.is_err());
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
     }
 }
@@ -184,8 +184,8 @@ impl HttpResponse<'_> {
 ///
 /// # Example
 ///
-/// ```rust,ignore
-/// use nanofish::{HttpResponse, HttpResponseBuilder, StatusCode, headers, mime_types};
+/// ```
+/// use nanofish::{StatusCode, mime_types, response::HttpResponseBuilder};
 ///
 /// // JSON success response
 /// let response = HttpResponseBuilder::new()
@@ -208,6 +208,7 @@ impl HttpResponse<'_> {
 ///     .status(StatusCode::Ok)
 ///     .content_type(mime_types::BINARY)?
 ///     .binary(&[0x00, 0x01, 0x02]);
+/// # Ok::<(), nanofish::Error>(())
 /// ```
 pub struct HttpResponseBuilder<'a> {
     status: StatusCode,
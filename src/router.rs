@@ -0,0 +1,489 @@
+use crate::{
+    error::Error,
+    handler::HttpHandler,
+    header::{HttpHeader, headers::ALLOW, mime_types},
+    method::HttpMethod,
+    request::HttpRequest,
+    response::{HttpResponse, ResponseBody},
+    status_code::StatusCode,
+};
+use heapless::{String, Vec};
+
+/// Maximum number of parameters a route pattern can capture
+pub const MAX_PATH_PARAMS: usize = 8;
+
+/// Number of distinct HTTP methods, the most an `Allow` header can list
+const METHOD_COUNT: usize = 9;
+
+/// Long enough for all methods joined with `", "`
+const ALLOW_LEN: usize = 64;
+
+/// Trait for handlers registered with a [`Router`]
+#[allow(async_fn_in_trait)]
+pub trait RouteHandler {
+    /// Handle a request whose method and path matched the route
+    ///
+    /// `params` holds the segments captured by the route pattern.
+    async fn handle_route(
+        &mut self,
+        request: &HttpRequest<'_>,
+        params: &PathParams<'_>,
+    ) -> Result<HttpResponse<'_>, Error>;
+}
+
+/// A plain function used as a route handler
+///
+/// This is the default handler type of [`Router`], so functions can be
+/// registered directly.
+pub type RouteFn = fn(&HttpRequest<'_>, &PathParams<'_>) -> Result<HttpResponse<'static>, Error>;
+
+impl RouteHandler for RouteFn {
+    async fn handle_route(
+        &mut self,
+        request: &HttpRequest<'_>,
+        params: &PathParams<'_>,
+    ) -> Result<HttpResponse<'_>, Error> {
+        self(request, params)
+    }
+}
+
+/// Path segments captured by a route pattern
+///
+/// Values are the raw segments from the request path; use
+/// [`percent_decode`](crate::percent_decode) if they may contain escapes.
+#[derive(Debug, Clone, Default)]
+pub struct PathParams<'a> {
+    params: Vec<(&'a str, &'a str), MAX_PATH_PARAMS>,
+}
+
+impl<'a> PathParams<'a> {
+    /// Get a captured value by its name in the pattern, without the `:` or `*`
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&'a str> {
+        self.params
+            .iter()
+            .find(|(param, _)| *param == name)
+            .map(|(_, value)| *value)
+    }
+
+    /// Iterate over `(name, value)` pairs in pattern order
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a str)> + '_ {
+        self.params.iter().copied()
+    }
+
+    /// Number of captured parameters
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    /// Whether no parameters were captured
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+#[derive(Clone)]
+struct Route<'r, H> {
+    method: HttpMethod,
+    pattern: &'r str,
+    handler: H,
+}
+
+/// Allocation-free request router
+///
+/// Routes are `(method, pattern, handler)` entries kept in a table of fixed
+/// capacity `N` and tried in registration order. Patterns are matched
+/// segment by segment against [`HttpRequest::route_path`]:
+///
+/// * `/devices` matches only that literal path.
+/// * `/devices/:id/config` captures one non-empty segment as `id`.
+/// * `/files/*path` captures the rest of the path, slashes included, as `path`;
+///   it may only be the last segment.
+///
+/// A `HEAD` request without a `HEAD` route of its own is handled by the
+/// matching `GET` route. A path that matches no route gets a `404 Not Found`.
+/// A path that matches only routes for other methods gets a
+/// `405 Method Not Allowed` with an `Allow` header listing them, including
+/// `HEAD` wherever `GET` is allowed.
+///
+/// The router itself implements [`HttpHandler`] and can be passed to
+/// [`HttpServer::serve`](crate::HttpServer::serve). It is `Clone` when its
+/// route handlers are, as [`HttpServer::serve_concurrent`](crate::HttpServer::serve_concurrent)
+/// requires.
+///
+/// # Examples
+///
+/// ```
+/// use nanofish::{
+///     Error, HttpMethod, HttpRequest, HttpResponse, PathParams, ResponseBody, Router, StatusCode,
+/// };
+///
+/// fn device(_request: &HttpRequest<'_>, params: &PathParams<'_>) -> Result<HttpResponse<'static>, Error> {
+///     let body = if params.get("id") == Some("1") { "thermostat" } else { "unknown" };
+///     Ok(HttpResponse {
+///         status_code: StatusCode::Ok,
+///         headers: heapless::Vec::new(),
+///         body: ResponseBody::Text(body),
+///     })
+/// }
+///
+/// let router = Router::<4>::new()
+///     .route(HttpMethod::GET, "/devices/:id", device)?;
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Router<'r, const N: usize, H = RouteFn> {
+    routes: Vec<Route<'r, H>, N>,
+    /// Backing storage for the `Allow` header of 405 responses
+    allow: String<ALLOW_LEN>,
+}
+
+impl<'r, const N: usize, H: RouteHandler> Router<'r, N, H> {
+    /// Create an empty router
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            routes: Vec::new(),
+            allow: String::new(),
+        }
+    }
+
+    /// Register a handler for `method` requests matching `pattern`
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferOverflow` if the table already holds `N` routes
+    /// or the pattern captures more than [`MAX_PATH_PARAMS`] parameters.
+    pub fn route(
+        mut self,
+        method: HttpMethod,
+        pattern: &'r str,
+        handler: H,
+    ) -> Result<Self, Error> {
+        let captures = pattern
+            .split('/')
+            .filter(|segment| segment.starts_with([':', '*']))
+            .count();
+        if captures > MAX_PATH_PARAMS {
+            return Err(Error::BufferOverflow);
+        }
+        self.routes
+            .push(Route {
+                method,
+                pattern,
+                handler,
+            })
+            .map_err(|_| Error::BufferOverflow)?;
+        Ok(self)
+    }
+}
+
+impl<const N: usize, H: RouteHandler> Default for Router<'_, N, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize, H: RouteHandler> HttpHandler for Router<'_, N, H> {
+    async fn handle_request(
+        &mut self,
+        request: &HttpRequest<'_>,
+    ) -> Result<HttpResponse<'_>, Error> {
+        let path = request.route_path();
+        let mut allowed = Vec::<HttpMethod, METHOD_COUNT>::new();
+        let mut matched = None;
+        let mut get_route = None;
+
+        for (index, route) in self.routes.iter().enumerate() {
+            let Some(params) = match_route(route.pattern, path) else {
+                continue;
+            };
+            if route.method == request.method {
+                matched = Some((index, params));
+                break;
+            }
+            if !allowed.contains(&route.method) {
+                let _ = allowed.push(route.method);
+            }
+            if route.method == HttpMethod::GET {
+                // A GET route also answers HEAD; the server leaves out the body
+                if !allowed.contains(&HttpMethod::HEAD) {
+                    let _ = allowed.push(HttpMethod::HEAD);
+                }
+                if request.method == HttpMethod::HEAD && get_route.is_none() {
+                    get_route = Some((index, params));
+                }
+            }
+        }
+
+        if let Some((index, params)) = matched.or(get_route) {
+            return self.routes[index]
+                .handler
+                .handle_route(request, &params)
+                .await;
+        }
+
+        let mut headers = Vec::new();
+        let _ = headers.push(HttpHeader::content_type(mime_types::TEXT));
+        if allowed.is_empty() {
+            return Ok(HttpResponse {
+                status_code: StatusCode::NotFound,
+                headers,
+                body: ResponseBody::Text("404 Not Found"),
+            });
+        }
+
+        self.allow.clear();
+        for (i, method) in allowed.iter().enumerate() {
+            if i > 0 {
+                let _ = self.allow.push_str(", ");
+            }
+            let _ = self.allow.push_str(method.as_str());
+        }
+        let _ = headers.push(HttpHeader::new(ALLOW, &self.allow));
+        Ok(HttpResponse {
+            status_code: StatusCode::MethodNotAllowed,
+            headers,
+            body: ResponseBody::Text("405 Method Not Allowed"),
+        })
+    }
+}
+
+/// Match `path` against a route pattern, returning the captured parameters.
+fn match_route<'a>(pattern: &'a str, path: &'a str) -> Option<PathParams<'a>> {
+    let mut params = PathParams::default();
+    let mut path_segments = path.split('/');
+    let mut consumed = 0;
+
+    for segment in pattern.split('/') {
+        if let Some(name) = segment.strip_prefix('*') {
+            // The rest of the path, which may be empty
+            let rest = &path[consumed.min(path.len())..];
+            params.params.push((name, rest)).ok()?;
+            return Some(params);
+        }
+
+        let part = path_segments.next()?;
+        consumed += part.len() + 1;
+        if let Some(name) = segment.strip_prefix(':') {
+            if part.is_empty() {
+                return None;
+            }
+            params.params.push((name, part)).ok()?;
+        } else if segment != part {
+            return None;
+        }
+    }
+
+    path_segments.next().is_none().then_some(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: HttpMethod, path: &str) -> HttpRequest<'_> {
+        HttpRequest {
+            method,
+            path,
+            version: "HTTP/1.1",
+            headers: Vec::new(),
+            body: b"",
+        }
+    }
+
+    fn text(body: &'static str) -> HttpResponse<'static> {
+        HttpResponse {
+            status_code: StatusCode::Ok,
+            headers: Vec::new(),
+            body: ResponseBody::Text(body),
+        }
+    }
+
+    fn router() -> Router<'static, 4> {
+        Router::<'_, 4>::new()
+            .route(HttpMethod::GET, "/devices", |_, _| Ok(text("devices")))
+            .unwrap()
+            .route(HttpMethod::POST, "/devices", |_, _| Ok(text("created")))
+            .unwrap()
+            .route(HttpMethod::GET, "/devices/:id/config", |_, params| {
+                Ok(text(match params.get("id") {
+                    Some("42") => "config of 42",
+                    _ => "other config",
+                }))
+            })
+            .unwrap()
+    }
+
+    /// Handle `request`, returning the status, body and `Allow` header
+    fn dispatch(
+        handler: &mut impl HttpHandler,
+        request: &HttpRequest<'_>,
+    ) -> (StatusCode, std::string::String, Option<std::string::String>) {
+        let response = futures_lite::future::block_on(handler.handle_request(request)).unwrap();
+        (
+            response.status_code,
+            response.body.as_str().unwrap_or_default().into(),
+            response.get_header(ALLOW).map(Into::into),
+        )
+    }
+
+    #[test]
+    fn test_match_route() {
+        let params = match_route("/devices/:id/config", "/devices/7/config").unwrap();
+        assert_eq!(params.get("id"), Some("7"));
+        assert_eq!(params.len(), 1);
+
+        assert!(match_route("/devices", "/devices").unwrap().is_empty());
+        assert!(match_route("/", "/").is_some());
+        assert!(match_route("/devices", "/devices/").is_none());
+        assert!(match_route("/devices/:id", "/devices/").is_none());
+        assert!(match_route("/devices/:id", "/devices/1/config").is_none());
+        assert!(match_route("/devices/:id/config", "/devices/1").is_none());
+        assert!(match_route("/devices", "/sensors").is_none());
+    }
+
+    #[test]
+    fn test_match_route_multiple_params() {
+        let params = match_route("/a/:x/b/:y", "/a/1/b/2").unwrap();
+        let pairs: std::vec::Vec<_> = params.iter().collect();
+        assert_eq!(pairs, [("x", "1"), ("y", "2")]);
+    }
+
+    #[test]
+    fn test_match_route_wildcard() {
+        let params = match_route("/files/*path", "/files/logs/today.txt").unwrap();
+        assert_eq!(params.get("path"), Some("logs/today.txt"));
+
+        let params = match_route("/files/*path", "/files/").unwrap();
+        assert_eq!(params.get("path"), Some(""));
+
+        let params = match_route("/files/*path", "/files").unwrap();
+        assert_eq!(params.get("path"), Some(""));
+
+        assert!(match_route("/files/*path", "/other/x").is_none());
+    }
+
+    #[test]
+    fn test_router_dispatch() {
+        let mut router = router();
+
+        let (status, body, _) = dispatch(&mut router, &request(HttpMethod::GET, "/devices"));
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(body, "devices");
+
+        let (_, body, _) = dispatch(&mut router, &request(HttpMethod::POST, "/devices"));
+        assert_eq!(body, "created");
+
+        let (_, body, _) = dispatch(
+            &mut router,
+            &request(HttpMethod::GET, "/devices/42/config?verbose=1"),
+        );
+        assert_eq!(body, "config of 42");
+    }
+
+    #[test]
+    fn test_router_not_found() {
+        let mut router = router();
+        let (status, _, allow) = dispatch(&mut router, &request(HttpMethod::GET, "/sensors"));
+        assert_eq!(status, StatusCode::NotFound);
+        assert!(allow.is_none());
+    }
+
+    #[test]
+    fn test_router_method_not_allowed() {
+        let mut router = router();
+        let (status, _, allow) = dispatch(&mut router, &request(HttpMethod::DELETE, "/devices"));
+        assert_eq!(status, StatusCode::MethodNotAllowed);
+        assert_eq!(allow.as_deref(), Some("GET, HEAD, POST"));
+
+        let (status, _, allow) =
+            dispatch(&mut router, &request(HttpMethod::PUT, "/devices/1/config"));
+        assert_eq!(status, StatusCode::MethodNotAllowed);
+        assert_eq!(allow.as_deref(), Some("GET, HEAD"));
+    }
+
+    #[test]
+    fn test_router_head_uses_get_route() {
+        let mut router = router();
+        let (status, body, _) = dispatch(&mut router, &request(HttpMethod::HEAD, "/devices"));
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(body, "devices");
+
+        // An explicit HEAD route takes precedence, wherever it is registered
+        let mut router = router
+            .route(HttpMethod::HEAD, "/devices", |_, _| Ok(text("head")))
+            .unwrap();
+        let (_, body, _) = dispatch(&mut router, &request(HttpMethod::HEAD, "/devices"));
+        assert_eq!(body, "head");
+
+        // Only GET routes answer HEAD
+        let mut router = Router::<'_, 1>::new()
+            .route(HttpMethod::POST, "/devices", |_, _| Ok(text("created")))
+            .unwrap();
+        let (status, _, allow) = dispatch(&mut router, &request(HttpMethod::HEAD, "/devices"));
+        assert_eq!(status, StatusCode::MethodNotAllowed);
+        assert_eq!(allow.as_deref(), Some("POST"));
+    }
+
+    #[test]
+    fn test_router_capacity() {
+        let router = Router::<'_, 1>::new()
+            .route(HttpMethod::GET, "/a", |_, _| Ok(text("a")))
+            .unwrap();
+        assert!(matches!(
+            router.route(HttpMethod::GET, "/b", |_, _| Ok(text("b"))),
+            Err(Error::BufferOverflow)
+        ));
+
+        let result =
+            Router::<'_, 1>::new().route(HttpMethod::GET, "/:a/:b/:c/:d/:e/:f/:g/:h/:i", |_, _| {
+                Ok(text("deep"))
+            });
+        assert!(matches!(result, Err(Error::BufferOverflow)));
+    }
+
+    /// Handler with state, answering from a buffer it owns
+    struct Counter {
+        hits: u32,
+        body: String<16>,
+    }
+
+    impl RouteHandler for Counter {
+        async fn handle_route(
+            &mut self,
+            _request: &HttpRequest<'_>,
+            _params: &PathParams<'_>,
+        ) -> Result<HttpResponse<'_>, Error> {
+            self.hits += 1;
+            self.body.clear();
+            core::fmt::write(&mut self.body, format_args!("{}", self.hits))
+                .map_err(|_| Error::BufferOverflow)?;
+            Ok(HttpResponse {
+                status_code: StatusCode::Ok,
+                headers: Vec::new(),
+                body: ResponseBody::Text(&self.body),
+            })
+        }
+    }
+
+    #[test]
+    fn test_router_stateful_handler() {
+        let mut router = Router::<'_, 2, Counter>::new()
+            .route(
+                HttpMethod::GET,
+                "/hits",
+                Counter {
+                    hits: 0,
+                    body: String::new(),
+                },
+            )
+            .unwrap();
+
+        dispatch(&mut router, &request(HttpMethod::GET, "/hits"));
+        let (_, body, _) = dispatch(&mut router, &request(HttpMethod::GET, "/hits"));
+        assert_eq!(body, "2");
+    }
+}
@@ -1,20 +1,35 @@
 use crate::{
     error::Error,
-    handler::HttpHandler,
-    header::{HttpHeader, headers::CONTENT_LENGTH, mime_types},
-    protocol::{self, DOUBLE_CRLF_LEN},
+    handler::StreamingHandler,
+    header::{HttpHeader, mime_types},
+    method::HttpMethod,
+    parser::{Message, Parser},
+    protocol::HTTP_VERSION_1_0,
     request::HttpRequest,
     response::{HttpResponse, ResponseBody},
     status_code::StatusCode,
+    transport::{Acceptor, Transport},
+    writer::{ResponseState, ResponseWriter, WriterState},
+};
+#[cfg(feature = "tls")]
+use crate::{tls::TlsIdentity, tls_server::TlsServerConnection};
+#[cfg(feature = "tls")]
+use core::cell::RefCell;
+use core::pin::pin;
+use embassy_futures::{
+    join::join_array,
+    select::{Either, select},
 };
-use embassy_net::{Stack, tcp::TcpSocket};
 use embassy_time::{Duration, Timer, with_timeout};
-use embedded_io_async::Write as EmbeddedWrite;
+use embedded_io_async::{Read, Write};
+#[cfg(feature = "tls")]
+use embedded_tls::CryptoRngCore;
 use heapless::Vec;
 
 const SERVER_BUFFER_SIZE: usize = 4096;
 const MAX_REQUEST_SIZE: usize = 4096;
 const DEFAULT_MAX_RESPONSE_SIZE: usize = 4096;
+const DEFAULT_MAX_REQUESTS: usize = 100;
 
 /// HTTP server timeout configuration
 #[derive(Debug, Clone, Copy)]
@@ -23,8 +38,13 @@ pub struct ServerTimeouts {
     pub accept_timeout: u64,
     /// Socket read timeout in seconds
     pub read_timeout: u64,
-    /// Request handler timeout in seconds
+    /// Time in seconds a handler may take before it starts its response
+    ///
+    /// Once the response has started, event streams and other long
+    /// responses run until the handler returns or the client goes away.
     pub handler_timeout: u64,
+    /// Idle time in seconds to wait for the next request on a persistent connection
+    pub keep_alive_timeout: u64,
 }
 
 impl Default for ServerTimeouts {
@@ -33,35 +53,99 @@ impl Default for ServerTimeouts {
             accept_timeout: 10,
             read_timeout: 30,
             handler_timeout: 60,
+            keep_alive_timeout: 5,
         }
     }
 }
 
 impl ServerTimeouts {
     /// Create new server timeouts with custom values
+    ///
+    /// The keep-alive timeout keeps its default of 5 seconds; use
+    /// [`ServerTimeouts::with_keep_alive_timeout`] to change it.
     #[must_use]
     pub const fn new(accept_timeout: u64, read_timeout: u64, handler_timeout: u64) -> Self {
         Self {
             accept_timeout,
             read_timeout,
             handler_timeout,
+            keep_alive_timeout: 5,
         }
     }
+
+    /// Set the idle timeout for persistent connections
+    #[must_use]
+    pub const fn with_keep_alive_timeout(mut self, keep_alive_timeout: u64) -> Self {
+        self.keep_alive_timeout = keep_alive_timeout;
+        self
+    }
+}
+
+/// Socket and request buffers for one server connection
+///
+/// [`HttpServer::serve_concurrent`] takes one set per connection it serves at
+/// the same time. They are large, so keep them in a `static` or in the
+/// serving task rather than on a small stack.
+pub struct ServerBuffers<const RX_SIZE: usize, const TX_SIZE: usize, const REQ_SIZE: usize> {
+    rx: [u8; RX_SIZE],
+    tx: [u8; TX_SIZE],
+    request: [u8; REQ_SIZE],
+}
+
+impl<const RX_SIZE: usize, const TX_SIZE: usize, const REQ_SIZE: usize>
+    ServerBuffers<RX_SIZE, TX_SIZE, REQ_SIZE>
+{
+    /// Create zeroed buffers
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            rx: [0; RX_SIZE],
+            tx: [0; TX_SIZE],
+            request: [0; REQ_SIZE],
+        }
+    }
+}
+
+impl<const RX_SIZE: usize, const TX_SIZE: usize, const REQ_SIZE: usize> Default
+    for ServerBuffers<RX_SIZE, TX_SIZE, REQ_SIZE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 /// Simple HTTP server implementation
 ///
-/// **Note**: This server only supports HTTP connections, not HTTPS/TLS.
-/// For secure connections, consider using a reverse proxy or load balancer
-/// that handles TLS termination.
+/// Connections are persistent: after a response the server keeps reading
+/// requests on the same socket, including pipelined ones, until the client
+/// sends `Connection: close` (or speaks HTTP/1.0 without
+/// `Connection: keep-alive`), the connection is idle for
+/// [`ServerTimeouts::keep_alive_timeout`], or
+/// [`HttpServer::with_max_requests`] requests have been served.
+///
+/// Responses are written straight to the socket, so their size is not
+/// limited by a buffer.
+///
+/// # Deprecated
+///
+/// The `MAX_RESPONSE_SIZE` parameter is ignored and will be removed in a
+/// future release. Leave it at its default, and only set it to reach the
+/// `TLS_READ` and `TLS_WRITE` parameters.
+///
+/// With the `tls` feature, [`HttpServer::serve_tls`] serves HTTPS. Incoming
+/// TLS records are read into a `TLS_READ` buffer and responses are sent in
+/// records of at most `TLS_WRITE` bytes.
 pub struct HttpServer<
     const RX_SIZE: usize,
     const TX_SIZE: usize,
     const REQ_SIZE: usize,
-    const MAX_RESPONSE_SIZE: usize,
+    const MAX_RESPONSE_SIZE: usize = DEFAULT_MAX_RESPONSE_SIZE,
+    const TLS_READ: usize = SERVER_BUFFER_SIZE,
+    const TLS_WRITE: usize = SERVER_BUFFER_SIZE,
 > {
     port: u16,
     timeouts: ServerTimeouts,
+    max_requests: usize,
 }
 
 impl<
@@ -69,7 +153,9 @@ impl<
     const TX_SIZE: usize,
     const REQ_SIZE: usize,
     const MAX_RESPONSE_SIZE: usize,
-> HttpServer<RX_SIZE, TX_SIZE, REQ_SIZE, MAX_RESPONSE_SIZE>
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+> HttpServer<RX_SIZE, TX_SIZE, REQ_SIZE, MAX_RESPONSE_SIZE, TLS_READ, TLS_WRITE>
 {
     /// Create a new HTTP server with default timeouts
     #[must_use]
@@ -77,205 +163,469 @@ impl<
         Self {
             port,
             timeouts: ServerTimeouts::default(),
+            max_requests: DEFAULT_MAX_REQUESTS,
         }
     }
 
     /// Create a new HTTP server with custom timeouts
     #[must_use]
     pub const fn with_timeouts(port: u16, timeouts: ServerTimeouts) -> Self {
-        Self { port, timeouts }
+        Self {
+            port,
+            timeouts,
+            max_requests: DEFAULT_MAX_REQUESTS,
+        }
+    }
+
+    /// Limit the number of requests served on one connection (default 100)
+    ///
+    /// The response to the last request carries `Connection: close`. A limit
+    /// of 1 disables keep-alive.
+    #[must_use]
+    pub const fn with_max_requests(mut self, max_requests: usize) -> Self {
+        self.max_requests = max_requests;
+        self
     }
 
     /// Start the HTTP server and handle incoming connections
     ///
-    /// **Important**: This server only accepts plain HTTP connections.
-    /// HTTPS/TLS is not supported by the server (only by the client).
+    /// Connections are accepted from `acceptor`, usually an embassy-net
+    /// [`Stack`](embassy_net::Stack) listening on the server's port, or any
+    /// other [`Acceptor`]. `handler` is any [`HttpHandler`](crate::HttpHandler),
+    /// or a [`StreamingHandler`] that writes large responses piece by piece.
+    /// Connections are served one at a time. Use
+    /// [`HttpServer::serve_concurrent`] to serve several at once.
     #[expect(clippy::future_not_send)]
-    pub async fn serve<H>(&mut self, stack: Stack<'_>, mut handler: H) -> !
+    pub async fn serve<A, H>(&mut self, acceptor: A, handler: H) -> !
     where
-        H: HttpHandler,
+        A: Acceptor,
+        H: StreamingHandler,
     {
         info!("HTTP server started on port {}", self.port);
 
-        let mut rx_buffer = [0; RX_SIZE];
-        let mut tx_buffer = [0; TX_SIZE];
-        let mut buf = [0; REQ_SIZE];
+        let mut buffers = ServerBuffers::<RX_SIZE, TX_SIZE, REQ_SIZE>::new();
+        self.serve_shared(acceptor, handler, &mut buffers).await
+    }
+
+    /// Serve up to `N` connections concurrently within the calling task
+    ///
+    /// Each entry of `buffers` backs one connection, so `N` clients can be
+    /// connected at the same time. Every connection is served with its own
+    /// clone of `handler`, so a slow client, an event stream or a WebSocket
+    /// session only occupies its own slot. Handlers that share state keep it
+    /// behind a reference, with interior mutability where it changes.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use embassy_net::Stack;
+    /// use nanofish::{DefaultHttpServer, DefaultServerBuffers, SimpleHandler};
+    ///
+    /// // Four connections with 4 KB socket and request buffers each, usually
+    /// // kept in a `static` so they stay off the task's stack
+    /// async fn example(stack: Stack<'_>, buffers: &mut [DefaultServerBuffers; 4]) -> ! {
+    ///     let mut server = DefaultHttpServer::new(80);
+    ///     server.serve_concurrent(stack, SimpleHandler, buffers).await
+    /// }
+    /// ```
+    #[expect(clippy::future_not_send)]
+    pub async fn serve_concurrent<A, H, const N: usize>(
+        &mut self,
+        acceptor: A,
+        handler: H,
+        buffers: &mut [ServerBuffers<RX_SIZE, TX_SIZE, REQ_SIZE>; N],
+    ) -> !
+    where
+        A: Acceptor,
+        H: StreamingHandler + Clone,
+    {
+        info!(
+            "HTTP server started on port {} with {} connections",
+            self.port, N
+        );
+
+        let server = &*self;
+        join_array(
+            buffers
+                .each_mut()
+                .map(|buffers| server.serve_shared(&acceptor, handler.clone(), buffers)),
+        )
+        .await;
 
+        // Only reached when no buffers were given
         loop {
-            let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
-            socket.set_timeout(Some(Duration::from_secs(self.timeouts.accept_timeout)));
+            core::future::pending::<()>().await;
+        }
+    }
 
-            if let Err(e) = socket.accept(self.port).await {
+    /// Serve connections one at a time, alongside other callers sharing the
+    /// server and acceptor
+    ///
+    /// This is the building block of [`HttpServer::serve_concurrent`]. Call it
+    /// from several tasks, each with its own `handler` and buffers, to spread
+    /// connections across tasks. No handler is shared between connections, so
+    /// one connection never waits for another's response to be written.
+    #[expect(clippy::future_not_send)]
+    pub async fn serve_shared<A, H>(
+        &self,
+        acceptor: A,
+        mut handler: H,
+        buffers: &mut ServerBuffers<RX_SIZE, TX_SIZE, REQ_SIZE>,
+    ) -> !
+    where
+        A: Acceptor,
+        H: StreamingHandler,
+    {
+        loop {
+            let Some(mut connection) = self
+                .accept(&acceptor, &mut buffers.rx, &mut buffers.tx)
+                .await
+            else {
+                continue;
+            };
+
+            self.serve_connection(&mut connection, &mut handler, &mut buffers.request)
+                .await;
+            connection.close().await;
+        }
+    }
+
+    /// Start an HTTPS server and handle incoming connections
+    ///
+    /// Like [`HttpServer::serve`], but every connection starts with a TLS 1.3
+    /// handshake in which the server presents `identity`. `rng` provides the
+    /// randomness of the handshake and should be a cryptographically secure
+    /// source such as a hardware TRNG.
+    ///
+    /// The handshake uses `TLS_AES_128_GCM_SHA256` with P-256 key exchange,
+    /// which every TLS 1.3 client supports, and has to complete within
+    /// [`ServerTimeouts::read_timeout`]. Clients that announce a record size
+    /// limit are asked to keep their records within `TLS_READ`; other clients
+    /// must not send records larger than `TLS_READ`, which limits the request
+    /// size in practice. Connections are served one at a time.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use core::cell::RefCell;
+    /// use embassy_net::Stack;
+    /// use nanofish::{DefaultHttpServer, SimpleHandler, TlsIdentity};
+    /// use rand_core::CryptoRngCore;
+    ///
+    /// // The DER certificate and the raw P-256 private key of the device
+    /// async fn example<R: CryptoRngCore + 'static>(
+    ///     stack: Stack<'_>,
+    ///     certificate: &[u8],
+    ///     key: &[u8; 32],
+    ///     trng: &RefCell<R>,
+    /// ) -> ! {
+    ///     let identity = TlsIdentity {
+    ///         certificate_chain: &[certificate],
+    ///         private_key: key,
+    ///     };
+    ///     let mut server = DefaultHttpServer::new(443);
+    ///     server.serve_tls(stack, identity, trng, SimpleHandler).await
+    /// }
+    /// ```
+    #[cfg(feature = "tls")]
+    #[expect(clippy::future_not_send)]
+    pub async fn serve_tls<A, H>(
+        &mut self,
+        acceptor: A,
+        identity: TlsIdentity<'_>,
+        rng: &RefCell<dyn CryptoRngCore>,
+        mut handler: H,
+    ) -> !
+    where
+        A: Acceptor,
+        H: StreamingHandler,
+    {
+        info!("HTTPS server started on port {}", self.port);
+
+        let mut buffers = ServerBuffers::<RX_SIZE, TX_SIZE, REQ_SIZE>::new();
+        let mut tls_read = [0; TLS_READ];
+        let mut tls_write = [0; TLS_WRITE];
+        loop {
+            let Some(mut transport) = self
+                .accept(&acceptor, &mut buffers.rx, &mut buffers.tx)
+                .await
+            else {
+                continue;
+            };
+
+            let handshake = with_timeout(
+                Duration::from_secs(self.timeouts.read_timeout),
+                TlsServerConnection::accept(
+                    &mut transport,
+                    &mut tls_read,
+                    &mut tls_write,
+                    &identity,
+                    rng,
+                ),
+            )
+            .await;
+            match handshake {
+                Ok(Ok(mut connection)) => {
+                    self.serve_connection(&mut connection, &mut handler, &mut buffers.request)
+                        .await;
+                    connection.close().await;
+                }
+                Ok(Err(e)) => warn!("TLS handshake failed: {:?}", e),
+                Err(_) => warn!("TLS handshake timed out"),
+            }
+            transport.close().await;
+        }
+    }
+
+    /// Accept a connection from `acceptor`.
+    ///
+    /// Returns `None` if accepting failed.
+    #[expect(clippy::future_not_send)]
+    async fn accept<'b, A: Acceptor>(
+        &self,
+        acceptor: &'b A,
+        rx_buffer: &'b mut [u8],
+        tx_buffer: &'b mut [u8],
+    ) -> Option<A::Transport<'b>> {
+        let timeout = Duration::from_secs(self.timeouts.accept_timeout);
+        match acceptor
+            .accept(self.port, timeout, rx_buffer, tx_buffer)
+            .await
+        {
+            Ok(transport) => Some(transport),
+            Err(e) => {
                 warn!("Accept error: {:?}", e);
                 Timer::after(Duration::from_millis(100)).await;
-                continue;
+                None
             }
+        }
+    }
+
+    /// Serve requests on an accepted connection until it should be closed.
+    ///
+    /// Bytes following a request in `buf` belong to the next, pipelined
+    /// request and are kept for the next iteration.
+    async fn serve_connection<C, H>(&self, connection: &mut C, handler: &mut H, buf: &mut [u8])
+    where
+        C: Read + Write<Error = Error>,
+        H: StreamingHandler,
+    {
+        let mut buffered = 0;
+        let mut served = 0;
 
-            // Reset socket timeout after accept so it doesn't race with read timeout
-            socket.set_timeout(None);
+        loop {
+            if served > 0 && buffered == 0 {
+                // Idle connection: wait for the next request
+                let idle = Duration::from_secs(self.timeouts.keep_alive_timeout);
+                match with_timeout(idle, connection.read(buf)).await {
+                    Ok(Ok(0)) => return,
+                    Ok(Ok(n)) => buffered = n,
+                    Ok(Err(e)) => {
+                        warn!("Read error: {:?}", e);
+                        return;
+                    }
+                    Err(_) => {
+                        debug!("Keep-alive timeout");
+                        return;
+                    }
+                }
+            }
 
-            // Read loop: accumulate data until headers + body are complete
-            let mut total_read = 0;
-            let read_ok = match with_timeout(
+            let read = match with_timeout(
                 Duration::from_secs(self.timeouts.read_timeout),
-                Self::read_request(&mut socket, &mut buf, &mut total_read),
+                Self::read_request(connection, buf, &mut buffered),
             )
             .await
             {
-                Ok(Ok(())) => true,
+                Ok(Ok(read)) => read,
+                Ok(Err(
+                    e @ (Error::BufferOverflow | Error::HeaderError(_) | Error::InvalidRequest(_)),
+                )) => {
+                    warn!("Invalid request: {:?}", e);
+                    let status = match e {
+                        Error::BufferOverflow => StatusCode::RequestEntityTooLarge,
+                        Error::HeaderError(_) => StatusCode::RequestHeaderFieldsTooLarge,
+                        _ => StatusCode::BadRequest,
+                    };
+                    Self::send_error(connection, HttpMethod::GET, status).await;
+                    return;
+                }
                 Ok(Err(e)) => {
                     warn!("Read error: {:?}", e);
-                    false
+                    return;
                 }
                 Err(_) => {
                     warn!("Socket read timeout");
-                    false
+                    return;
                 }
             };
 
-            if !read_ok || total_read == 0 {
-                socket.close();
-                continue;
-            }
-
-            // Parse the request
-            match self
-                .handle_connection(&buf[..total_read], &mut handler)
-                .await
-            {
-                Ok(response_bytes) => {
-                    if let Err(e) = socket.write_all(&response_bytes).await {
-                        warn!("Failed to write response: {:?}", e);
-                    }
-                    if let Err(e) = socket.flush().await {
-                        warn!("Failed to flush response: {:?}", e);
-                    }
-                }
-                Err(e) => {
-                    error!("Error handling request: {:?}", e);
-                    if let Ok(error_bytes) = Self::text_error_response(
-                        StatusCode::InternalServerError,
-                        "Internal Server Error",
-                    ) {
-                        let _ = socket.write_all(&error_bytes).await;
-                        let _ = socket.flush().await;
-                    }
-                }
+            // The client closed the connection, possibly in the middle of a
+            // request that is then dropped
+            let Some((message_len, message)) = read else {
+                return;
+            };
+            served += 1;
+            let keep_alive = message.head.keep_alive && served < self.max_requests;
+
+            let keep_alive = self
+                .respond(connection, &buf[..message_len], handler, keep_alive)
+                .await;
+            if !keep_alive {
+                return;
             }
 
-            socket.close();
+            buf.copy_within(message.len..buffered, 0);
+            buffered -= message.len;
         }
     }
 
-    /// Read a complete HTTP request from the socket.
+    /// Read until `buf` holds a complete HTTP request.
     ///
-    /// Accumulates data until headers are found (`\r\n\r\n`), then reads
-    /// any remaining body bytes indicated by `Content-Length`.
-    #[expect(clippy::future_not_send)]
-    async fn read_request(
-        socket: &mut TcpSocket<'_>,
+    /// `buffered` is the number of bytes already in `buf` and is updated as
+    /// data arrives. Returns the request with a chunked body decoded in
+    /// place, along with the length of the request after decoding, or `None`
+    /// if the client stopped sending before it was complete.
+    ///
+    /// A request that does not fit into `buf` fails with
+    /// `Error::HeaderError` if its head is too large, or with
+    /// `Error::BufferOverflow` if its body is.
+    async fn read_request<R: Read<Error = Error>>(
+        connection: &mut R,
         buf: &mut [u8],
-        total_read: &mut usize,
-    ) -> Result<(), embassy_net::tcp::Error> {
-        let mut header_end = None;
-
-        while *total_read < buf.len() {
-            let n = socket.read(&mut buf[*total_read..]).await?;
-            if n == 0 {
-                break;
+        buffered: &mut usize,
+    ) -> Result<Option<(usize, Message)>, Error> {
+        let mut parser = Parser::request();
+        loop {
+            if let Some(message) = parser.parse(&buf[..*buffered])? {
+                return Ok(Some((message.decode_body(buf), message)));
             }
-            *total_read += n;
-
-            // Look for end of headers if not yet found
-            if header_end.is_none() {
-                header_end = protocol::find_double_crlf(&buf[..*total_read]);
+            if *buffered == buf.len() {
+                return Err(if parser.framing().is_some() {
+                    Error::BufferOverflow
+                } else {
+                    Error::HeaderError("Request head too large")
+                });
             }
 
-            if let Some(hdr_end) = header_end {
-                let body_start = hdr_end + DOUBLE_CRLF_LEN;
-                // Try to determine Content-Length from the headers
-                if let Some(cl) = Self::parse_content_length(&buf[..hdr_end]) {
-                    if *total_read >= body_start + cl {
-                        break;
-                    }
-                } else {
-                    // No Content-Length — headers are complete, no body expected
-                    break;
-                }
+            let n = connection.read(&mut buf[*buffered..]).await?;
+            if n == 0 {
+                return Ok(None);
             }
+            *buffered += n;
         }
-        Ok(())
-    }
-
-    /// Extract `Content-Length` value from raw header bytes.
-    fn parse_content_length(header_bytes: &[u8]) -> Option<usize> {
-        let headers_str = core::str::from_utf8(header_bytes).ok()?;
-        protocol::find_header_value(headers_str, CONTENT_LENGTH)?
-            .parse()
-            .ok()
     }
 
-    /// Build a plain-text error response.
-    fn text_error_response(
-        status: StatusCode,
-        body: &str,
-    ) -> Result<Vec<u8, MAX_RESPONSE_SIZE>, Error> {
-        let mut headers = Vec::new();
-        let _ = headers.push(HttpHeader::content_type(mime_types::TEXT));
-        let resp = HttpResponse {
-            status_code: status,
-            headers,
-            body: ResponseBody::Text(body),
-        };
-        resp.build_bytes::<MAX_RESPONSE_SIZE>()
-    }
-
-    async fn handle_connection<H>(
+    /// Handle one request and write the response to `sink`.
+    ///
+    /// Returns whether the connection stays open, which requires
+    /// `keep_alive`, a client that wants a persistent connection and a
+    /// complete response without `Connection: close`.
+    async fn respond<C, H>(
         &self,
+        sink: &mut C,
         buffer: &[u8],
         handler: &mut H,
-    ) -> Result<Vec<u8, MAX_RESPONSE_SIZE>, Error>
+        keep_alive: bool,
+    ) -> bool
     where
-        H: HttpHandler,
+        C: Read + Write<Error = Error>,
+        H: StreamingHandler,
     {
-        // Parse the request
-        let request = HttpRequest::try_from(buffer)?;
+        let request = match HttpRequest::try_from(buffer) {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("Invalid request: {:?}", e);
+                Self::send_error(sink, HttpMethod::GET, StatusCode::BadRequest).await;
+                return false;
+            }
+        };
 
-        // Handle the request
-        let response = match with_timeout(
-            Duration::from_secs(self.timeouts.handler_timeout),
-            handler.handle_request(&request),
-        )
-        .await
-        {
-            Ok(Ok(response)) => response,
-            Ok(Err(e)) => {
+        let keep_alive = keep_alive && request.is_keep_alive();
+        let chunked_allowed = !request.version.eq_ignore_ascii_case(HTTP_VERSION_1_0);
+        let state = ResponseState::new();
+        let mut response =
+            ResponseWriter::new(sink, &state, request.method, keep_alive, chunked_allowed);
+
+        let result = {
+            let mut handling = pin!(handler.handle_streaming(&request, &mut response));
+            let timeout = Timer::after(Duration::from_secs(self.timeouts.handler_timeout));
+            match select(&mut handling, timeout).await {
+                Either::First(result) => Some(result),
+                Either::Second(()) if state.get() == WriterState::Pending => None,
+                // The timeout only covers producing the response, so event
+                // streams and other long responses can keep going
+                Either::Second(()) => Some(handling.await),
+            }
+        };
+
+        let status = match result {
+            Some(Ok(())) if response.is_finished() => return response.keep_alive(),
+            Some(Ok(())) => {
+                warn!("Handler did not finish the response");
+                StatusCode::InternalServerError
+            }
+            Some(Err(e)) => {
                 warn!("Handler error: {:?}", e);
-                return Self::text_error_response(
-                    StatusCode::InternalServerError,
-                    "Internal Server Error",
-                );
+                StatusCode::InternalServerError
             }
-            Err(_) => {
+            None => {
                 warn!("Request handling timed out");
-                return Self::text_error_response(StatusCode::RequestTimeout, "Request Timeout");
+                StatusCode::RequestTimeout
             }
         };
 
-        response.build_bytes::<MAX_RESPONSE_SIZE>()
+        // Once the response has started the client can only learn about the
+        // failure from the connection closing
+        if !response.is_started() {
+            Self::send_error(sink, request.method, status).await;
+        }
+        false
+    }
+
+    /// Send a plain-text error response that closes the connection.
+    ///
+    /// The body is left out if `method` is `HEAD`.
+    async fn send_error<W: Write<Error = Error>>(
+        sink: &mut W,
+        method: HttpMethod,
+        status: StatusCode,
+    ) {
+        let mut headers = Vec::new();
+        let _ = headers.push(HttpHeader::content_type(mime_types::TEXT));
+        let response = HttpResponse {
+            status_code: status,
+            headers,
+            body: ResponseBody::Text(status.text()),
+        };
+        let state = ResponseState::new();
+        let mut writer = ResponseWriter::new(sink, &state, method, false, true);
+        if let Err(e) = writer.send(&response).await {
+            warn!("Failed to write response: {:?}", e);
+        }
     }
 }
 
 /// Type alias for `HttpServer` with default buffer sizes (4KB each)
-pub type DefaultHttpServer =
-    HttpServer<SERVER_BUFFER_SIZE, SERVER_BUFFER_SIZE, MAX_REQUEST_SIZE, DEFAULT_MAX_RESPONSE_SIZE>;
+pub type DefaultHttpServer = HttpServer<SERVER_BUFFER_SIZE, SERVER_BUFFER_SIZE, MAX_REQUEST_SIZE>;
 
 /// Type alias for `HttpServer` with small buffer sizes for memory-constrained environments (1KB each)
-pub type SmallHttpServer = HttpServer<1024, 1024, 1024, 1024>;
+pub type SmallHttpServer = HttpServer<1024, 1024, 1024>;
+
+/// Connection buffers matching [`DefaultHttpServer`]
+pub type DefaultServerBuffers =
+    ServerBuffers<SERVER_BUFFER_SIZE, SERVER_BUFFER_SIZE, MAX_REQUEST_SIZE>;
+
+/// Connection buffers matching [`SmallHttpServer`]
+pub type SmallServerBuffers = ServerBuffers<1024, 1024, 1024>;
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        SimpleHandler,
+        testing::{MockPeer, Step},
+    };
 
     #[test]
     fn test_http_server_creation() {
@@ -285,8 +635,213 @@ mod tests {
         assert_eq!(server.timeouts.read_timeout, 30);
         assert_eq!(server.timeouts.handler_timeout, 60);
 
-        let server: SmallHttpServer = HttpServer::new(3000);
+        assert_eq!(server.max_requests, DEFAULT_MAX_REQUESTS);
+
+        let server: SmallHttpServer = HttpServer::new(3000).with_max_requests(1);
         assert_eq!(server.port, 3000);
+        assert_eq!(server.max_requests, 1);
+    }
+
+    fn read_request(request: &[u8]) -> Result<Option<(std::vec::Vec<u8>, usize)>, Error> {
+        let mut buf = request.to_vec();
+        // Leave room so that an incomplete request is not a full buffer
+        buf.resize(request.len() + 64, 0);
+        let mut buffered = request.len();
+        // The client has stopped sending
+        let peer: MockPeer = MockPeer::new(&[]);
+        let read = futures_lite::future::block_on(DefaultHttpServer::read_request(
+            &mut peer.connection(),
+            &mut buf,
+            &mut buffered,
+        ))?;
+        Ok(read.map(|(message_len, message)| (buf[..message_len].to_vec(), message.len)))
+    }
+
+    fn complete_request(request: &[u8]) -> Option<(std::vec::Vec<u8>, usize)> {
+        read_request(request).unwrap()
+    }
+
+    #[test]
+    fn test_complete_request() {
+        let pipelined = b"GET / HTTP/1.1\r\n\r\nPOST /a HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc";
+        let (request, len) = complete_request(pipelined).unwrap();
+        assert_eq!(request, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(len, 18);
+        assert_eq!(complete_request(&pipelined[18..]).unwrap().1, 42);
+        assert_eq!(complete_request(&pipelined[18..59]), None);
+        assert_eq!(complete_request(b"GET / HTTP/1.1\r\n"), None);
+    }
+
+    #[test]
+    fn test_complete_request_chunked() {
+        let head = b"POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let mut wire = head.to_vec();
+        wire.extend_from_slice(b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\nGET / HTTP/1.1\r\n\r\n");
+
+        let (request, len) = complete_request(&wire).unwrap();
+        assert_eq!(len, wire.len() - 18);
+        let parsed = HttpRequest::try_from(request.as_slice()).unwrap();
+        assert_eq!(parsed.body, b"hello world");
+
+        // Incomplete until the final chunk arrives
+        assert_eq!(complete_request(&wire[..head.len() + 12]), None);
+
+        let mut invalid = head.to_vec();
+        invalid.extend_from_slice(b"zz\r\n");
+        assert!(read_request(&invalid).is_err());
+    }
+
+    fn handle<H: StreamingHandler>(
+        handler: &mut H,
+        request: &[u8],
+        keep_alive: bool,
+    ) -> (std::string::String, bool) {
+        let server = DefaultHttpServer::new(80);
+        let peer: MockPeer = MockPeer::new(&[]);
+        let keep_alive = futures_lite::future::block_on(server.respond(
+            &mut peer.connection(),
+            request,
+            handler,
+            keep_alive,
+        ));
+        let response = std::string::String::from_utf8(peer.written().to_vec()).unwrap();
+        (response, keep_alive)
+    }
+
+    #[test]
+    fn test_respond_keep_alive() {
+        let handler = &mut crate::SimpleHandler;
+        let (response, keep_alive) = handle(handler, b"GET /health HTTP/1.1\r\n\r\n", true);
+        assert!(keep_alive);
+        assert!(response.contains("Content-Length: 15\r\n"));
+        assert!(response.contains("Connection: keep-alive\r\n"));
+
+        // Last request allowed on the connection
+        let (response, keep_alive) = handle(handler, b"GET /health HTTP/1.1\r\n\r\n", false);
+        assert!(!keep_alive);
+        assert!(response.contains("Connection: close\r\n"));
+
+        let request = b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n";
+        let (_, keep_alive) = handle(handler, request, true);
+        assert!(!keep_alive);
+
+        let (response, keep_alive) = handle(handler, b"GET / HTTP/1.0\r\n\r\n", true);
+        assert!(!keep_alive);
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn test_respond_invalid_request() {
+        let handler = &mut crate::SimpleHandler;
+        let (response, keep_alive) = handle(handler, b"BREW /pot HTTP/1.1\r\n\r\n", true);
+        assert!(!keep_alive);
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    /// Streams the request path back, failing at the stage named by it
+    struct Echo;
+
+    impl StreamingHandler for Echo {
+        async fn handle_streaming<W: Read + Write<Error = Error>>(
+            &mut self,
+            request: &HttpRequest<'_>,
+            response: &mut ResponseWriter<'_, W>,
+        ) -> Result<(), Error> {
+            if request.path == "/fail-early" {
+                return Err(Error::InvalidResponse("fail"));
+            }
+            let mut body = response.start(StatusCode::Ok, &[], None).await?;
+            for part in request.path.as_bytes().chunks(2) {
+                body.write_all(part).await?;
+            }
+            if request.path == "/fail-late" {
+                return Err(Error::InvalidResponse("fail"));
+            }
+            body.finish().await
+        }
+    }
+
+    #[test]
+    fn test_respond_streaming() {
+        let (response, keep_alive) = handle(&mut Echo, b"GET /a/b HTTP/1.1\r\n\r\n", true);
+        assert!(keep_alive);
+        assert!(response.ends_with(
+            "Transfer-Encoding: chunked\r\nConnection: keep-alive\r\n\r\n\
+             2\r\n/a\r\n2\r\n/b\r\n0\r\n\r\n"
+        ));
+
+        let (response, keep_alive) = handle(&mut Echo, b"GET /fail-early HTTP/1.1\r\n\r\n", true);
+        assert!(!keep_alive);
+        assert!(response.starts_with("HTTP/1.1 500 Internal Server Error\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+
+        // A started response cannot be replaced by an error
+        let (response, keep_alive) = handle(&mut Echo, b"GET /fail-late HTTP/1.1\r\n\r\n", true);
+        assert!(!keep_alive);
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(!response.contains("500"));
+    }
+
+    #[test]
+    fn test_serve_custom_acceptor() {
+        let script = [Step::Send(b"GET /health HTTP/1.1\r\nHost: device\r\n\r\n")];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut server = SmallHttpServer::new(80);
+        // The connection closes once the client side has sent everything
+        futures_lite::future::block_on(select(server.serve(&peer, SimpleHandler), peer.closed()));
+
+        let response = std::string::String::from_utf8(peer.written().to_vec()).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n{\"status\":\"ok\"}"));
+    }
+
+    /// Starts the response to `/stall` and then never finishes it
+    #[derive(Clone)]
+    struct Stall;
+
+    impl StreamingHandler for Stall {
+        async fn handle_streaming<W: Read + Write<Error = Error>>(
+            &mut self,
+            request: &HttpRequest<'_>,
+            response: &mut ResponseWriter<'_, W>,
+        ) -> Result<(), Error> {
+            if request.path == "/stall" {
+                let _body = response.start(StatusCode::Ok, &[], None).await?;
+                core::future::pending::<()>().await;
+            }
+            SimpleHandler.handle_streaming(request, response).await
+        }
+    }
+
+    #[test]
+    fn test_serve_concurrent_stalled_connection() {
+        let stalled = [Step::Send(b"GET /stall HTTP/1.1\r\n\r\n")];
+        let healthy = [Step::Send(
+            b"GET /health HTTP/1.1\r\nConnection: close\r\n\r\n",
+        )];
+        let peers: [MockPeer<'_, 1024>; 2] = [MockPeer::new(&stalled), MockPeer::new(&healthy)];
+        let mut server = SmallHttpServer::new(80);
+        let mut buffers = [SmallServerBuffers::new(), SmallServerBuffers::new()];
+
+        // The second connection is served while the first one's handler is stuck
+        futures_lite::future::block_on(select(
+            Box::pin(server.serve_concurrent(&peers[..], Stall, &mut buffers)),
+            peers[1].closed(),
+        ));
+
+        assert!(peers[0].written().starts_with(b"HTTP/1.1 200 OK\r\n"));
+        assert!(peers[1].written().ends_with(b"\r\n\r\n{\"status\":\"ok\"}"));
+    }
+
+    #[test]
+    fn test_server_buffers() {
+        let buffers = DefaultServerBuffers::default();
+        assert_eq!(buffers.rx.len(), SERVER_BUFFER_SIZE);
+        assert_eq!(buffers.tx.len(), SERVER_BUFFER_SIZE);
+        assert_eq!(buffers.request.len(), MAX_REQUEST_SIZE);
+
+        let buffers = SmallServerBuffers::new();
+        assert_eq!(buffers.request.len(), 1024);
     }
 
     #[test]
@@ -296,15 +851,23 @@ mod tests {
         assert_eq!(timeouts.accept_timeout, 10);
         assert_eq!(timeouts.read_timeout, 30);
         assert_eq!(timeouts.handler_timeout, 60);
+        assert_eq!(timeouts.keep_alive_timeout, 5);
 
         // Test custom timeouts
         let custom_timeouts = ServerTimeouts::new(5, 15, 45);
         assert_eq!(custom_timeouts.accept_timeout, 5);
         assert_eq!(custom_timeouts.read_timeout, 15);
         assert_eq!(custom_timeouts.handler_timeout, 45);
+        assert_eq!(custom_timeouts.keep_alive_timeout, 5);
+        assert_eq!(
+            custom_timeouts
+                .with_keep_alive_timeout(2)
+                .keep_alive_timeout,
+            2
+        );
 
         // Test server with custom timeouts
-        let server = HttpServer::<1024, 1024, 1024, 1024>::with_timeouts(8080, custom_timeouts);
+        let server = HttpServer::<1024, 1024, 1024>::with_timeouts(8080, custom_timeouts);
         assert_eq!(server.port, 8080);
         assert_eq!(server.timeouts.accept_timeout, 5);
         assert_eq!(server.timeouts.read_timeout, 15);
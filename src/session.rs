@@ -0,0 +1,687 @@
+use crate::{
+    client::HttpClient,
+    connection::Connection,
+    error::Error,
+    header::HttpHeader,
+    method::HttpMethod,
+    parser::{BodyFraming, Message, Parser},
+    response::HttpResponse,
+    transport::{Connector, TcpConnector, Transport},
+    url::Url,
+};
+use core::marker::PhantomData;
+#[cfg(feature = "tls")]
+use core::ptr::NonNull;
+use embassy_net::tcp;
+use embassy_time::Instant;
+use embedded_io_async::{Read as EmbeddedRead, Write as EmbeddedWrite};
+#[cfg(feature = "tls")]
+use embedded_tls::TlsError;
+
+/// Client type used to reach the shared request building and parsing helpers
+type Client<
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+> = HttpClient<'static, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ>;
+
+/// Socket and TLS record buffers backing an [`HttpSession`]
+///
+/// The buffers are owned by the caller so that they outlive the session's
+/// connection. The same buffers can be reused for a new session once the
+/// previous one has been dropped. They also back the connection of a
+/// [`StreamingResponse`](crate::StreamingResponse).
+pub struct SessionBuffers<
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+> {
+    pub(crate) tcp_rx: [u8; TCP_RX],
+    pub(crate) tcp_tx: [u8; TCP_TX],
+    #[cfg(feature = "tls")]
+    pub(crate) tls_read: [u8; TLS_READ],
+    #[cfg(feature = "tls")]
+    pub(crate) tls_write: [u8; TLS_WRITE],
+}
+
+impl<const TCP_RX: usize, const TCP_TX: usize, const TLS_READ: usize, const TLS_WRITE: usize>
+    SessionBuffers<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>
+{
+    /// Create a new set of zeroed session buffers
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            tcp_rx: [0; TCP_RX],
+            tcp_tx: [0; TCP_TX],
+            #[cfg(feature = "tls")]
+            tls_read: [0; TLS_READ],
+            #[cfg(feature = "tls")]
+            tls_write: [0; TLS_WRITE],
+        }
+    }
+}
+
+impl<const TCP_RX: usize, const TCP_TX: usize, const TLS_READ: usize, const TLS_WRITE: usize>
+    Default for SessionBuffers<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A persistent HTTP/1.1 connection to a single origin
+///
+/// A session keeps its TCP socket (and TLS session, for `https` origins) open
+/// between requests and sends `Connection: keep-alive`, so repeated requests
+/// to the same backend skip DNS resolution, the TCP handshake and the TLS
+/// handshake. Responses are read exactly by `Content-Length` or chunk
+/// framing, so the connection is ready for the next request as soon as a
+/// response has been returned.
+///
+/// The connection is given up when the server sends `Connection: close`,
+/// when a response is delimited by connection close, when an error occurs, or
+/// when the connection has been idle for longer than
+/// [`HttpClientOptions::keep_alive_timeout`](crate::HttpClientOptions::keep_alive_timeout).
+/// The next request then reconnects to the origin with the same buffers,
+/// redoing the TLS handshake for `https` origins. A server may also drop an
+/// idle connection just as a request is sent; such a request is sent again on
+/// a new connection if its method is idempotent.
+///
+/// # Examples
+///
+/// ```no_run
+/// use nanofish::{DefaultHttpClient, SessionBuffers};
+/// use embassy_net::Stack;
+///
+/// async fn poll(stack: &Stack<'_>) -> Result<(), nanofish::Error> {
+///     let client = DefaultHttpClient::new(stack);
+///     let mut buffers = SessionBuffers::new();
+///     let mut response_buffer = [0u8; 2048];
+///
+///     let mut session = client.session("http://example.com", &mut buffers).await?;
+///     for _ in 0..10 {
+///         let (response, _) = session.get("/status", &[], &mut response_buffer).await?;
+///         if !response.is_success() {
+///             break;
+///         }
+///     }
+///     session.close().await;
+///     Ok(())
+/// }
+/// ```
+pub struct HttpSession<
+    's,
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector + 's = TcpConnector<'s>,
+> {
+    client: &'s HttpClient<'s, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>,
+    scheme: &'static str,
+    host: &'s str,
+    port: u16,
+    link: Link<'s, C::Transport<'s>>,
+    /// The record buffers TLS connections of an `https` session are opened with
+    #[cfg(feature = "tls")]
+    tls_buffers: Option<TlsBuffers<'s>>,
+    open: bool,
+    /// Whether a response has been read on the current connection
+    reused: bool,
+    last_used: Instant,
+}
+
+impl<
+    'a,
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector,
+> HttpClient<'a, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
+    /// Open a persistent session to `origin`
+    ///
+    /// `origin` is a URL without a path, such as `http://example.com:8080`.
+    /// The connection (and TLS handshake for `https`) is established before
+    /// this method returns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the origin is malformed or contains a path, if DNS
+    /// resolution or the TCP connection fails, or if the TLS handshake fails.
+    #[expect(clippy::future_not_send)]
+    pub async fn session<'s>(
+        &'s self,
+        origin: &'s str,
+        buffers: &'s mut SessionBuffers<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>,
+    ) -> Result<HttpSession<'s, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>, Error>
+    where
+        'a: 's,
+    {
+        let url = Url::parse(origin)?;
+        if !matches!(url.path(), "" | "/") || url.query().is_some() {
+            return Err(Error::InvalidUrl);
+        }
+        let (scheme, (host, port)) = Self::connection_target(&url)?;
+
+        let SessionBuffers {
+            tcp_rx,
+            tcp_tx,
+            #[cfg(feature = "tls")]
+            tls_read,
+            #[cfg(feature = "tls")]
+            tls_write,
+        } = buffers;
+        let transport = self
+            .open_transport(scheme, (host, port), tcp_rx, tcp_tx)
+            .await?;
+        let mut session = HttpSession {
+            client: self,
+            scheme,
+            host,
+            port,
+            link: Link::Lost,
+            #[cfg(feature = "tls")]
+            tls_buffers: (scheme == "https").then(|| TlsBuffers::new(tls_read, tls_write)),
+            open: false,
+            reused: false,
+            last_used: Instant::now(),
+        };
+        session.link = Link::Open(session.secure(transport).await?);
+        session.open = true;
+        Ok(session)
+    }
+}
+
+/// The connection of an [`HttpSession`]
+#[cfg_attr(feature = "tls", expect(clippy::large_enum_variant))]
+enum Link<'s, T: Transport + 's> {
+    /// Open, though the server may have closed it since
+    Open(Connection<'s, T>),
+    /// Given up, with a transport that failed to reconnect and may be tried again
+    Closed(T),
+    /// Lost for good, after a TLS handshake on a new connection failed
+    Lost,
+}
+
+/// The TLS record buffers of an `https` session
+///
+/// A TLS connection keeps the buffers it was opened with, and cannot be
+/// reopened once the server has closed it. The session lends the buffers to
+/// each new TLS connection in turn, which the borrow checker cannot follow.
+#[cfg(feature = "tls")]
+struct TlsBuffers<'s> {
+    read: NonNull<[u8]>,
+    write: NonNull<[u8]>,
+    buffers: PhantomData<&'s mut [u8]>,
+}
+
+#[cfg(feature = "tls")]
+impl<'s> TlsBuffers<'s> {
+    fn new(read: &'s mut [u8], write: &'s mut [u8]) -> Self {
+        Self {
+            read: NonNull::from(read),
+            write: NonNull::from(write),
+            buffers: PhantomData,
+        }
+    }
+
+    /// Lend the buffers to a new TLS connection
+    ///
+    /// # Safety
+    ///
+    /// The connection they were lent to before, if any, must have been
+    /// dropped.
+    const unsafe fn lend(&mut self) -> (&'s mut [u8], &'s mut [u8]) {
+        // SAFETY: the buffers were borrowed mutably for 's and are only
+        // reached through these pointers, and the caller guarantees that no
+        // earlier borrow is still in use
+        unsafe { (self.read.as_mut(), self.write.as_mut()) }
+    }
+}
+
+impl<
+    's,
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector,
+> HttpSession<'s, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
+    /// Turn a freshly connected transport into the session's connection,
+    /// performing the TLS handshake for `https`.
+    #[expect(clippy::future_not_send)]
+    #[cfg_attr(
+        not(feature = "tls"),
+        expect(clippy::unused_async, clippy::needless_pass_by_ref_mut)
+    )]
+    async fn secure(
+        &mut self,
+        transport: C::Transport<'s>,
+    ) -> Result<Connection<'s, C::Transport<'s>>, Error> {
+        #[cfg(feature = "tls")]
+        if let Some(tls_buffers) = &mut self.tls_buffers {
+            // SAFETY: the session's link holds no TLS connection while it is
+            // being replaced, so the buffers are not lent to any other
+            let (read, write) = unsafe { tls_buffers.lend() };
+            return self
+                .client
+                .open_tls(transport, self.host, read, write)
+                .await;
+        }
+        Ok(Connection::Plain(transport, PhantomData))
+    }
+
+    /// Check whether the current connection can still be used for requests
+    ///
+    /// This also returns `false` once the idle timeout has expired. A request
+    /// on a session that is no longer open reconnects first.
+    #[must_use]
+    pub fn is_open(&self) -> bool {
+        self.open && self.last_used.elapsed() < self.client.options.keep_alive_timeout
+    }
+
+    /// Make an HTTP request on the persistent connection
+    ///
+    /// `path` is the request target on the session's origin, such as
+    /// `/api/status?verbose=1`. The response is stored in `response_buffer`
+    /// exactly as with [`HttpClient::request`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ConnectionClosed`] if the connection is gone and
+    /// cannot be reopened, or if the server closed the connection before
+    /// responding to a request that is not sent again, and
+    /// [`Error::BufferOverflow`] if the response does not fit into
+    /// `response_buffer`. Any error closes the connection, and the next
+    /// request reconnects.
+    #[expect(clippy::future_not_send)]
+    pub async fn request<'b>(
+        &mut self,
+        method: HttpMethod,
+        path: &str,
+        headers: &[HttpHeader<'_>],
+        body: Option<&[u8]>,
+        response_buffer: &'b mut [u8],
+    ) -> Result<(HttpResponse<'b>, usize), Error> {
+        if !self.is_open() {
+            self.reconnect().await?;
+        }
+
+        let mut result = self
+            .exchange(method, path, headers, body, response_buffer)
+            .await;
+        // The server closed the idle connection before it saw the request
+        if self.reused && is_idempotent(method) && result.as_ref().is_err_and(is_stale_connection) {
+            self.reconnect().await?;
+            result = self
+                .exchange(method, path, headers, body, response_buffer)
+                .await;
+        }
+        let message = match result {
+            Ok((message, reusable)) => {
+                self.open = reusable;
+                message
+            }
+            Err(e) => {
+                self.open = false;
+                return Err(e);
+            }
+        };
+        self.reused = true;
+        self.last_used = Instant::now();
+        let total_read = message.decode_body(response_buffer);
+        let response =
+            Client::<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ>::parse_http_response_zero_copy(
+                &response_buffer[..total_read],
+            )?;
+        Ok((response, total_read))
+    }
+
+    /// Replace the connection with a new one to the session's origin,
+    /// with a new TLS handshake for `https`.
+    #[expect(clippy::future_not_send)]
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        debug!("Reconnecting session to {}", self.host);
+        self.open = false;
+        let mut transport = match core::mem::replace(&mut self.link, Link::Lost) {
+            Link::Open(connection) => connection.into_transport().await,
+            Link::Closed(transport) => transport,
+            Link::Lost => return Err(Error::ConnectionClosed),
+        };
+        if let Err(e) = transport.reconnect().await {
+            self.link = Link::Closed(transport);
+            return Err(e);
+        }
+        self.link = Link::Open(self.secure(transport).await?);
+        self.open = true;
+        self.reused = false;
+        self.last_used = Instant::now();
+        Ok(())
+    }
+
+    /// Send a request and read exactly one response into `response_buffer`.
+    ///
+    /// Also returns whether the connection can be used for another request.
+    #[expect(clippy::future_not_send)]
+    async fn exchange(
+        &mut self,
+        method: HttpMethod,
+        path: &str,
+        headers: &[HttpHeader<'_>],
+        body: Option<&[u8]>,
+        response_buffer: &mut [u8],
+    ) -> Result<(Message, bool), Error> {
+        let Link::Open(connection) = &mut self.link else {
+            return Err(Error::ConnectionClosed);
+        };
+        let http_request = Client::<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ>::build_http_request(
+            method,
+            self.scheme,
+            (self.host, self.port),
+            path,
+            headers,
+            body.map(|b| BodyFraming::Length(b.len())),
+            true,
+        )?;
+        connection.write_all(http_request.as_bytes()).await?;
+        if let Some(body_data) = body {
+            connection.write_all(body_data).await?;
+        }
+        connection.flush().await?;
+
+        let mut parser = Parser::response(method);
+        let mut total_read = 0;
+
+        loop {
+            if total_read == response_buffer.len() {
+                return Err(Error::BufferOverflow);
+            }
+
+            let n = connection.read(&mut response_buffer[total_read..]).await?;
+            if n == 0 {
+                // The server closed the connection
+                if total_read == 0 {
+                    return Err(Error::ConnectionClosed);
+                }
+                return Ok((parser.finish()?, false));
+            }
+            total_read += n;
+
+            if let Some(message) = parser.parse(&response_buffer[..total_read])? {
+                // Unsolicited bytes after the response leave the connection
+                // in an unknown state
+                let reusable = message.head.keep_alive && message.len == total_read;
+                return Ok((message, reusable));
+            }
+        }
+    }
+
+    /// Convenience method for making a GET request on the session
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`HttpSession::request`].
+    #[expect(clippy::future_not_send)]
+    pub async fn get<'b>(
+        &mut self,
+        path: &str,
+        headers: &[HttpHeader<'_>],
+        response_buffer: &'b mut [u8],
+    ) -> Result<(HttpResponse<'b>, usize), Error> {
+        self.request(HttpMethod::GET, path, headers, None, response_buffer)
+            .await
+    }
+
+    /// Convenience method for making a POST request on the session
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`HttpSession::request`].
+    #[expect(clippy::future_not_send)]
+    pub async fn post<'b>(
+        &mut self,
+        path: &str,
+        headers: &[HttpHeader<'_>],
+        body: &[u8],
+        response_buffer: &'b mut [u8],
+    ) -> Result<(HttpResponse<'b>, usize), Error> {
+        self.request(HttpMethod::POST, path, headers, Some(body), response_buffer)
+            .await
+    }
+
+    /// Convenience method for making a PUT request on the session
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`HttpSession::request`].
+    #[expect(clippy::future_not_send)]
+    pub async fn put<'b>(
+        &mut self,
+        path: &str,
+        headers: &[HttpHeader<'_>],
+        body: &[u8],
+        response_buffer: &'b mut [u8],
+    ) -> Result<(HttpResponse<'b>, usize), Error> {
+        self.request(HttpMethod::PUT, path, headers, Some(body), response_buffer)
+            .await
+    }
+
+    /// Convenience method for making a DELETE request on the session
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`HttpSession::request`].
+    #[expect(clippy::future_not_send)]
+    pub async fn delete<'b>(
+        &mut self,
+        path: &str,
+        headers: &[HttpHeader<'_>],
+        response_buffer: &'b mut [u8],
+    ) -> Result<(HttpResponse<'b>, usize), Error> {
+        self.request(HttpMethod::DELETE, path, headers, None, response_buffer)
+            .await
+    }
+
+    /// Close the session, shutting down TLS (if used) and the TCP connection
+    #[expect(clippy::future_not_send)]
+    pub async fn close(self) {
+        match self.link {
+            Link::Open(connection) => connection.close().await,
+            Link::Closed(transport) => transport.close().await,
+            Link::Lost => {}
+        }
+    }
+}
+
+/// Whether `error` shows that the server had closed the connection before a
+/// request was sent on it
+const fn is_stale_connection(error: &Error) -> bool {
+    match error {
+        Error::ConnectionClosed | Error::TcpError(tcp::Error::ConnectionReset) => true,
+        // A close notification, or the connection ending without one
+        #[cfg(feature = "tls")]
+        Error::TlsError(TlsError::ConnectionClosed | TlsError::IoError) => true,
+        _ => false,
+    }
+}
+
+/// Whether sending a request with `method` twice has the same effect as once
+const fn is_idempotent(method: HttpMethod) -> bool {
+    !matches!(
+        method,
+        HttpMethod::POST | HttpMethod::PATCH | HttpMethod::CONNECT
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HttpClientOptions, ResponseBody,
+        testing::{MockPeer, Step},
+    };
+    use embassy_time::{Duration, Timer};
+    use futures_lite::future::block_on;
+
+    type Client<'p> = HttpClient<'p, 64, 64, 64, 64, 512, &'p MockPeer<'p>>;
+
+    const FIRST: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nfirst";
+    const SECOND: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 6\r\n\r\nsecond";
+
+    fn options() -> HttpClientOptions {
+        HttpClientOptions {
+            socket_timeout: Duration::from_millis(50),
+            socket_close_delay: Duration::from_ticks(0),
+            ..HttpClientOptions::default()
+        }
+    }
+
+    /// Send `count` GET requests on one session, returning the response bodies
+    fn get_bodies(client: &Client<'_>, count: usize) -> std::vec::Vec<std::string::String> {
+        block_on(async {
+            let mut buffers = SessionBuffers::new();
+            let mut session = client
+                .session("http://device.local", &mut buffers)
+                .await
+                .unwrap();
+            let mut bodies = std::vec::Vec::new();
+            for _ in 0..count {
+                let mut buffer = [0u8; 128];
+                let (response, _) = session.get("/status", &[], &mut buffer).await.unwrap();
+                let ResponseBody::Text(body) = response.body else {
+                    panic!("Expected a text body");
+                };
+                bodies.push(body.into());
+            }
+            bodies
+        })
+    }
+
+    #[test]
+    fn test_session_rejects_origin_with_path() {
+        let peer = MockPeer::new(&[]);
+        let client = Client::with_connector(&peer, options());
+        let mut buffers = SessionBuffers::new();
+
+        let result = block_on(client.session("http://example.com/api", &mut buffers));
+        assert!(matches!(result, Err(Error::InvalidUrl)));
+        assert_eq!(peer.connections(), 0);
+    }
+
+    #[test]
+    fn test_session_host_header_names_port() {
+        let script = [Step::Send(FIRST)];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+        block_on(async {
+            let mut buffers = SessionBuffers::new();
+            let mut session = client
+                .session("http://device.local:8080", &mut buffers)
+                .await
+                .unwrap();
+            let mut buffer = [0u8; 128];
+            session.get("/status", &[], &mut buffer).await.unwrap();
+        });
+        assert!(
+            peer.written()
+                .starts_with(b"GET /status HTTP/1.1\r\nHost: device.local:8080\r\n")
+        );
+    }
+
+    #[test]
+    fn test_session_reuses_connection() {
+        let script = [Step::Send(FIRST), Step::Send(SECOND)];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+
+        assert_eq!(get_bodies(&client, 2), ["first", "second"]);
+        assert_eq!(peer.connections(), 1);
+        let written = core::str::from_utf8(&peer.written()).unwrap().to_owned();
+        assert_eq!(written.matches("GET /status HTTP/1.1\r\n").count(), 2);
+        assert_eq!(written.matches("Connection: keep-alive\r\n").count(), 2);
+    }
+
+    #[test]
+    fn test_session_reconnects_after_connection_close() {
+        let script = [
+            Step::Send(b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 5\r\n\r\nfirst"),
+            Step::Send(SECOND),
+        ];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+
+        // The new connection replays the script from the start
+        assert_eq!(get_bodies(&client, 2), ["first", "first"]);
+        assert_eq!(peer.connections(), 2);
+    }
+
+    #[test]
+    fn test_session_reconnects_after_idle_timeout() {
+        let script = [Step::Send(FIRST), Step::Send(SECOND)];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(
+            &peer,
+            HttpClientOptions {
+                keep_alive_timeout: Duration::from_millis(10),
+                ..options()
+            },
+        );
+
+        block_on(async {
+            let mut buffers = SessionBuffers::new();
+            let mut session = client
+                .session("http://device.local", &mut buffers)
+                .await
+                .unwrap();
+            let mut buffer = [0u8; 128];
+            session.get("/status", &[], &mut buffer).await.unwrap();
+            assert!(session.is_open());
+
+            Timer::after(Duration::from_millis(20)).await;
+            assert!(!session.is_open());
+            let (response, _) = session.get("/status", &[], &mut buffer).await.unwrap();
+            assert_eq!(response.body, ResponseBody::Text("first"));
+            assert!(session.is_open());
+        });
+        assert_eq!(peer.connections(), 2);
+    }
+
+    #[test]
+    fn test_session_resends_after_stale_connection() {
+        // The peer closes the connection after its only response
+        let script = [Step::Send(FIRST)];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+
+        assert_eq!(get_bodies(&client, 2), ["first", "first"]);
+        assert_eq!(peer.connections(), 2);
+
+        // A POST is not sent twice
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+        block_on(async {
+            let mut buffers = SessionBuffers::new();
+            let mut session = client
+                .session("http://device.local", &mut buffers)
+                .await
+                .unwrap();
+            let mut buffer = [0u8; 128];
+            session
+                .post("/status", &[], b"1", &mut buffer)
+                .await
+                .unwrap();
+            let result = session.post("/status", &[], b"2", &mut buffer).await;
+            assert!(matches!(result, Err(Error::ConnectionClosed)));
+        });
+        assert_eq!(peer.connections(), 1);
+    }
+}
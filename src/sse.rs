@@ -0,0 +1,948 @@
+use crate::{
+    client::HttpClient,
+    error::Error,
+    header::{
+        HttpHeader,
+        headers::{ACCEPT, CACHE_CONTROL, CONTENT_TYPE, LAST_EVENT_ID},
+        mime_types::EVENT_STREAM,
+    },
+    method::HttpMethod,
+    protocol::MAX_HEADERS,
+    session::SessionBuffers,
+    status_code::StatusCode,
+    transport::Connector,
+    writer::{BodyWriter, ResponseWriter},
+};
+use core::{future::Future, iter, ops::ControlFlow, pin::pin};
+use embassy_futures::select::{Either3, select3};
+use embassy_time::{Duration, Timer};
+use embedded_io_async::{Read, Write};
+use heapless::{String, Vec};
+
+/// Default interval between keep-alive comments on an idle event stream
+pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Longest event type or event ID accepted by [`EventReader`]
+pub const MAX_EVENT_FIELD_LEN: usize = 64;
+
+/// Default delay before [`HttpClient::subscribe`] reconnects, unless the
+/// server sets one with a `retry` field
+pub const DEFAULT_RECONNECTION_TIME: Duration = Duration::from_secs(3);
+
+/// A server-sent event
+///
+/// Multi-line `data` is sent as one `data:` field per line and arrives at
+/// the client joined with `\n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Event<'a> {
+    /// Event type, dispatched as `message` by clients when not set
+    pub event: Option<&'a str>,
+    /// Event payload
+    pub data: &'a str,
+    /// Event ID, sent back by reconnecting clients in `Last-Event-ID`
+    pub id: Option<&'a str>,
+    /// Reconnection delay in milliseconds for the client
+    pub retry: Option<u32>,
+}
+
+impl<'a> Event<'a> {
+    /// Create an event with the given data
+    #[must_use]
+    pub const fn new(data: &'a str) -> Self {
+        Self {
+            event: None,
+            data,
+            id: None,
+            retry: None,
+        }
+    }
+
+    /// Set the event type
+    #[must_use]
+    pub const fn with_event(mut self, event: &'a str) -> Self {
+        self.event = Some(event);
+        self
+    }
+
+    /// Set the event ID
+    #[must_use]
+    pub const fn with_id(mut self, id: &'a str) -> Self {
+        self.id = Some(id);
+        self
+    }
+
+    /// Set the client's reconnection delay in milliseconds
+    #[must_use]
+    pub const fn with_retry(mut self, retry: u32) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+}
+
+impl<W: Read + Write<Error = Error>> ResponseWriter<'_, W> {
+    /// Start a `text/event-stream` response and return a sink for its events
+    ///
+    /// `Content-Type: text/event-stream` and `Cache-Control: no-cache` are
+    /// added to `headers` unless they set their own `Cache-Control`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferOverflow` if there are too many headers,
+    /// `Error::InvalidResponse` if the response was already started, or the
+    /// error from writing to the connection.
+    pub async fn start_events(
+        &mut self,
+        headers: &[HttpHeader<'_>],
+    ) -> Result<EventSink<'_, W>, Error> {
+        let mut all_headers = Vec::<HttpHeader<'_>, MAX_HEADERS>::new();
+        all_headers
+            .push(HttpHeader::event_stream())
+            .map_err(|_| Error::BufferOverflow)?;
+        if !headers
+            .iter()
+            .any(|header| header.name.eq_ignore_ascii_case(CACHE_CONTROL))
+        {
+            all_headers
+                .push(HttpHeader::cache_control("no-cache"))
+                .map_err(|_| Error::BufferOverflow)?;
+        }
+        for header in headers
+            .iter()
+            .filter(|header| !header.name.eq_ignore_ascii_case(CONTENT_TYPE))
+        {
+            all_headers
+                .push(header.clone())
+                .map_err(|_| Error::BufferOverflow)?;
+        }
+
+        let body = self.start(StatusCode::Ok, &all_headers, None).await?;
+        Ok(EventSink {
+            body,
+            keep_alive_interval: DEFAULT_KEEP_ALIVE_INTERVAL,
+        })
+    }
+}
+
+/// Sends server-sent events over an open response
+///
+/// Returned by [`ResponseWriter::start_events`]. The connection stays open
+/// for as long as the handler keeps the sink. Wait for the next thing to
+/// report with [`EventSink::wait`], which sends keep-alive comments while
+/// the stream is idle and notices when the client goes away.
+///
+/// # Examples
+///
+/// ```no_run
+/// use core::fmt::Write as _;
+/// use embedded_io_async::{Read, Write};
+/// use nanofish::{Error, Event, ResponseWriter};
+/// # struct Sensor;
+/// # impl Sensor {
+/// #     async fn next_reading(&mut self) -> f32 {
+/// #         todo!()
+/// #     }
+/// # }
+///
+/// async fn stream_readings<W: Read + Write<Error = Error>>(
+///     response: &mut ResponseWriter<'_, W>,
+///     sensor: &mut Sensor,
+/// ) -> Result<(), Error> {
+///     let mut events = response.start_events(&[]).await?;
+///     loop {
+///         // Fails with `Error::ConnectionClosed` once the client disconnects
+///         let reading = events.wait(sensor.next_reading()).await?;
+///         let mut data = heapless::String::<32>::new();
+///         write!(data, "{reading}").ok();
+///         events.send(&Event::new(&data).with_event("reading")).await?;
+///     }
+/// }
+/// ```
+pub struct EventSink<'w, W> {
+    body: BodyWriter<'w, W>,
+    keep_alive_interval: Duration,
+}
+
+impl<W: Read + Write<Error = Error>> EventSink<'_, W> {
+    /// Set the interval between keep-alive comments (default 15 seconds)
+    #[must_use]
+    pub const fn with_keep_alive_interval(mut self, interval: Duration) -> Self {
+        self.keep_alive_interval = interval;
+        self
+    }
+
+    /// Send an event
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidResponse` if the event type or ID contains a
+    /// line break, or the error from writing to the connection.
+    pub async fn send(&mut self, event: &Event<'_>) -> Result<(), Error> {
+        check_single_line(event.event)?;
+        check_single_line(event.id)?;
+
+        let mut retry = String::<10>::new();
+        if let Some(ms) = event.retry {
+            core::fmt::write(&mut retry, format_args!("{ms}"))
+                .map_err(|_| Error::BufferOverflow)?;
+        }
+        let retry = event.retry.map(|_| retry.as_str());
+
+        let parts = field(b"event: ", event.event)
+            .chain(field(b"id: ", event.id))
+            .chain(field(b"retry: ", retry))
+            .chain(
+                data_lines(event.data)
+                    .flat_map(|line| [b"data: ".as_slice(), line.as_bytes(), b"\n".as_slice()]),
+            )
+            .chain(iter::once(b"\n".as_slice()));
+        self.body.write_parts(parts).await?;
+        self.body.flush().await
+    }
+
+    /// Send a comment, which clients ignore
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidResponse` if `text` contains a line break, or
+    /// the error from writing to the connection.
+    pub async fn comment(&mut self, text: &str) -> Result<(), Error> {
+        check_single_line(Some(text))?;
+        let separator = if text.is_empty() { "" } else { " " };
+        let parts = [
+            b":".as_slice(),
+            separator.as_bytes(),
+            text.as_bytes(),
+            b"\n".as_slice(),
+        ];
+        self.body.write_parts(parts.into_iter()).await?;
+        self.body.flush().await
+    }
+
+    /// Wait for `future` while keeping the stream alive
+    ///
+    /// Sends a keep-alive comment whenever the stream has been idle for the
+    /// keep-alive interval. Anything the client sends in the meantime is
+    /// discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ConnectionClosed` if the client closes the connection
+    /// first, or the error from reading or writing the connection.
+    pub async fn wait<F: Future>(&mut self, future: F) -> Result<F::Output, Error> {
+        let mut future = pin!(future);
+        loop {
+            let idle = Timer::after(self.keep_alive_interval);
+            match select3(&mut future, idle, closed(self.body.connection())).await {
+                Either3::First(output) => return Ok(output),
+                Either3::Second(()) => self.comment("keep-alive").await?,
+                Either3::Third(e) => return Err(e),
+            }
+        }
+    }
+
+    /// End the event stream
+    ///
+    /// # Errors
+    ///
+    /// Returns the error from writing to the connection.
+    pub async fn finish(self) -> Result<(), Error> {
+        self.body.finish().await
+    }
+}
+
+/// Resolve once the client closes `connection`, discarding what it sends.
+async fn closed<R: Read<Error = Error>>(connection: &mut R) -> Error {
+    let mut discard = [0u8; 16];
+    loop {
+        match connection.read(&mut discard).await {
+            Ok(0) => return Error::ConnectionClosed,
+            Ok(_) => {}
+            Err(e) => return e,
+        }
+    }
+}
+
+/// The parts of a `name: value` line, or nothing if `value` is not set.
+fn field<'p>(
+    name: &'static [u8],
+    value: Option<&'p str>,
+) -> impl Iterator<Item = &'p [u8]> + Clone {
+    value
+        .map(|value| [name, value.as_bytes(), b"\n".as_slice()])
+        .into_iter()
+        .flatten()
+}
+
+/// Split event data at `\r\n`, `\r` and `\n`, the line breaks of the event
+/// stream format.
+fn data_lines(data: &str) -> impl Iterator<Item = &str> + Clone {
+    let mut rest = Some(data);
+    iter::from_fn(move || {
+        let current = rest?;
+        if let Some(pos) = current.find(['\r', '\n']) {
+            let skip = if current[pos..].starts_with("\r\n") {
+                2
+            } else {
+                1
+            };
+            rest = Some(&current[pos + skip..]);
+            Some(&current[..pos])
+        } else {
+            rest = None;
+            Some(current)
+        }
+    })
+}
+
+fn check_single_line(value: Option<&str>) -> Result<(), Error> {
+    if value.is_some_and(|value| value.contains(['\r', '\n'])) {
+        return Err(Error::InvalidResponse("Event field contains a line break"));
+    }
+    Ok(())
+}
+
+/// Byte order mark that may precede an event stream
+const BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// Parses server-sent events incrementally from a byte stream
+///
+/// Lines are read into a caller-provided buffer, which also holds the data of
+/// the event being assembled. The buffer must be large enough for the data of
+/// the largest event plus one line. Event types and IDs are limited to
+/// [`MAX_EVENT_FIELD_LEN`] bytes.
+///
+/// # Examples
+///
+/// ```no_run
+/// use embassy_net::Stack;
+/// use nanofish::{DefaultHttpClient, EventReader, HttpHeader, HttpMethod, SessionBuffers};
+/// # fn handle_command(_event: Option<&str>, _data: &str) {}
+///
+/// async fn example(stack: &Stack<'_>) -> Result<(), nanofish::Error> {
+///     let client = DefaultHttpClient::new(stack);
+///     let mut buffers = SessionBuffers::new();
+///     let mut header_buffer = [0u8; 1024];
+///     let headers = [HttpHeader::new("Accept", "text/event-stream")];
+///     let response = client
+///         .request_streaming(
+///             HttpMethod::GET,
+///             "http://example.com/events",
+///             &headers,
+///             None,
+///             &mut buffers,
+///             &mut header_buffer,
+///         )
+///         .await?;
+///     let mut buffer = [0u8; 512];
+///     let mut events = EventReader::new(response.body, &mut buffer);
+///     while let Some(event) = events.next_event().await? {
+///         handle_command(event.event, event.data);
+///     }
+///     Ok(())
+/// }
+/// ```
+pub struct EventReader<'b, R> {
+    source: R,
+    buffer: &'b mut [u8],
+    /// Data of the current event, at the start of `buffer`
+    data_len: usize,
+    /// Unparsed bytes are `buffer[start..end]`
+    start: usize,
+    end: usize,
+    event_type: String<MAX_EVENT_FIELD_LEN>,
+    pub(crate) last_event_id: String<MAX_EVENT_FIELD_LEN>,
+    /// `retry` field of the current event
+    event_retry: Option<u32>,
+    /// Latest `retry` field of the stream
+    retry: Option<u32>,
+    /// The last line ended with CR, so an LF right after it belongs to it
+    skip_lf: bool,
+    at_start: bool,
+    dispatched: bool,
+}
+
+impl<'b, R: Read<Error = Error>> EventReader<'b, R> {
+    /// Create a reader parsing events from `source`, using `buffer` for lines
+    /// and event data
+    pub const fn new(source: R, buffer: &'b mut [u8]) -> Self {
+        Self {
+            source,
+            buffer,
+            data_len: 0,
+            start: 0,
+            end: 0,
+            event_type: String::new(),
+            last_event_id: String::new(),
+            event_retry: None,
+            retry: None,
+            skip_lf: false,
+            at_start: true,
+            dispatched: false,
+        }
+    }
+
+    /// Read the next event, or `None` once the stream has ended
+    ///
+    /// An event cut off by the end of the stream is discarded. The `id` of
+    /// the returned event is the last ID the stream has set, which may come
+    /// from an earlier event.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferOverflow` if an event or line does not fit into
+    /// the buffer or an event type or ID is longer than
+    /// [`MAX_EVENT_FIELD_LEN`], `Error::InvalidResponse` for text that is not
+    /// valid UTF-8, or the error from reading the source.
+    pub async fn next_event(&mut self) -> Result<Option<Event<'_>>, Error> {
+        if self.dispatched {
+            self.dispatched = false;
+            self.reset_event();
+        }
+
+        loop {
+            let Some(line_end) = self.find_line() else {
+                if self.fill().await? {
+                    continue;
+                }
+                return Ok(None);
+            };
+            let mut line_start = self.start;
+            self.start = line_end + 1;
+            if self.at_start {
+                self.at_start = false;
+                if self.buffer[line_start..line_end].starts_with(BOM) {
+                    line_start += BOM.len();
+                }
+            }
+
+            if line_start < line_end {
+                self.parse_line(line_start, line_end)?;
+            } else if self.data_len == 0 {
+                // An event without data is not dispatched
+                self.reset_event();
+            } else {
+                self.dispatched = true;
+                return self.event().map(Some);
+            }
+        }
+    }
+
+    /// The ID of the last event that set one
+    #[must_use]
+    pub fn last_event_id(&self) -> Option<&str> {
+        Some(self.last_event_id.as_str()).filter(|id| !id.is_empty())
+    }
+
+    /// The reconnection delay in milliseconds last requested by the server
+    #[must_use]
+    pub const fn retry(&self) -> Option<u32> {
+        self.retry
+    }
+
+    /// Give back the source
+    pub fn into_inner(self) -> R {
+        self.source
+    }
+
+    fn reset_event(&mut self) {
+        self.data_len = 0;
+        self.event_type.clear();
+        self.event_retry = None;
+    }
+
+    /// Find the end of the next complete line in the unparsed bytes.
+    fn find_line(&mut self) -> Option<usize> {
+        if self.skip_lf && self.start < self.end {
+            self.skip_lf = false;
+            if self.buffer[self.start] == b'\n' {
+                self.start += 1;
+            }
+        }
+        let pos = self.buffer[self.start..self.end]
+            .iter()
+            .position(|&b| b == b'\r' || b == b'\n')?;
+        let line_end = self.start + pos;
+        self.skip_lf = self.buffer[line_end] == b'\r';
+        Some(line_end)
+    }
+
+    /// Drop parsed lines and read more of the stream, returning `false` at
+    /// its end.
+    async fn fill(&mut self) -> Result<bool, Error> {
+        self.buffer.copy_within(self.start..self.end, self.data_len);
+        self.end -= self.start - self.data_len;
+        self.start = self.data_len;
+        if self.end == self.buffer.len() {
+            return Err(Error::BufferOverflow);
+        }
+        let n = self.source.read(&mut self.buffer[self.end..]).await?;
+        self.end += n;
+        Ok(n > 0)
+    }
+
+    /// Apply one non-empty line to the current event.
+    fn parse_line(&mut self, start: usize, end: usize) -> Result<(), Error> {
+        let line = &self.buffer[start..end];
+        let (name, value_start) = match line.iter().position(|&b| b == b':') {
+            // Comment
+            Some(0) => return Ok(()),
+            Some(colon) if line.get(colon + 1) == Some(&b' ') => {
+                (&line[..colon], start + colon + 2)
+            }
+            Some(colon) => (&line[..colon], start + colon + 1),
+            None => (line, end),
+        };
+
+        match name {
+            b"event" => {
+                let value = text(&self.buffer[value_start..end])?;
+                self.event_type.clear();
+                self.event_type
+                    .push_str(value)
+                    .map_err(|_| Error::BufferOverflow)?;
+            }
+            b"data" => {
+                // The line sits behind the data, so the value always fits
+                self.buffer.copy_within(value_start..end, self.data_len);
+                self.data_len += end - value_start;
+                self.buffer[self.data_len] = b'\n';
+                self.data_len += 1;
+            }
+            b"id" if !self.buffer[value_start..end].contains(&0) => {
+                let value = text(&self.buffer[value_start..end])?;
+                self.last_event_id.clear();
+                self.last_event_id
+                    .push_str(value)
+                    .map_err(|_| Error::BufferOverflow)?;
+            }
+            b"retry" => {
+                let value = &self.buffer[value_start..end];
+                if value.iter().all(u8::is_ascii_digit)
+                    && let Ok(retry) = text(value)?.parse()
+                {
+                    self.event_retry = Some(retry);
+                    self.retry = Some(retry);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn event(&self) -> Result<Event<'_>, Error> {
+        // Without the LF that ended the last data line
+        let data = text(&self.buffer[..self.data_len - 1])?;
+        Ok(Event {
+            event: Some(self.event_type.as_str()).filter(|event| !event.is_empty()),
+            data,
+            id: self.last_event_id(),
+            retry: self.event_retry,
+        })
+    }
+}
+
+fn text(bytes: &[u8]) -> Result<&str, Error> {
+    core::str::from_utf8(bytes).map_err(|_| Error::InvalidResponse("Event stream is not UTF-8"))
+}
+
+impl<
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector,
+> HttpClient<'_, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
+    /// Subscribe to a server-sent event stream
+    ///
+    /// Sends a `GET` request to `url` and calls `on_event` for every event
+    /// as it arrives, until it returns [`ControlFlow::Break`]. The response
+    /// head is read into `header_buffer` and events are assembled in
+    /// `event_buffer`, as with [`EventReader`].
+    ///
+    /// When the connection fails or the stream ends, the client waits for
+    /// the reconnection delay ([`DEFAULT_RECONNECTION_TIME`] or the latest
+    /// `retry` field) and reconnects, sending the last event ID in
+    /// `Last-Event-ID`. A stream that stays silent for longer than
+    /// [`HttpClientOptions::socket_timeout`](crate::HttpClientOptions::socket_timeout)
+    /// is treated as a failed connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidStatusCode` if the server answers with a status
+    /// other than `200 OK` (a `204 No Content` tells clients to stop),
+    /// `Error::InvalidResponse` if the response is not a
+    /// `text/event-stream`, and `Error::BufferOverflow` if the headers or an
+    /// event do not fit into their buffers. Errors that are not caused by the
+    /// network, such as a malformed URL, are returned as well.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use core::ops::ControlFlow;
+    /// use embassy_net::Stack;
+    /// use nanofish::{DefaultHttpClient, HttpHeader, SessionBuffers};
+    /// # async fn run_command(_data: &str) {}
+    ///
+    /// async fn example(stack: &Stack<'_>) -> Result<(), nanofish::Error> {
+    ///     let client = DefaultHttpClient::new(stack);
+    ///     let mut buffers = SessionBuffers::new();
+    ///     let mut header_buffer = [0u8; 1024];
+    ///     let mut event_buffer = [0u8; 512];
+    ///     client
+    ///         .subscribe(
+    ///             "https://example.com/devices/42/commands",
+    ///             &[HttpHeader::authorization("Bearer token")],
+    ///             &mut buffers,
+    ///             &mut header_buffer,
+    ///             &mut event_buffer,
+    ///             async |event| match event.event {
+    ///                 Some("shutdown") => ControlFlow::Break(()),
+    ///                 _ => {
+    ///                     run_command(event.data).await;
+    ///                     ControlFlow::Continue(())
+    ///                 }
+    ///             },
+    ///         )
+    ///         .await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[expect(clippy::future_not_send)]
+    pub async fn subscribe<B, F>(
+        &self,
+        url: &str,
+        headers: &[HttpHeader<'_>],
+        buffers: &mut SessionBuffers<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>,
+        header_buffer: &mut [u8],
+        event_buffer: &mut [u8],
+        mut on_event: F,
+    ) -> Result<B, Error>
+    where
+        F: AsyncFnMut(Event<'_>) -> ControlFlow<B>,
+    {
+        let mut last_event_id = String::<MAX_EVENT_FIELD_LEN>::new();
+        let mut reconnection_time = DEFAULT_RECONNECTION_TIME;
+
+        loop {
+            let result = async {
+                let response = {
+                    let mut request_headers = Vec::<HttpHeader<'_>, MAX_HEADERS>::new();
+                    request_headers
+                        .extend_from_slice(headers)
+                        .map_err(|_| Error::BufferOverflow)?;
+                    request_headers
+                        .push(HttpHeader::new(ACCEPT, EVENT_STREAM))
+                        .map_err(|_| Error::BufferOverflow)?;
+                    request_headers
+                        .push(HttpHeader::cache_control("no-cache"))
+                        .map_err(|_| Error::BufferOverflow)?;
+                    if !last_event_id.is_empty() {
+                        request_headers
+                            .push(HttpHeader::new(LAST_EVENT_ID, &last_event_id))
+                            .map_err(|_| Error::BufferOverflow)?;
+                    }
+
+                    self.request_streaming(
+                        HttpMethod::GET,
+                        url,
+                        &request_headers,
+                        None,
+                        &mut *buffers,
+                        &mut *header_buffer,
+                    )
+                    .await?
+                };
+                if response.status_code != StatusCode::Ok {
+                    response.body.close().await;
+                    return Err(Error::InvalidStatusCode);
+                }
+                if !is_event_stream(response.content_type()) {
+                    response.body.close().await;
+                    return Err(Error::InvalidResponse("Response is not an event stream"));
+                }
+
+                let mut reader = EventReader::new(response.body, &mut *event_buffer);
+                reader.last_event_id.clone_from(&last_event_id);
+                let flow = loop {
+                    let event = match reader.next_event().await {
+                        Ok(Some(event)) => event,
+                        Ok(None) => break Ok(ControlFlow::Continue(())),
+                        Err(Error::BufferOverflow) => break Err(Error::BufferOverflow),
+                        // Anything else read from an open stream is worth a
+                        // fresh connection
+                        Err(e) => {
+                            warn!("Event stream interrupted: {:?}", e);
+                            break Ok(ControlFlow::Continue(()));
+                        }
+                    };
+                    if let ControlFlow::Break(value) = on_event(event).await {
+                        break Ok(ControlFlow::Break(value));
+                    }
+                };
+
+                last_event_id.clone_from(&reader.last_event_id);
+                if let Some(retry) = reader.retry() {
+                    reconnection_time = Duration::from_millis(retry.into());
+                }
+                reader.into_inner().close().await;
+                flow
+            }
+            .await;
+
+            match result {
+                Ok(ControlFlow::Break(value)) => return Ok(value),
+                Ok(ControlFlow::Continue(())) => debug!("Event stream ended, reconnecting"),
+                Err(e) if is_transient(&e) => warn!("Event stream failed: {:?}, reconnecting", e),
+                Err(e) => return Err(e),
+            }
+            Timer::after(reconnection_time).await;
+        }
+    }
+}
+
+/// Check whether a `Content-Type` value is `text/event-stream`.
+fn is_event_stream(content_type: Option<&str>) -> bool {
+    content_type.is_some_and(|value| {
+        value
+            .split(';')
+            .next()
+            .is_some_and(|media_type| media_type.trim().eq_ignore_ascii_case(EVENT_STREAM))
+    })
+}
+
+/// Errors after which reconnecting may help.
+const fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::DnsError(_)
+        | Error::IpAddressEmpty
+        | Error::ConnectionError(_)
+        | Error::TcpError(_)
+        | Error::NoResponse
+        | Error::ConnectionClosed => true,
+        #[cfg(feature = "tls")]
+        Error::TlsError(_) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HttpRequest, SimpleHandler, SmallHttpServer, SmallServerBuffers, StreamingHandler,
+        testing::{MockPeer, MockTransport, Step},
+        writer::ResponseState,
+    };
+    use embassy_futures::select::select;
+    use futures_lite::future::block_on;
+
+    /// Run `f` on a fresh event stream and return everything written
+    fn stream<F>(f: F) -> std::string::String
+    where
+        F: AsyncFnOnce(&mut EventSink<'_, MockTransport<'_, '_, 4096>>) -> Result<(), Error>,
+    {
+        // A client that never sends anything
+        let script = [Step::Delay(Duration::from_secs(3600))];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut client = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut client, &state, HttpMethod::GET, true, true);
+        block_on(async {
+            let mut events = response.start_events(&[]).await?;
+            f(&mut events).await?;
+            events.finish().await
+        })
+        .unwrap();
+        std::string::String::from_utf8(peer.written().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_event_fields() {
+        let event = Event::new("a\nb\r\nc\rd")
+            .with_event("update")
+            .with_id("42")
+            .with_retry(3000);
+        let written = stream(async |events| events.send(&event).await);
+        assert!(written.starts_with(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+             Transfer-Encoding: chunked\r\nConnection: keep-alive\r\n\r\n"
+        ));
+        let payload = "event: update\nid: 42\nretry: 3000\ndata: a\ndata: b\ndata: c\ndata: d\n\n";
+        assert!(written.ends_with(&format!("{:x}\r\n{payload}\r\n0\r\n\r\n", payload.len())));
+    }
+
+    #[test]
+    fn test_event_data_lines() {
+        assert_eq!(data_lines("").collect::<std::vec::Vec<_>>(), [""]);
+        assert_eq!(data_lines("a\n").collect::<std::vec::Vec<_>>(), ["a", ""]);
+        assert_eq!(
+            data_lines("a\r\n\nb").collect::<std::vec::Vec<_>>(),
+            ["a", "", "b"]
+        );
+    }
+
+    #[test]
+    fn test_event_rejects_line_breaks() {
+        let written = stream(async |events| {
+            let result = events.send(&Event::new("x").with_id("1\n2")).await;
+            assert!(matches!(result, Err(Error::InvalidResponse(_))));
+            assert!(events.comment("a\rb").await.is_err());
+            events.comment("").await
+        });
+        assert!(written.ends_with("\r\n\r\n2\r\n:\n\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_wait_sends_keep_alive() {
+        let written = stream(async |events| {
+            events.keep_alive_interval = Duration::from_millis(10);
+            let value = events.wait(Timer::after_millis(35)).await;
+            assert!(value.is_ok());
+            Ok(())
+        });
+        assert!(written.contains("\r\n: keep-alive\n\r\n"));
+    }
+
+    #[test]
+    fn test_wait_detects_close() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut connection = peer.connection();
+        let state = ResponseState::new();
+        let mut response =
+            ResponseWriter::new(&mut connection, &state, HttpMethod::GET, true, true);
+        let result = block_on(async {
+            let mut events = response.start_events(&[]).await?;
+            events.wait(core::future::pending::<()>()).await
+        });
+        assert!(matches!(result, Err(Error::ConnectionClosed)));
+    }
+
+    /// Parse `stream` and check its events against `expected`
+    fn assert_events(stream: &[u8], expected: &[Event<'_>]) {
+        let script = [Step::Send(stream)];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(3);
+        let mut buffer = [0u8; 64];
+        let mut reader = EventReader::new(peer.connection(), &mut buffer);
+        let mut count = 0;
+        block_on(async {
+            while let Some(event) = reader.next_event().await.unwrap() {
+                assert_eq!(Some(&event), expected.get(count));
+                count += 1;
+            }
+        });
+        assert_eq!(count, expected.len());
+    }
+
+    #[test]
+    fn test_reader_fields() {
+        assert_events(
+            b"\xEF\xBB\xBF: hello\n\
+              event: update\ndata: first\ndata:second\r\nid: 7\n\n\
+              data\rretry: 2500\r\r\n\
+              data: third\n\n\
+              id\ndata: fourth\n\n",
+            &[
+                Event::new("first\nsecond")
+                    .with_event("update")
+                    .with_id("7"),
+                Event::new("").with_id("7").with_retry(2500),
+                Event::new("third").with_id("7"),
+                Event::new("fourth"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_reader_ignores() {
+        assert_events(
+            b"event: empty\nretry: 1s\n\n\
+              data: a\nunknown: x\nretry: 12x\nid: bad\0id\n\n\
+              data: cut off",
+            &[Event::new("a")],
+        );
+    }
+
+    #[test]
+    fn test_reader_state() {
+        let script = [Step::Send(b"retry: 100\nid: 3\ndata: x\n\n")];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(3);
+        let mut buffer = [0u8; 32];
+        let mut reader = EventReader::new(peer.connection(), &mut buffer);
+        block_on(async {
+            assert!(reader.next_event().await.unwrap().is_some());
+            assert!(reader.next_event().await.unwrap().is_none());
+        });
+        assert_eq!(reader.last_event_id(), Some("3"));
+        assert_eq!(reader.retry(), Some(100));
+    }
+
+    #[test]
+    fn test_reader_overflow() {
+        let script = [Step::Send(b"data: 0123456789abcdef\n\n")];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(3);
+        let mut buffer = [0u8; 16];
+        let mut reader = EventReader::new(peer.connection(), &mut buffer);
+        let result = block_on(reader.next_event());
+        assert!(matches!(result, Err(Error::BufferOverflow)));
+
+        // Data lines add up until the event is dispatched
+        let script = [Step::Send(b"data: 01234\ndata: 56789\ndata: abcde\n\n")];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(3);
+        let mut buffer = [0u8; 16];
+        let mut reader = EventReader::new(peer.connection(), &mut buffer);
+        let result = block_on(reader.next_event());
+        assert!(matches!(result, Err(Error::BufferOverflow)));
+    }
+
+    #[test]
+    fn test_is_event_stream() {
+        assert!(is_event_stream(Some("text/event-stream")));
+        assert!(is_event_stream(Some("Text/Event-Stream; charset=utf-8")));
+        assert!(!is_event_stream(Some("text/plain")));
+        assert!(!is_event_stream(None));
+    }
+
+    /// Streams one event on `/events` and then waits forever
+    #[derive(Clone)]
+    struct Events;
+
+    impl StreamingHandler for Events {
+        async fn handle_streaming<W: Read + Write<Error = Error>>(
+            &mut self,
+            request: &HttpRequest<'_>,
+            response: &mut ResponseWriter<'_, W>,
+        ) -> Result<(), Error> {
+            if request.path != "/events" {
+                return SimpleHandler.handle_streaming(request, response).await;
+            }
+            let mut events = response.start_events(&[]).await?;
+            events.send(&Event::new("hello")).await?;
+            events.wait(core::future::pending::<()>()).await
+        }
+    }
+
+    #[test]
+    fn test_stream_does_not_block_other_connections() {
+        let streaming = [
+            Step::Send(b"GET /events HTTP/1.1\r\n\r\n"),
+            Step::Delay(Duration::from_secs(60)),
+        ];
+        let other = [Step::Send(
+            b"GET /health HTTP/1.1\r\nConnection: close\r\n\r\n",
+        )];
+        let peers: [MockPeer<'_, 1024>; 2] = [MockPeer::new(&streaming), MockPeer::new(&other)];
+        let mut server = SmallHttpServer::new(80);
+        let mut buffers = [SmallServerBuffers::new(), SmallServerBuffers::new()];
+
+        block_on(select(
+            Box::pin(server.serve_concurrent(&peers[..], Events, &mut buffers)),
+            peers[1].closed(),
+        ));
+
+        // Chunked, since the stream has no length
+        assert!(peers[0].written().ends_with(b"data: hello\n\n\r\n"));
+        assert!(peers[1].written().ends_with(b"\r\n\r\n{\"status\":\"ok\"}"));
+    }
+}
@@ -78,6 +78,8 @@ pub enum StatusCode {
     RequestedRangeNotSatisfiable = 416,
     /// 417 Expectation Failed: The server cannot meet the requirements of the Expect request-header field.
     ExpectationFailed = 417,
+    /// 431 Request Header Fields Too Large: The server is unwilling to process the request because its header fields are too large.
+    RequestHeaderFieldsTooLarge = 431,
 
     // 5xx Server Error
     /// 500 Internal Server Error: The server has encountered a situation it doesn't know how to handle.
@@ -135,6 +137,7 @@ impl StatusCode {
             Self::UnsupportedMediaType => 415,
             Self::RequestedRangeNotSatisfiable => 416,
             Self::ExpectationFailed => 417,
+            Self::RequestHeaderFieldsTooLarge => 431,
             Self::InternalServerError => 500,
             Self::NotImplemented => 501,
             Self::BadGateway => 502,
@@ -186,6 +189,7 @@ impl StatusCode {
             Self::UnsupportedMediaType => "Unsupported Media Type",
             Self::RequestedRangeNotSatisfiable => "Requested Range Not Satisfiable",
             Self::ExpectationFailed => "Expectation Failed",
+            Self::RequestHeaderFieldsTooLarge => "Request Header Fields Too Large",
             // 5xx
             Self::InternalServerError => "Internal Server Error",
             Self::NotImplemented => "Not Implemented",
@@ -217,6 +221,15 @@ impl StatusCode {
         let code = self.as_u16();
         (500..600).contains(&code)
     }
+
+    /// Check if a response with this status may carry a body
+    ///
+    /// 1xx, 204 No Content and 304 Not Modified responses never have one.
+    #[must_use]
+    pub(crate) fn allows_body(self) -> bool {
+        let code = self.as_u16();
+        !(100..200).contains(&code) && code != 204 && code != 304
+    }
 }
 
 impl From<u16> for StatusCode {
@@ -256,6 +269,7 @@ impl From<u16> for StatusCode {
             415 => Self::UnsupportedMediaType,
             416 => Self::RequestedRangeNotSatisfiable,
             417 => Self::ExpectationFailed,
+            431 => Self::RequestHeaderFieldsTooLarge,
             500 => Self::InternalServerError,
             501 => Self::NotImplemented,
             502 => Self::BadGateway,
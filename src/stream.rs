@@ -0,0 +1,732 @@
+use crate::{
+    client::HttpClient,
+    connection::Connection,
+    error::Error,
+    header::{
+        HttpHeader,
+        headers::{CONTENT_LENGTH, CONTENT_TYPE},
+    },
+    method::HttpMethod,
+    parser::{BodyFraming, BodyState, Head, Message, Parser},
+    protocol::{CHUNKED_END_MARKER, MAX_HEADERS},
+    response::HttpResponse,
+    session::SessionBuffers,
+    status_code::StatusCode,
+    transport::{Connector, TcpTransport, Transport},
+    url::Url,
+    writer::write_chunk,
+};
+use embassy_time::Timer;
+use embedded_io_async::{Error as _, ErrorKind, ErrorType, Read, Write};
+use heapless::Vec;
+
+/// An HTTP response whose body is read incrementally from the connection
+///
+/// The status line and headers are parsed up front; the body is available
+/// through [`BodyReader`], which implements [`embedded_io_async::Read`]. This
+/// allows downloading bodies far larger than the available memory, such as
+/// firmware images or log files.
+pub struct StreamingResponse<'s, T: Transport + 's = TcpTransport<'s>> {
+    /// The HTTP status code (e.g., 200 for OK, 404 for Not Found)
+    pub status_code: StatusCode,
+    /// A collection of response headers with both names and values
+    pub headers: Vec<HttpHeader<'s>, MAX_HEADERS>,
+    /// Reader for the response body
+    pub body: BodyReader<'s, T>,
+}
+
+impl<T: Transport> StreamingResponse<'_, T> {
+    /// Get a header value by name (case-insensitive)
+    #[must_use]
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| h.value)
+    }
+
+    /// Get the Content-Type header value
+    #[must_use]
+    pub fn content_type(&self) -> Option<&str> {
+        self.get_header(CONTENT_TYPE)
+    }
+
+    /// Get the Content-Length header value as a number
+    #[must_use]
+    pub fn content_length(&self) -> Option<usize> {
+        self.get_header(CONTENT_LENGTH)?.parse().ok()
+    }
+
+    /// Check if the response indicates success (2xx status codes)
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.status_code.is_success()
+    }
+}
+
+/// Incremental reader for a response body
+///
+/// Reads return the decoded body bytes: `Content-Length` bodies stop after
+/// the announced length, chunked bodies are de-chunked on the fly and other
+/// bodies are read until the server closes the connection. A read of `0`
+/// bytes marks the end of the body.
+pub struct BodyReader<'s, T: Transport + 's = TcpTransport<'s>> {
+    connection: Connection<'s, T>,
+    decoder: BodyDecoder<'s>,
+}
+
+impl<T: Transport> BodyReader<'_, T> {
+    /// Check whether the whole body has been read
+    #[must_use]
+    pub const fn is_complete(&self) -> bool {
+        matches!(self.decoder.state, BodyState::Done)
+    }
+
+    /// Close the underlying connection
+    ///
+    /// Dropping the reader also releases the connection, but without shutting
+    /// down TLS or the TCP connection gracefully.
+    pub async fn close(self) {
+        self.connection.close().await;
+    }
+}
+
+impl<T: Transport> ErrorType for BodyReader<'_, T> {
+    type Error = Error;
+}
+
+#[cfg_attr(feature = "tls", expect(clippy::future_not_send))]
+impl<T: Transport> Read for BodyReader<'_, T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.decoder.read(&mut self.connection, buf).await
+    }
+}
+
+/// Decodes a response body from a byte source
+///
+/// Framing bytes are parsed from a small read-ahead buffer, while body data
+/// is copied straight from the source into the caller's buffer whenever no
+/// read-ahead bytes are pending.
+struct BodyDecoder<'s> {
+    buffer: &'s mut [u8],
+    start: usize,
+    end: usize,
+    state: BodyState,
+}
+
+impl<'s> BodyDecoder<'s> {
+    /// Create a decoder whose read-ahead `buffer` already holds `buffered` body bytes.
+    const fn new(buffer: &'s mut [u8], buffered: usize, framing: BodyFraming) -> Self {
+        Self {
+            buffer,
+            start: 0,
+            end: buffered,
+            state: BodyState::new(framing),
+        }
+    }
+
+    async fn read<R: Read<Error = Error>>(
+        &mut self,
+        source: &mut R,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            match self.state {
+                BodyState::Done => return Ok(0),
+                BodyState::UntilClose => {
+                    let n = self.read_data(source, buf).await?;
+                    if n == 0 {
+                        self.state = BodyState::Done;
+                    }
+                    return Ok(n);
+                }
+                BodyState::Length(remaining) | BodyState::ChunkData(remaining) => {
+                    let len = buf.len().min(remaining);
+                    let n = self.read_data(source, &mut buf[..len]).await?;
+                    if n == 0 {
+                        return Err(Error::InvalidResponse(
+                            "Connection closed before response body was complete",
+                        ));
+                    }
+                    self.state = match (self.state, remaining - n) {
+                        (BodyState::Length(_), 0) => BodyState::Done,
+                        (BodyState::Length(_), left) => BodyState::Length(left),
+                        (_, 0) => BodyState::ChunkDataCr,
+                        (_, left) => BodyState::ChunkData(left),
+                    };
+                    return Ok(n);
+                }
+                state => {
+                    let byte = self.next_byte(source).await?.ok_or(Error::InvalidResponse(
+                        "Connection closed before response body was complete",
+                    ))?;
+                    self.state = state.next(byte).map_err(Error::InvalidResponse)?;
+                }
+            }
+        }
+    }
+
+    /// Read body data, draining the read-ahead buffer first.
+    async fn read_data<R: Read<Error = Error>>(
+        &mut self,
+        source: &mut R,
+        buf: &mut [u8],
+    ) -> Result<usize, Error> {
+        if self.start < self.end {
+            let n = buf.len().min(self.end - self.start);
+            buf[..n].copy_from_slice(&self.buffer[self.start..self.start + n]);
+            self.start += n;
+            return Ok(n);
+        }
+        source.read(buf).await
+    }
+
+    /// Read the next framing byte, or `None` if the source is exhausted.
+    async fn next_byte<R: Read<Error = Error>>(
+        &mut self,
+        source: &mut R,
+    ) -> Result<Option<u8>, Error> {
+        if self.start == self.end {
+            if self.buffer.is_empty() {
+                return Err(Error::BufferOverflow);
+            }
+            let n = source.read(self.buffer).await?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.start = 0;
+            self.end = n;
+        }
+        let byte = self.buffer[self.start];
+        self.start += 1;
+        Ok(Some(byte))
+    }
+}
+
+impl<
+    'a,
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector,
+> HttpClient<'a, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
+    /// Make an HTTP request and stream the response body
+    ///
+    /// Unlike [`HttpClient::request`], only the status line and headers have
+    /// to fit into `header_buffer`. The rest of `header_buffer` serves as a
+    /// read-ahead buffer for the body, and the connection lives in `buffers`
+    /// until the returned [`BodyReader`] is dropped or closed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the URL is malformed, if connecting fails, if the
+    /// status line and headers do not fit into `header_buffer`
+    /// ([`Error::BufferOverflow`]) or if they cannot be parsed. Errors while
+    /// reading the body are returned by [`BodyReader`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use embedded_io_async::Read;
+    /// use nanofish::{DefaultHttpClient, HttpMethod, SessionBuffers};
+    /// use embassy_net::Stack;
+    ///
+    /// async fn example(stack: &Stack<'_>) -> Result<(), nanofish::Error> {
+    ///     let client = DefaultHttpClient::new(stack);
+    ///     let mut buffers = SessionBuffers::new();
+    ///     let mut header_buffer = [0u8; 1024];
+    ///     let mut response = client
+    ///         .request_streaming(
+    ///             HttpMethod::GET,
+    ///             "http://example.com/firmware.bin",
+    ///             &[],
+    ///             None,
+    ///             &mut buffers,
+    ///             &mut header_buffer,
+    ///         )
+    ///         .await?;
+    ///
+    ///     let mut chunk = [0u8; 512];
+    ///     loop {
+    ///         let n = response.body.read(&mut chunk).await?;
+    ///         if n == 0 {
+    ///             break;
+    ///         }
+    ///         // Write chunk[..n] to flash
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    #[expect(clippy::future_not_send)]
+    pub async fn request_streaming<'s>(
+        &'s self,
+        method: HttpMethod,
+        endpoint: &str,
+        headers: &[HttpHeader<'_>],
+        body: Option<&[u8]>,
+        buffers: &'s mut SessionBuffers<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>,
+        header_buffer: &'s mut [u8],
+    ) -> Result<StreamingResponse<'s, C::Transport<'s>>, Error>
+    where
+        'a: 's,
+    {
+        let url = Url::parse(endpoint)?;
+        let (scheme, (host, port)) = Self::connection_target(&url)?;
+        let path = url.path_and_query();
+
+        let mut connection = self.open_connection(scheme, (host, port), buffers).await?;
+
+        let http_request = Self::build_http_request(
+            method,
+            scheme,
+            (host, port),
+            path,
+            headers,
+            body.map(|b| BodyFraming::Length(b.len())),
+            false,
+        )?;
+        connection.write_all(http_request.as_bytes()).await?;
+        if let Some(body_data) = body {
+            connection.write_all(body_data).await?;
+        }
+        connection.flush().await?;
+
+        let (head, total_read) = Self::read_head(&mut connection, header_buffer, method).await?;
+
+        let (head_bytes, read_ahead) = header_buffer.split_at_mut(head.len);
+        let response = Self::parse_http_response_zero_copy(head_bytes)?;
+
+        Ok(StreamingResponse {
+            status_code: response.status_code,
+            headers: response.headers,
+            body: BodyReader {
+                connection,
+                decoder: BodyDecoder::new(read_ahead, total_read - head.len, head.framing),
+            },
+        })
+    }
+
+    /// Read until the end of the head of the response to a `method` request.
+    ///
+    /// Returns the parsed head and the total number of bytes read into
+    /// `buffer`.
+    pub(crate) async fn read_head<R: Read<Error = Error>>(
+        source: &mut R,
+        buffer: &mut [u8],
+        method: HttpMethod,
+    ) -> Result<(Head, usize), Error> {
+        let mut parser = Parser::response(method);
+        let mut total_read = 0;
+        loop {
+            if total_read == buffer.len() {
+                return Err(Error::BufferOverflow);
+            }
+            let n = source.read(&mut buffer[total_read..]).await?;
+            if n == 0 {
+                return Err(if total_read == 0 {
+                    Error::NoResponse
+                } else {
+                    Error::InvalidResponse("Connection closed before headers were complete")
+                });
+            }
+            total_read += n;
+            if let Some(head) = parser.parse_head(&buffer[..total_read])? {
+                return Ok((head, total_read));
+            }
+        }
+    }
+    /// Make an HTTP request whose body is streamed from a reader
+    ///
+    /// The body is read from `body` piece by piece and written to the
+    /// connection, so it never has to be held in memory as a whole. With a
+    /// known `body_len` the request carries `Content-Length` and exactly that
+    /// many bytes are sent; otherwise it is sent with
+    /// `Transfer-Encoding: chunked` until the reader is exhausted.
+    ///
+    /// `response_buffer` doubles as the scratch buffer for body pieces before
+    /// it receives the response, which is then handled exactly as with
+    /// [`HttpClient::request`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BodySource`] if reading `body` fails or it ends before
+    /// `body_len` bytes, and otherwise the same errors as
+    /// [`HttpClient::request`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use nanofish::{DefaultHttpClient, HttpMethod};
+    /// use embassy_net::Stack;
+    ///
+    /// async fn example(stack: &Stack<'_>, log: &[u8]) -> Result<(), nanofish::Error> {
+    ///     let client = DefaultHttpClient::new(stack);
+    ///     // Any `embedded_io_async::Read` works, such as a file or a flash region
+    ///     let mut source = log;
+    ///     let mut buffer = [0u8; 1024];
+    ///     let (response, _) = client
+    ///         .request_upload(
+    ///             HttpMethod::POST,
+    ///             "http://example.com/logs",
+    ///             &[],
+    ///             &mut source,
+    ///             Some(log.len()),
+    ///             &mut buffer,
+    ///         )
+    ///         .await?;
+    ///     assert!(response.is_success());
+    ///     Ok(())
+    /// }
+    /// ```
+    #[expect(clippy::future_not_send)]
+    pub async fn request_upload<'b, R: Read>(
+        &self,
+        method: HttpMethod,
+        endpoint: &str,
+        headers: &[HttpHeader<'_>],
+        body: &mut R,
+        body_len: Option<usize>,
+        response_buffer: &'b mut [u8],
+    ) -> Result<(HttpResponse<'b>, usize), Error> {
+        let url = Url::parse(endpoint)?;
+        let (scheme, (host, port)) = Self::connection_target(&url)?;
+        let path = url.path_and_query();
+        let framing = body_len.map_or(BodyFraming::Chunked, BodyFraming::Length);
+        let http_request = Self::build_http_request(
+            method,
+            scheme,
+            (host, port),
+            path,
+            headers,
+            Some(framing),
+            false,
+        )?;
+
+        let mut buffers = SessionBuffers::<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>::new();
+        let mut connection = self
+            .open_connection(scheme, (host, port), &mut buffers)
+            .await?;
+
+        let result = async {
+            connection.write_all(http_request.as_bytes()).await?;
+            Self::send_body(&mut connection, body, framing, response_buffer).await?;
+            Self::read_response(&mut connection, method, response_buffer).await
+        }
+        .await;
+
+        connection.close().await;
+        Timer::after(self.options.socket_close_delay).await;
+
+        let total_read = result?.decode_body(response_buffer);
+        let response = Self::parse_http_response_zero_copy(&response_buffer[..total_read])?;
+        Ok((response, total_read))
+    }
+
+    /// Copy a request body from `body` to `sink` with the given framing.
+    ///
+    /// `scratch` holds each piece between reading and writing it.
+    async fn send_body<W: Write<Error = Error>, R: Read>(
+        sink: &mut W,
+        body: &mut R,
+        framing: BodyFraming,
+        scratch: &mut [u8],
+    ) -> Result<(), Error> {
+        if scratch.is_empty() {
+            return Err(Error::BufferOverflow);
+        }
+
+        let mut remaining = match framing {
+            BodyFraming::Length(len) => Some(len),
+            BodyFraming::Chunked | BodyFraming::UntilClose => None,
+        };
+        let chunked = framing == BodyFraming::Chunked;
+
+        loop {
+            let len = remaining.map_or(scratch.len(), |left| left.min(scratch.len()));
+            if len == 0 {
+                break;
+            }
+            let n = body
+                .read(&mut scratch[..len])
+                .await
+                .map_err(|e| Error::BodySource(e.kind()))?;
+            if n == 0 {
+                if remaining.is_some() {
+                    // The body ended before the announced length
+                    return Err(Error::BodySource(ErrorKind::InvalidInput));
+                }
+                break;
+            }
+
+            if let Some(left) = remaining.as_mut() {
+                *left -= n;
+            }
+            if chunked {
+                write_chunk(sink, &scratch[..n]).await?;
+            } else {
+                sink.write_all(&scratch[..n]).await?;
+            }
+        }
+
+        if chunked {
+            sink.write_all(CHUNKED_END_MARKER).await?;
+        }
+        sink.flush().await
+    }
+
+    /// Read the complete response to a `method` request into `buffer`.
+    ///
+    /// Returns [`Error::BufferOverflow`] if the response does not fit.
+    async fn read_response<R: Read<Error = Error>>(
+        source: &mut R,
+        method: HttpMethod,
+        buffer: &mut [u8],
+    ) -> Result<Message, Error> {
+        let mut parser = Parser::response(method);
+        let mut total_read = 0;
+        loop {
+            if total_read == buffer.len() {
+                return Err(Error::BufferOverflow);
+            }
+            let n = source.read(&mut buffer[total_read..]).await?;
+            if n == 0 {
+                return if total_read == 0 {
+                    Err(Error::NoResponse)
+                } else {
+                    parser.finish()
+                };
+            }
+            total_read += n;
+            if let Some(message) = parser.parse(&buffer[..total_read])? {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        DefaultHttpClient,
+        testing::{MockPeer, Step},
+    };
+
+    /// Decode `wire` with the given framing, reading `out_step` bytes at a time.
+    fn decode(
+        wire: &[u8],
+        framing: BodyFraming,
+        step: usize,
+        out_step: usize,
+    ) -> Result<std::vec::Vec<u8>, Error> {
+        let script = [Step::Send(wire)];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(step);
+        let mut source = peer.connection();
+        let mut read_ahead = [0u8; 8];
+        let mut decoder = BodyDecoder::new(&mut read_ahead, 0, framing);
+        let mut out = std::vec::Vec::new();
+        let mut chunk = [0u8; 64];
+        loop {
+            let n =
+                futures_lite::future::block_on(decoder.read(&mut source, &mut chunk[..out_step]))?;
+            if n == 0 {
+                return Ok(out);
+            }
+            out.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    #[test]
+    fn test_decode_content_length() {
+        let wire = b"hello world, trailing garbage";
+        for step in [1, 3, 64] {
+            let body = decode(wire, BodyFraming::Length(11), step, 4).unwrap();
+            assert_eq!(body, b"hello world");
+        }
+    }
+
+    #[test]
+    fn test_decode_content_length_truncated() {
+        let result = decode(b"short", BodyFraming::Length(10), 64, 64);
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
+    }
+
+    #[test]
+    fn test_decode_until_close() {
+        let body = decode(b"all of it", BodyFraming::UntilClose, 2, 3).unwrap();
+        assert_eq!(body, b"all of it");
+    }
+
+    #[test]
+    fn test_decode_chunked() {
+        let wire = b"5;ext=1\r\nhello\r\n7\r\n, world\r\nA\r\n0123456789\r\n0\r\nX-Trailer: yes\r\n\r\nnext";
+        for step in [1, 2, 5, 64] {
+            for out_step in [1, 4, 64] {
+                let body = decode(wire, BodyFraming::Chunked, step, out_step).unwrap();
+                assert_eq!(body, b"hello, world0123456789");
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_chunked_invalid() {
+        // Missing CRLF after chunk data
+        let result = decode(b"3\r\nabcX\r\n0\r\n\r\n", BodyFraming::Chunked, 64, 64);
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
+        // Size line without digits
+        let result = decode(b"\r\n", BodyFraming::Chunked, 64, 64);
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
+        // Connection closed mid-chunk
+        let result = decode(b"a\r\nabc", BodyFraming::Chunked, 64, 64);
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
+        // Oversized chunk size
+        let result = decode(b"ffffffffffffffffff\r\n", BodyFraming::Chunked, 64, 64);
+        assert!(matches!(result, Err(Error::InvalidResponse(_))));
+    }
+
+    #[test]
+    fn test_decode_drains_read_ahead_first() {
+        let script = [Step::Send(b"world")];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut source = peer.connection();
+        let mut read_ahead = *b"hello ..";
+        let mut decoder = BodyDecoder::new(&mut read_ahead, 6, BodyFraming::Length(11));
+        let mut out = [0u8; 16];
+        let n = futures_lite::future::block_on(decoder.read(&mut source, &mut out)).unwrap();
+        assert_eq!(&out[..n], b"hello ");
+        let n = futures_lite::future::block_on(decoder.read(&mut source, &mut out)).unwrap();
+        assert_eq!(&out[..n], b"world");
+        assert_eq!(decoder.state, BodyState::Done);
+    }
+
+    #[test]
+    fn test_send_body_with_length() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let mut body: &[u8] = b"hello world, and more";
+        let mut scratch = [0u8; 4];
+        futures_lite::future::block_on(<DefaultHttpClient>::send_body(
+            &mut sink,
+            &mut body,
+            BodyFraming::Length(11),
+            &mut scratch,
+        ))
+        .unwrap();
+        assert_eq!(&*peer.written(), b"hello world");
+        // Nothing past the announced length is consumed
+        assert_eq!(body, b", and more");
+    }
+
+    #[test]
+    fn test_send_body_too_short() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let mut body: &[u8] = b"short";
+        let mut scratch = [0u8; 16];
+        let result = futures_lite::future::block_on(<DefaultHttpClient>::send_body(
+            &mut sink,
+            &mut body,
+            BodyFraming::Length(10),
+            &mut scratch,
+        ));
+        assert!(matches!(
+            result,
+            Err(Error::BodySource(ErrorKind::InvalidInput))
+        ));
+    }
+
+    #[test]
+    fn test_send_body_chunked() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let mut body: &[u8] = b"0123456789abcdefXYZ";
+        let mut scratch = [0u8; 16];
+        futures_lite::future::block_on(<DefaultHttpClient>::send_body(
+            &mut sink,
+            &mut body,
+            BodyFraming::Chunked,
+            &mut scratch,
+        ))
+        .unwrap();
+        assert_eq!(
+            &*peer.written(),
+            b"10\r\n0123456789abcdef\r\n3\r\nXYZ\r\n0\r\n\r\n"
+        );
+
+        // The encoded body decodes back to the original
+        let decoded = decode(&peer.written(), BodyFraming::Chunked, 5, 7).unwrap();
+        assert_eq!(decoded, b"0123456789abcdefXYZ");
+    }
+
+    #[test]
+    fn test_read_response() {
+        let wire = b"HTTP/1.1 201 Created\r\nContent-Length: 2\r\n\r\nok";
+        let script = [Step::Send(wire)];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(7);
+        let mut source = peer.connection();
+        let mut buffer = [0u8; 128];
+        let message = futures_lite::future::block_on(<DefaultHttpClient>::read_response(
+            &mut source,
+            HttpMethod::POST,
+            &mut buffer,
+        ))
+        .unwrap();
+        assert_eq!(&buffer[..message.len], wire);
+
+        let script = [Step::Send(wire)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut source = peer.connection();
+        let mut small = [0u8; 20];
+        let result = futures_lite::future::block_on(<DefaultHttpClient>::read_response(
+            &mut source,
+            HttpMethod::POST,
+            &mut small,
+        ));
+        assert!(matches!(result, Err(Error::BufferOverflow)));
+    }
+
+    #[test]
+    fn test_read_head() {
+        let wire = b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\nbody";
+        let script = [Step::Send(wire)];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(3);
+        let mut source = peer.connection();
+        let mut buffer = [0u8; 64];
+        let (head, total_read) = futures_lite::future::block_on(<DefaultHttpClient>::read_head(
+            &mut source,
+            &mut buffer,
+            HttpMethod::GET,
+        ))
+        .unwrap();
+        assert_eq!(head.len, wire.len() - 4);
+        assert_eq!(head.framing, BodyFraming::Length(4));
+        assert!(total_read >= head.len);
+
+        let script = [Step::Send(wire)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut source = peer.connection();
+        let mut small = [0u8; 16];
+        let result = futures_lite::future::block_on(<DefaultHttpClient>::read_head(
+            &mut source,
+            &mut small,
+            HttpMethod::GET,
+        ));
+        assert!(matches!(result, Err(Error::BufferOverflow)));
+
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut source = peer.connection();
+        let result = futures_lite::future::block_on(<DefaultHttpClient>::read_head(
+            &mut source,
+            &mut buffer,
+            HttpMethod::GET,
+        ));
+        assert!(matches!(result, Err(Error::NoResponse)));
+    }
+}
@@ -0,0 +1,713 @@
+//! Available with the `testing` feature, and to the crate's own tests.
+//!
+//! A [`MockPeer`](crate::testing::MockPeer) plays the other end of a
+//! connection from a script of [`Step`](crate::testing::Step)s: the bytes it
+//! sends, pauses between them and resets. It records everything written to
+//! it, so the request a client sent or the response a server produced can be
+//! checked afterwards. Pass it by reference to
+//! [`HttpClient::with_connector`](crate::HttpClient::with_connector) to test
+//! the client, or to [`serve_once`](crate::testing::serve_once) to test the
+//! server.
+//!
+//! A [`Loopback`](crate::testing::Loopback) instead joins two ends that are
+//! both driven by the code under test, such as a TLS client and server.
+//!
+//! Delays use [`embassy_time`], so the tests need a time driver, such as the
+//! one of its `std` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use embassy_time::Duration;
+//! use nanofish::testing::{MockPeer, Step};
+//! use nanofish::{HttpClient, HttpClientOptions};
+//!
+//! # embassy_futures::block_on(async {
+//! let script = [
+//!     Step::Send(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"),
+//!     Step::Delay(Duration::from_millis(10)),
+//!     Step::Send(b"5\r\nhello\r\n0\r\n\r\n"),
+//! ];
+//! let peer: MockPeer = MockPeer::new(&script).with_segment_size(1);
+//!
+//! let client = HttpClient::<'_, 1024, 1024, 1024, 1024, 1024, _>::with_connector(
+//!     &peer,
+//!     HttpClientOptions::default(),
+//! );
+//! let mut buffer = [0u8; 1024];
+//! let (response, _) = client.get("http://device.local/", &[], &mut buffer).await?;
+//! assert_eq!(response.body.as_str(), Some("hello"));
+//! assert!(peer.written().starts_with(b"GET / HTTP/1.1\r\n"));
+//! # Ok::<(), nanofish::Error>(())
+//! # })?;
+//! # Ok::<(), nanofish::Error>(())
+//! ```
+
+use crate::{
+    error::Error,
+    handler::StreamingHandler,
+    server::{HttpServer, ServerBuffers},
+    transport::{Acceptor, Connector, Transport},
+};
+use core::cell::{Cell, Ref, RefCell};
+use embassy_futures::select::select;
+use embassy_net::tcp;
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, pipe::Pipe, signal::Signal};
+use embassy_time::{Duration, Instant, Timer};
+use embedded_io_async::{ErrorType, Read, Write};
+use heapless::Vec;
+
+/// One step of a [`MockPeer`]'s script
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Step<'a> {
+    /// Send bytes, handed out in reads of at most the peer's segment size.
+    /// A read never returns bytes of two steps.
+    Send(&'a [u8]),
+    /// Wait before the next step. A delay longer than the connection's
+    /// timeout fails the read like an expired socket timeout.
+    Delay(Duration),
+    /// Reset the connection: pending and later reads and writes fail with
+    /// a connection reset
+    Reset,
+}
+
+/// The remote end of a scripted connection
+///
+/// Every connection opened through the [`Connector`] implementation, or
+/// reopened with [`Transport::reconnect`], replays the script from the start,
+/// and the connection is closed by the peer once the script has run out.
+///
+/// As an [`Acceptor`] it hands out a single connection and then waits
+/// forever, and a slice of peers hands out one connection from each peer in
+/// turn. Bytes written to any of its connections are recorded, up to `N`
+/// bytes.
+pub struct MockPeer<'a, const N: usize = 4096> {
+    script: &'a [Step<'a>],
+    segment_size: usize,
+    written: RefCell<Vec<u8, N>>,
+    connections: Cell<usize>,
+    closed: Signal<NoopRawMutex, ()>,
+}
+
+impl<'a, const N: usize> MockPeer<'a, N> {
+    /// Create a peer that plays `script`, sending each step's bytes at once
+    #[must_use]
+    pub const fn new(script: &'a [Step<'a>]) -> Self {
+        Self {
+            script,
+            segment_size: usize::MAX,
+            written: RefCell::new(Vec::new()),
+            connections: Cell::new(0),
+            closed: Signal::new(),
+        }
+    }
+
+    /// Hand out sent bytes in reads of at most `segment_size` bytes, to
+    /// exercise parsing of data that arrives in pieces
+    #[must_use]
+    pub const fn with_segment_size(mut self, segment_size: usize) -> Self {
+        self.segment_size = if segment_size == 0 { 1 } else { segment_size };
+        self
+    }
+
+    /// Everything written to the peer's connections so far
+    pub fn written(&self) -> Ref<'_, [u8]> {
+        Ref::map(self.written.borrow(), |written| written.as_slice())
+    }
+
+    /// The number of connections opened or accepted so far
+    pub const fn connections(&self) -> usize {
+        self.connections.get()
+    }
+
+    /// Wait until a connection to the peer is closed by the other side
+    #[expect(clippy::future_not_send)]
+    pub async fn closed(&self) {
+        self.closed.wait().await;
+    }
+
+    /// Open a connection that plays the script without a [`Connector`] or
+    /// [`Acceptor`], to test code that reads from or writes to a connection
+    /// directly. Delays never time out on it.
+    pub fn connection(&self) -> MockTransport<'_, 'a, N> {
+        self.open(None)
+    }
+
+    /// Start a connection that plays the script
+    fn open(&self, timeout: Option<Duration>) -> MockTransport<'_, 'a, N> {
+        self.connections.set(self.connections.get() + 1);
+        MockTransport {
+            peer: self,
+            step: 0,
+            offset: 0,
+            deadline: None,
+            timeout,
+            reset: false,
+        }
+    }
+}
+
+impl<'a, const N: usize> Connector for MockPeer<'a, N> {
+    type Transport<'b>
+        = MockTransport<'b, 'a, N>
+    where
+        Self: 'b;
+
+    #[expect(clippy::future_not_send)]
+    async fn connect<'b>(
+        &'b self,
+        _host: &str,
+        _port: u16,
+        timeout: Duration,
+        _rx_buffer: &'b mut [u8],
+        _tx_buffer: &'b mut [u8],
+    ) -> Result<MockTransport<'b, 'a, N>, Error> {
+        Ok(self.open(Some(timeout)))
+    }
+}
+
+impl<'a, const N: usize> Acceptor for MockPeer<'a, N> {
+    type Transport<'b>
+        = MockTransport<'b, 'a, N>
+    where
+        Self: 'b;
+
+    #[expect(clippy::future_not_send)]
+    async fn accept<'b>(
+        &'b self,
+        _port: u16,
+        _timeout: Duration,
+        _rx_buffer: &'b mut [u8],
+        _tx_buffer: &'b mut [u8],
+    ) -> Result<MockTransport<'b, 'a, N>, Error> {
+        if self.connections.get() > 0 {
+            core::future::pending::<()>().await;
+        }
+        Ok(self.open(None))
+    }
+}
+
+impl<'a, const N: usize> Acceptor for [MockPeer<'a, N>] {
+    type Transport<'b>
+        = MockTransport<'b, 'a, N>
+    where
+        Self: 'b;
+
+    #[expect(clippy::future_not_send)]
+    async fn accept<'b>(
+        &'b self,
+        _port: u16,
+        _timeout: Duration,
+        _rx_buffer: &'b mut [u8],
+        _tx_buffer: &'b mut [u8],
+    ) -> Result<MockTransport<'b, 'a, N>, Error> {
+        match self.iter().find(|peer| peer.connections() == 0) {
+            Some(peer) => Ok(peer.open(None)),
+            None => core::future::pending().await,
+        }
+    }
+}
+
+/// A connection to a [`MockPeer`]
+pub struct MockTransport<'b, 'a, const N: usize> {
+    peer: &'b MockPeer<'a, N>,
+    step: usize,
+    offset: usize,
+    /// When the delay being waited for ends
+    deadline: Option<Instant>,
+    timeout: Option<Duration>,
+    reset: bool,
+}
+
+impl<const N: usize> MockTransport<'_, '_, N> {
+    /// The error of a reset connection, as reported by embassy-net
+    const RESET: Error = Error::TcpError(tcp::Error::ConnectionReset);
+}
+
+impl<const N: usize> ErrorType for MockTransport<'_, '_, N> {
+    type Error = Error;
+}
+
+impl<const N: usize> Read for MockTransport<'_, '_, N> {
+    #[expect(clippy::future_not_send)]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            if self.reset {
+                return Err(Self::RESET);
+            }
+            let Some(&step) = self.peer.script.get(self.step) else {
+                return Ok(0);
+            };
+
+            match step {
+                Step::Send(bytes) => {
+                    let remaining = &bytes[self.offset..];
+                    let n = remaining.len().min(self.peer.segment_size).min(buf.len());
+                    buf[..n].copy_from_slice(&remaining[..n]);
+                    self.offset += n;
+                    if self.offset == bytes.len() {
+                        self.step += 1;
+                        self.offset = 0;
+                    }
+                    if n > 0 || buf.is_empty() {
+                        return Ok(n);
+                    }
+                }
+                Step::Delay(delay) => {
+                    let wait = self.timeout.map_or(delay, |timeout| delay.min(timeout));
+                    // A cancelled read resumes the same delay
+                    let deadline = *self.deadline.get_or_insert_with(|| Instant::now() + wait);
+                    Timer::at(deadline).await;
+                    self.deadline = None;
+                    self.step += 1;
+                    self.reset = wait < delay;
+                }
+                Step::Reset => {
+                    self.step += 1;
+                    self.reset = true;
+                }
+            }
+        }
+    }
+}
+
+impl<const N: usize> Write for MockTransport<'_, '_, N> {
+    #[expect(clippy::future_not_send)]
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if self.reset {
+            return Err(Self::RESET);
+        }
+        self.peer
+            .written
+            .borrow_mut()
+            .extend_from_slice(buf)
+            .map_err(|_| Error::BufferOverflow)?;
+        Ok(buf.len())
+    }
+
+    #[expect(clippy::future_not_send)]
+    async fn flush(&mut self) -> Result<(), Error> {
+        if self.reset {
+            return Err(Self::RESET);
+        }
+        Ok(())
+    }
+}
+
+impl<const N: usize> Transport for MockTransport<'_, '_, N> {
+    #[expect(clippy::future_not_send)]
+    async fn close(self) {
+        self.peer.closed.signal(());
+    }
+
+    #[expect(clippy::future_not_send)]
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        *self = self.peer.open(self.timeout);
+        Ok(())
+    }
+}
+
+/// An in-memory connection between two ends in the same test
+///
+/// Unlike a [`MockPeer`], both ends are driven by code under test, such as a
+/// TLS client and server joined in one future. Each direction buffers up to
+/// `N` bytes; writes wait while it is full and reads while it is empty.
+pub struct Loopback<const N: usize = 4096> {
+    to_server: Pipe<NoopRawMutex, N>,
+    to_client: Pipe<NoopRawMutex, N>,
+}
+
+impl<const N: usize> Loopback<N> {
+    /// Create an idle connection
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            to_server: Pipe::new(),
+            to_client: Pipe::new(),
+        }
+    }
+
+    /// The client's end of the connection
+    pub const fn client(&self) -> LoopbackEnd<'_, N> {
+        LoopbackEnd {
+            rx: &self.to_client,
+            tx: &self.to_server,
+        }
+    }
+
+    /// The server's end of the connection
+    pub const fn server(&self) -> LoopbackEnd<'_, N> {
+        LoopbackEnd {
+            rx: &self.to_server,
+            tx: &self.to_client,
+        }
+    }
+}
+
+impl<const N: usize> Default for Loopback<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// One end of a [`Loopback`] connection
+pub struct LoopbackEnd<'l, const N: usize> {
+    rx: &'l Pipe<NoopRawMutex, N>,
+    tx: &'l Pipe<NoopRawMutex, N>,
+}
+
+impl<const N: usize> ErrorType for LoopbackEnd<'_, N> {
+    type Error = Error;
+}
+
+impl<const N: usize> Read for LoopbackEnd<'_, N> {
+    #[expect(clippy::future_not_send)]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        Ok(self.rx.read(buf).await)
+    }
+}
+
+impl<const N: usize> Write for LoopbackEnd<'_, N> {
+    #[expect(clippy::future_not_send)]
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        Ok(self.tx.write(buf).await)
+    }
+
+    #[expect(clippy::future_not_send)]
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Serve the connection of `peer` with `server` and `handler`
+///
+/// The peer's script is the raw request data sent by the client. Returns once
+/// the server has closed the connection; the response it wrote is in
+/// [`MockPeer::written`]. The peer must not have been connected before.
+#[expect(clippy::future_not_send)]
+pub async fn serve_once<
+    const RX_SIZE: usize,
+    const TX_SIZE: usize,
+    const REQ_SIZE: usize,
+    const MAX_RESPONSE_SIZE: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const N: usize,
+    H: StreamingHandler,
+>(
+    server: &HttpServer<RX_SIZE, TX_SIZE, REQ_SIZE, MAX_RESPONSE_SIZE, TLS_READ, TLS_WRITE>,
+    peer: &MockPeer<'_, N>,
+    handler: H,
+) {
+    let mut buffers = ServerBuffers::<RX_SIZE, TX_SIZE, REQ_SIZE>::new();
+    select(
+        server.serve_shared(peer, handler, &mut buffers),
+        peer.closed(),
+    )
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HttpClient, HttpClientOptions, HttpHeader, HttpMethod, RedirectPolicy, ResponseBody,
+        SessionBuffers, SimpleHandler, SmallHttpServer, redirect::MAX_REDIRECT_URL_LEN,
+    };
+    use futures_lite::future::block_on;
+
+    type Client<'p> = HttpClient<'p, 64, 64, 64, 64, 512, &'p MockPeer<'p>>;
+
+    fn options() -> HttpClientOptions {
+        HttpClientOptions {
+            max_retries: 1,
+            socket_timeout: Duration::from_millis(50),
+            socket_close_delay: Duration::from_ticks(0),
+            ..HttpClientOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_client_chunked_response_in_single_bytes() {
+        let script = [
+            Step::Send(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Step::Delay(Duration::from_millis(1)),
+            Step::Send(b"5\r\nhello\r\n6\r\n world\r\n0\r\n\r\n"),
+        ];
+        let peer = MockPeer::new(&script).with_segment_size(1);
+        let client = Client::with_connector(&peer, options());
+
+        let mut buffer = [0u8; 256];
+        let (response, _) =
+            block_on(client.get("http://device.local/greeting", &[], &mut buffer)).unwrap();
+        assert_eq!(response.body, ResponseBody::Text("hello world"));
+        assert_eq!(peer.connections(), 1);
+        assert!(
+            peer.written()
+                .starts_with(b"GET /greeting HTTP/1.1\r\nHost: device.local\r\n")
+        );
+    }
+
+    #[test]
+    fn test_client_stops_at_content_length() {
+        // Bytes after the announced body are never waited for
+        let script = [
+            Step::Send(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"),
+            Step::Delay(Duration::from_secs(60)),
+            Step::Send(b"ignored"),
+        ];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+
+        let mut buffer = [0u8; 256];
+        let (response, _) = block_on(client.get("http://device.local/", &[], &mut buffer)).unwrap();
+        assert_eq!(response.body, ResponseBody::Text("ok"));
+    }
+
+    #[test]
+    fn test_client_timeout_and_reset() {
+        let mut buffer = [0u8; 256];
+
+        let script = [
+            Step::Send(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhel"),
+            Step::Delay(Duration::from_secs(60)),
+        ];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+        let result = block_on(client.get("http://device.local/", &[], &mut buffer)).map(|_| ());
+        assert!(matches!(
+            result,
+            Err(Error::TcpError(tcp::Error::ConnectionReset))
+        ));
+
+        let script = [
+            Step::Send(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhel"),
+            Step::Reset,
+        ];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+        let result = block_on(client.get("http://device.local/", &[], &mut buffer)).map(|_| ());
+        assert!(matches!(
+            result,
+            Err(Error::TcpError(tcp::Error::ConnectionReset))
+        ));
+    }
+
+    #[test]
+    fn test_client_host_header_names_port() {
+        let script = [Step::Send(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        )];
+        let mut buffer = [0u8; 256];
+        for (url, host) in [
+            ("http://device.local:8080/", "Host: device.local:8080\r\n"),
+            ("http://device.local:80/", "Host: device.local\r\n"),
+            ("http://[fe80::1]:8080/", "Host: [fe80::1]:8080\r\n"),
+        ] {
+            let peer = MockPeer::new(&script);
+            let client = Client::with_connector(&peer, options());
+            block_on(client.get(url, &[], &mut buffer)).unwrap();
+            let written = core::str::from_utf8(&peer.written()).unwrap().to_owned();
+            assert!(written.starts_with("GET / HTTP/1.1\r\n"));
+            assert!(written.contains(host));
+        }
+    }
+
+    #[test]
+    fn test_streaming_host_header_names_port() {
+        let script = [Step::Send(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        )];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+        let mut buffers = SessionBuffers::new();
+        let mut header_buffer = [0u8; 128];
+        let response = block_on(client.request_streaming(
+            HttpMethod::GET,
+            "http://device.local:8080/log",
+            &[],
+            None,
+            &mut buffers,
+            &mut header_buffer,
+        ));
+        assert!(response.is_ok());
+        drop(response);
+        assert!(
+            peer.written()
+                .starts_with(b"GET /log HTTP/1.1\r\nHost: device.local:8080\r\n")
+        );
+    }
+
+    #[test]
+    fn test_upload_host_header_names_port() {
+        let script = [Step::Send(
+            b"HTTP/1.1 204 No Content\r\nContent-Length: 0\r\n\r\n",
+        )];
+        let peer = MockPeer::new(&script);
+        let client = Client::with_connector(&peer, options());
+        let mut body: &[u8] = b"image";
+        let mut buffer = [0u8; 256];
+        block_on(client.request_upload(
+            HttpMethod::PUT,
+            "http://device.local:8080/firmware",
+            &[],
+            &mut body,
+            Some(5),
+            &mut buffer,
+        ))
+        .unwrap();
+        assert!(
+            peer.written()
+                .starts_with(b"PUT /firmware HTTP/1.1\r\nHost: device.local:8080\r\n")
+        );
+    }
+
+    #[test]
+    fn test_client_long_url_without_redirect() {
+        let script = [Step::Send(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+        )];
+        let peer: MockPeer = MockPeer::new(&script);
+        let options = HttpClientOptions {
+            redirect_policy: RedirectPolicy::SameOrigin { max_hops: 1 },
+            ..options()
+        };
+        let client = HttpClient::<'_, 64, 64, 64, 64, 1024, _>::with_connector(&peer, options);
+
+        // Longer than any redirect target, but never copied as one
+        let url = format!("http://device.local/{}", "a".repeat(MAX_REDIRECT_URL_LEN));
+        let mut buffer = [0u8; 256];
+        let (response, _) = block_on(client.get(&url, &[], &mut buffer)).unwrap();
+        assert_eq!(response.body, ResponseBody::Text("ok"));
+    }
+
+    #[test]
+    fn test_client_redirect_drops_body_headers() {
+        let script = [Step::Send(
+            b"HTTP/1.1 303 See Other\r\nLocation: /done\r\nContent-Length: 0\r\n\r\n",
+        )];
+        let peer = MockPeer::new(&script);
+        let options = HttpClientOptions {
+            redirect_policy: RedirectPolicy::SameOrigin { max_hops: 1 },
+            ..options()
+        };
+        let client = Client::with_connector(&peer, options);
+
+        let headers = [
+            HttpHeader::content_type("application/json"),
+            HttpHeader::user_agent("nanofish"),
+        ];
+        let mut buffer = [0u8; 256];
+        let result =
+            block_on(client.post("http://device.local/form", &headers, b"{}", &mut buffer))
+                .map(|_| ());
+        // The peer answers the redirected request with the same redirect
+        assert!(matches!(result, Err(Error::TooManyRedirects)));
+
+        let written = core::str::from_utf8(&peer.written()).unwrap().to_owned();
+        let (first, second) = written.split_once("GET /done HTTP/1.1\r\n").unwrap();
+        assert!(first.starts_with("POST /form HTTP/1.1\r\n"));
+        assert!(first.contains("Content-Type: application/json\r\n"));
+        assert!(first.contains("Content-Length: 2\r\n"));
+        assert!(second.contains("User-Agent: nanofish\r\n"));
+        assert!(!second.contains("Content-Type"));
+        assert!(!second.contains("Content-Length"));
+        assert!(second.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_server_request_in_pieces() {
+        let script = [
+            Step::Send(b"GET /health HTTP/1.1\r\nHost: dev"),
+            Step::Delay(Duration::from_millis(1)),
+            Step::Send(b"ice\r\nConnection: close\r\n\r\n"),
+        ];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(3);
+        let server = SmallHttpServer::new(80);
+        block_on(serve_once(&server, &peer, SimpleHandler));
+
+        let response = core::str::from_utf8(&peer.written()).unwrap().to_owned();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with("\r\n\r\n{\"status\":\"ok\"}"));
+    }
+
+    #[test]
+    fn test_server_pipelined_requests() {
+        let script = [Step::Send(
+            b"GET /health HTTP/1.1\r\n\r\nGET /missing HTTP/1.1\r\nConnection: close\r\n\r\n",
+        )];
+        let peer: MockPeer = MockPeer::new(&script);
+        let server = SmallHttpServer::new(80);
+        block_on(serve_once(&server, &peer, SimpleHandler));
+
+        let response = core::str::from_utf8(&peer.written()).unwrap().to_owned();
+        let second = response.find("HTTP/1.1 404 Not Found\r\n").unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response[..second].ends_with("{\"status\":\"ok\"}"));
+    }
+
+    #[test]
+    fn test_server_head_then_get() {
+        let script = [Step::Send(
+            b"HEAD /health HTTP/1.1\r\n\r\nGET /health HTTP/1.1\r\nConnection: close\r\n\r\n",
+        )];
+        let peer: MockPeer = MockPeer::new(&script);
+        let server = SmallHttpServer::new(80);
+        block_on(serve_once(&server, &peer, SimpleHandler));
+
+        // The HEAD response announces the body without sending it, and the
+        // connection stays usable for the next request
+        let response = core::str::from_utf8(&peer.written()).unwrap().to_owned();
+        let (head, get) = response.split_at(response.rfind("HTTP/1.1 200 OK\r\n").unwrap());
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Length: 15\r\n"));
+        assert!(head.ends_with("Connection: keep-alive\r\n\r\n"));
+        assert!(get.contains("Content-Length: 15\r\n"));
+        assert!(get.ends_with("\r\n\r\n{\"status\":\"ok\"}"));
+    }
+
+    #[test]
+    fn test_server_request_too_large() {
+        let mut body = b"POST /data HTTP/1.1\r\nContent-Length: 2000\r\n\r\n".to_vec();
+        body.resize(1100, b'x');
+        let mut head = b"GET /health HTTP/1.1\r\nCookie: ".to_vec();
+        head.resize(1100, b'x');
+        let server = SmallHttpServer::new(80);
+
+        for (request, status) in [
+            (body, "413 Request Entity Too Large"),
+            (head, "431 Request Header Fields Too Large"),
+        ] {
+            let script = [Step::Send(&request), Step::Delay(Duration::from_secs(60))];
+            let peer: MockPeer = MockPeer::new(&script);
+            block_on(serve_once(&server, &peer, SimpleHandler));
+
+            let response = core::str::from_utf8(&peer.written()).unwrap().to_owned();
+            assert!(response.starts_with(&std::format!("HTTP/1.1 {status}\r\n")));
+            assert!(response.contains("Connection: close\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_server_incomplete_request() {
+        let script = [Step::Send(
+            b"POST /data HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc",
+        )];
+        let peer: MockPeer = MockPeer::new(&script);
+        let server = SmallHttpServer::new(80);
+        block_on(serve_once(&server, &peer, SimpleHandler));
+        assert!(peer.written().is_empty());
+    }
+
+    #[test]
+    fn test_server_reset_mid_request() {
+        let script = [
+            Step::Send(b"POST /data HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc"),
+            Step::Reset,
+        ];
+        let peer: MockPeer = MockPeer::new(&script);
+        let server = SmallHttpServer::new(80);
+        block_on(serve_once(&server, &peer, SimpleHandler));
+        assert!(peer.written().is_empty());
+    }
+}
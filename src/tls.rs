@@ -0,0 +1,909 @@
+use crate::error::Error;
+use chacha20poly1305::{
+    ChaCha20Poly1305,
+    consts::{U12, U32, U54},
+};
+use core::cell::RefCell;
+use embassy_time::Instant;
+use embedded_tls::{
+    Certificate, CertificateEntryRef, CertificateRef, CertificateVerifyRef, CryptoProvider,
+    CryptoRngCore, NoClock, SignatureScheme, TlsCipherSuite, TlsConfig, TlsError, TlsVerifier,
+    pki::CertVerifier,
+};
+use heapless::{String, Vec};
+use p256::ecdsa::{
+    Signature, SigningKey, VerifyingKey,
+    signature::{SignerMut, Verifier},
+};
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+
+/// Maximum length of a server name that can be verified against a certificate
+const MAX_HOSTNAME_LEN: usize = 64;
+
+/// Context string prefixed to the transcript hash in a server `CertificateVerify`
+pub(crate) const SERVER_VERIFY_CONTEXT: &[u8] = b"TLS 1.3, server CertificateVerify\x00";
+
+/// Length of the padding that precedes the context string in a `CertificateVerify`
+pub(crate) const SERVER_VERIFY_PADDING: usize = 64;
+
+/// Longest signed `CertificateVerify` message (padding + context + SHA-384 hash)
+const MAX_VERIFY_MESSAGE_LEN: usize = SERVER_VERIFY_PADDING + SERVER_VERIFY_CONTEXT.len() + 48;
+
+/// Uncompressed SEC1 P-256 public key length
+const P256_PUBLIC_KEY_LEN: usize = 65;
+
+/// How the client decides whether to trust the certificate presented by a server
+///
+/// The default is [`ServerVerification::RootCertificates`] with no roots, which
+/// rejects every server until trust anchors are configured. Skipping verification
+/// must be chosen explicitly with [`ServerVerification::Unverified`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServerVerification<'a> {
+    /// Verify the server chain against these DER-encoded root CA certificates
+    /// and check that the leaf certificate matches the requested host name.
+    ///
+    /// The root is selected by matching its subject against the issuer of the
+    /// topmost certificate sent by the server. Validity periods are not checked,
+    /// since most devices have no trusted wall clock.
+    RootCertificates(&'a [&'a [u8]]),
+    /// Accept only a server whose leaf public key matches one of these
+    /// SHA-256 hashes of the DER-encoded `SubjectPublicKeyInfo`.
+    ///
+    /// The chain and host name are not checked; the pin is the identity.
+    /// Only P-256 leaf keys are supported. A pin can be computed with
+    /// `openssl x509 -pubkey -noout -in cert.pem | openssl pkey -pubin -outform der | openssl dgst -sha256`.
+    PinnedPublicKeys(&'a [[u8; 32]]),
+    /// Accept any certificate without verification
+    ///
+    /// Anyone on the network path can impersonate the server. Only use this
+    /// for testing or when the transport is otherwise trusted.
+    Unverified,
+}
+
+impl Default for ServerVerification<'_> {
+    fn default() -> Self {
+        Self::RootCertificates(&[])
+    }
+}
+
+/// Options for TLS connections made by the HTTP client
+///
+/// Attach them to a client with [`HttpClient::with_tls`](crate::HttpClient::with_tls).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TlsOptions<'a> {
+    /// How the server certificate is verified
+    pub verification: ServerVerification<'a>,
+    /// Client certificate and key presented when a server asks for one
+    ///
+    /// Only the first certificate of the chain is sent, so the server has to
+    /// trust its issuer directly.
+    pub client_identity: Option<TlsIdentity<'a>>,
+    /// External pre-shared key that authenticates both ends instead of
+    /// certificates
+    ///
+    /// The key is offered in every handshake. A server that does not accept
+    /// it fails the handshake, and one that does needs no certificate.
+    pub pre_shared_key: Option<PreSharedKey<'a>>,
+    /// Cipher suite offered to the server
+    pub cipher_suite: CipherSuite,
+    /// ALPN protocol names offered to the server, most preferred first
+    ///
+    /// Leave empty to send no ALPN extension.
+    pub alpn_protocols: &'a [&'a [u8]],
+    /// Largest record the server may send, negotiated with the
+    /// max-fragment-length extension
+    ///
+    /// Lets the TLS read buffer be smaller than the 16 KB records servers
+    /// send by default. Servers that do not support the extension ignore it.
+    pub max_fragment_length: Option<MaxFragmentLength>,
+}
+
+impl<'a> TlsOptions<'a> {
+    /// Handshake configuration for a connection to `host`
+    pub(crate) fn config(&self, host: &'a str) -> TlsConfig<'a> {
+        let mut config = TlsConfig::new().with_server_name(host);
+        if let Some(psk) = self.pre_shared_key {
+            config = config.with_psk(psk.key, &[psk.identity]);
+        }
+        if !self.alpn_protocols.is_empty() {
+            config = config.with_alpn(self.alpn_protocols);
+        }
+        if let Some(length) = self.max_fragment_length {
+            config = config.with_max_fragment_length(length.into());
+        }
+        config
+    }
+}
+
+/// TLS 1.3 cipher suite used by the HTTP client
+///
+/// Exactly one suite is offered in each handshake, so it has to be one the
+/// server accepts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CipherSuite {
+    /// `TLS_AES_128_GCM_SHA256`, supported by every TLS 1.3 server
+    #[default]
+    Aes128GcmSha256,
+    /// `TLS_AES_256_GCM_SHA384`
+    Aes256GcmSha384,
+    /// `TLS_CHACHA20_POLY1305_SHA256`, faster than AES on chips without AES
+    /// acceleration
+    ChaCha20Poly1305Sha256,
+}
+
+/// Maximum TLS record payload requested from the server (RFC 6066)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaxFragmentLength {
+    /// 512 bytes
+    Bytes512,
+    /// 1024 bytes
+    Bytes1024,
+    /// 2048 bytes
+    Bytes2048,
+    /// 4096 bytes
+    Bytes4096,
+}
+
+impl From<MaxFragmentLength> for embedded_tls::MaxFragmentLength {
+    fn from(length: MaxFragmentLength) -> Self {
+        match length {
+            MaxFragmentLength::Bytes512 => Self::Bits9,
+            MaxFragmentLength::Bytes1024 => Self::Bits10,
+            MaxFragmentLength::Bytes2048 => Self::Bits11,
+            MaxFragmentLength::Bytes4096 => Self::Bits12,
+        }
+    }
+}
+
+/// `TLS_CHACHA20_POLY1305_SHA256`, which embedded-tls does not provide itself
+pub(crate) struct ChaCha20Poly1305Sha256;
+
+impl TlsCipherSuite for ChaCha20Poly1305Sha256 {
+    const CODE_POINT: u16 = 0x1303;
+    type Cipher = ChaCha20Poly1305;
+    type KeyLen = U32;
+    type IvLen = U12;
+
+    type Hash = Sha256;
+    // Longest HKDF label: hash length plus 22 bytes
+    type LabelBufferSize = U54;
+}
+
+/// Certificate chain and private key that identify one end of a TLS connection
+///
+/// Only P-256 keys are supported. The key is the 32-byte big-endian private
+/// scalar, as printed by `openssl ec -in key.pem -noout -text`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TlsIdentity<'a> {
+    /// DER-encoded certificates, starting with the end-entity certificate
+    pub certificate_chain: &'a [&'a [u8]],
+    /// Private key of the end-entity certificate
+    pub private_key: &'a [u8; 32],
+}
+
+impl core::fmt::Debug for TlsIdentity<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("TlsIdentity")
+            .field("certificate_chain", &self.certificate_chain)
+            .finish_non_exhaustive()
+    }
+}
+
+/// External pre-shared key for TLS 1.3 PSK authentication
+///
+/// The key is used with `TLS_AES_128_GCM_SHA256` and combined with an ECDHE
+/// key exchange (`psk_dhe_ke`), so past sessions stay secret if it leaks.
+/// Use a random key of at least 16 bytes; 32 bytes match the SHA-256 hash of
+/// the cipher suite.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct PreSharedKey<'a> {
+    /// Identity the server looks the key up by, such as a device ID
+    pub identity: &'a [u8],
+    /// The secret key shared with the server
+    pub key: &'a [u8],
+}
+
+impl core::fmt::Debug for PreSharedKey<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PreSharedKey")
+            .field("identity", &self.identity)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Certificate verifier backing [`ServerVerification`]
+///
+/// `CERT_SIZE` bounds the certificate chain that can be retained between the
+/// `Certificate` and `CertificateVerify` handshake messages.
+pub(crate) struct ServerVerifier<'a, CipherSuite, const CERT_SIZE: usize>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    verification: ServerVerification<'a>,
+    host: Option<String<MAX_HOSTNAME_LEN>>,
+    /// Chain verifier for the root selected from the server's chain
+    chain: Option<CertVerifier<'a, CipherSuite, NoClock, CERT_SIZE>>,
+    /// Pinned leaf key and the transcript it must sign
+    pinned: Option<(Vec<u8, P256_PUBLIC_KEY_LEN>, CipherSuite::Hash)>,
+    verified: bool,
+    failed: bool,
+}
+
+impl<'a, CipherSuite, const CERT_SIZE: usize> ServerVerifier<'a, CipherSuite, CERT_SIZE>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    pub(crate) const fn new(verification: ServerVerification<'a>) -> Self {
+        Self {
+            verification,
+            host: None,
+            chain: None,
+            pinned: None,
+            verified: matches!(verification, ServerVerification::Unverified),
+            failed: false,
+        }
+    }
+
+    /// Whether the server identity was rejected during the handshake
+    pub(crate) const fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Whether the server identity was fully established
+    pub(crate) const fn verified(&self) -> bool {
+        self.verified && !self.failed
+    }
+
+    /// Record the outcome of a verification step
+    fn check<T>(&mut self, result: Result<T, TlsError>) -> Result<T, TlsError> {
+        if let Err(e) = &result {
+            warn!("Server certificate rejected: {:?}", Error::from(*e));
+            self.failed = true;
+        }
+        result
+    }
+
+    fn select_root(&self, cert: &CertificateRef) -> Result<&'a [u8], TlsError> {
+        let ServerVerification::RootCertificates(roots) = self.verification else {
+            return Err(TlsError::InvalidCertificate);
+        };
+
+        let Some(CertificateEntryRef::X509(top)) = cert.entries.last() else {
+            return Err(TlsError::InvalidCertificate);
+        };
+        let issuer = certificate_fields(top).ok_or(TlsError::DecodeError)?.issuer;
+
+        roots
+            .iter()
+            .copied()
+            .find(|root| certificate_fields(root).is_some_and(|fields| fields.subject == issuer))
+            .ok_or(TlsError::InvalidCertificate)
+    }
+
+    fn verify_chain(
+        &mut self,
+        transcript: &CipherSuite::Hash,
+        cert: CertificateRef,
+    ) -> Result<(), TlsError> {
+        let root = self.select_root(&cert)?;
+        let mut chain = CertVerifier::new(Certificate::X509(root));
+        if let Some(host) = &self.host {
+            chain.set_hostname_verification(host)?;
+        }
+        chain.verify_certificate(transcript, cert)?;
+        self.chain = Some(chain);
+        Ok(())
+    }
+
+    fn verify_pin(
+        &mut self,
+        pins: &[[u8; 32]],
+        transcript: &CipherSuite::Hash,
+        cert: &CertificateRef,
+    ) -> Result<(), TlsError> {
+        let Some(CertificateEntryRef::X509(leaf)) = cert.entries.first() else {
+            return Err(TlsError::InvalidCertificate);
+        };
+        let spki = certificate_fields(leaf).ok_or(TlsError::DecodeError)?.spki;
+
+        let hash: [u8; 32] = Sha256::digest(spki).into();
+        if !pins.contains(&hash) {
+            return Err(TlsError::InvalidCertificate);
+        }
+
+        let key = public_key(spki).ok_or(TlsError::DecodeError)?;
+        let key = Vec::from_slice(key).map_err(|_| TlsError::InvalidCertificate)?;
+        self.pinned = Some((key, transcript.clone()));
+        Ok(())
+    }
+
+    fn verify_pinned_signature(&mut self, verify: &CertificateVerifyRef) -> Result<(), TlsError> {
+        let (key, transcript) = self.pinned.take().ok_or(TlsError::InvalidHandshake)?;
+        if verify.signature_scheme != SignatureScheme::EcdsaSecp256r1Sha256 {
+            return Err(TlsError::InvalidSignatureScheme);
+        }
+
+        let mut message: Vec<u8, MAX_VERIFY_MESSAGE_LEN> = Vec::new();
+        message
+            .resize(SERVER_VERIFY_PADDING, b' ')
+            .map_err(|_| TlsError::EncodeError)?;
+        message
+            .extend_from_slice(SERVER_VERIFY_CONTEXT)
+            .map_err(|_| TlsError::EncodeError)?;
+        message
+            .extend_from_slice(&transcript.finalize())
+            .map_err(|_| TlsError::EncodeError)?;
+
+        let key = VerifyingKey::from_sec1_bytes(&key).map_err(|_| TlsError::DecodeError)?;
+        let signature = Signature::from_der(verify.signature).map_err(|_| TlsError::DecodeError)?;
+        key.verify(&message, &signature)
+            .map_err(|_| TlsError::InvalidSignature)
+    }
+}
+
+impl<CipherSuite, const CERT_SIZE: usize> TlsVerifier<CipherSuite>
+    for ServerVerifier<'_, CipherSuite, CERT_SIZE>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    fn set_hostname_verification(&mut self, hostname: &str) -> Result<(), TlsError> {
+        self.host = Some(String::try_from(hostname).map_err(|_| TlsError::InsufficientSpace)?);
+        Ok(())
+    }
+
+    fn verify_certificate(
+        &mut self,
+        transcript: &CipherSuite::Hash,
+        cert: CertificateRef,
+    ) -> Result<(), TlsError> {
+        let result = match self.verification {
+            ServerVerification::RootCertificates(_) => self.verify_chain(transcript, cert),
+            ServerVerification::PinnedPublicKeys(pins) => self.verify_pin(pins, transcript, &cert),
+            ServerVerification::Unverified => Ok(()),
+        };
+        self.check(result)
+    }
+
+    fn verify_signature(&mut self, verify: CertificateVerifyRef) -> Result<(), TlsError> {
+        let result = match self.verification {
+            ServerVerification::RootCertificates(_) => self
+                .chain
+                .as_mut()
+                .map_or(Err(TlsError::InvalidHandshake), |chain| {
+                    chain.verify_signature(verify)
+                }),
+            ServerVerification::PinnedPublicKeys(_) => self.verify_pinned_signature(&verify),
+            ServerVerification::Unverified => return Ok(()),
+        };
+        self.check(result)?;
+        self.verified = true;
+        Ok(())
+    }
+}
+
+/// Crypto provider used for client TLS handshakes
+pub(crate) struct ClientProvider<'a, CipherSuite, Rng, const CERT_SIZE: usize>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    rng: Rng,
+    pub(crate) verifier: ServerVerifier<'a, CipherSuite, CERT_SIZE>,
+    identity: Option<TlsIdentity<'a>>,
+    /// The server sent a `CertificateRequest`
+    pub(crate) certificate_requested: bool,
+}
+
+impl<'a, CipherSuite, Rng, const CERT_SIZE: usize> ClientProvider<'a, CipherSuite, Rng, CERT_SIZE>
+where
+    CipherSuite: TlsCipherSuite,
+{
+    pub(crate) const fn new(rng: Rng, options: &TlsOptions<'a>) -> Self {
+        Self {
+            rng,
+            verifier: ServerVerifier::new(options.verification),
+            identity: options.client_identity,
+            certificate_requested: false,
+        }
+    }
+}
+
+impl<CipherSuite, Rng, const CERT_SIZE: usize> CryptoProvider
+    for ClientProvider<'_, CipherSuite, Rng, CERT_SIZE>
+where
+    CipherSuite: TlsCipherSuite,
+    Rng: CryptoRngCore,
+{
+    type CipherSuite = CipherSuite;
+    type Signature = p256::ecdsa::DerSignature;
+
+    fn rng(&mut self) -> impl CryptoRngCore {
+        &mut self.rng
+    }
+
+    fn verifier(&mut self) -> Result<&mut impl TlsVerifier<CipherSuite>, TlsError> {
+        Ok(&mut self.verifier)
+    }
+
+    fn signer(&mut self) -> Result<(impl SignerMut<Self::Signature>, SignatureScheme), TlsError> {
+        let identity = self.identity.ok_or(TlsError::InvalidPrivateKey)?;
+        let key = SigningKey::from_bytes(identity.private_key.into())
+            .map_err(|_| TlsError::InvalidPrivateKey)?;
+        Ok((key, SignatureScheme::EcdsaSecp256r1Sha256))
+    }
+
+    fn client_cert(&mut self) -> Option<Certificate<impl AsRef<[u8]>>> {
+        // Only called when the server asked for a certificate
+        self.certificate_requested = true;
+        let leaf = self.identity?.certificate_chain.first()?;
+        Some(Certificate::X509(*leaf))
+    }
+}
+
+/// Random number generator shared by all handshakes of a client
+///
+/// The `RefCell` is borrowed only for the duration of each call, never across
+/// an `.await`, so concurrent handshakes can share one generator.
+pub(crate) struct SharedRng<'r>(pub(crate) &'r RefCell<dyn CryptoRngCore>);
+
+impl RngCore for SharedRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.borrow_mut().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.borrow_mut().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.borrow_mut().fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.0.borrow_mut().try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for SharedRng<'_> {}
+
+/// `XORShift32` generator seeded from the current [`Instant`]
+///
+/// **Not cryptographically secure.** The seed is predictable, especially right
+/// after boot, so the generator does not implement [`CryptoRng`] and cannot
+/// be used for TLS on its own. Devices without a hardware random number
+/// generator can opt in with [`InsecureTimeSeededRng`].
+#[derive(Debug, Clone)]
+pub struct TimeSeededRng(u32);
+
+impl TimeSeededRng {
+    /// Create a generator seeded from the current time
+    #[must_use]
+    pub fn new() -> Self {
+        let ticks = Instant::now().as_ticks();
+        // Fold the tick counter so that the fast-changing low bits seed the
+        // generator; XORShift must never be seeded with zero
+        #[expect(clippy::cast_possible_truncation)]
+        let seed = (ticks ^ (ticks >> 32)) as u32;
+        Self(if seed == 0 { 0x9E37_79B9 } else { seed })
+    }
+}
+
+impl Default for TimeSeededRng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RngCore for TimeSeededRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let val = self.next_u32();
+            chunk.copy_from_slice(&val.to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// [`TimeSeededRng`] marked as fit for TLS, for devices without a hardware
+/// random number generator
+///
+/// **Insecure.** Handshake secrets drawn from it can be guessed by anyone who
+/// can guess the device's uptime. It exists so that such devices can still
+/// speak TLS, and can only be created with
+/// [`InsecureTimeSeededRng::new_insecure`], which makes the choice visible
+/// where the generator is set up.
+#[derive(Debug, Clone)]
+pub struct InsecureTimeSeededRng(TimeSeededRng);
+
+impl InsecureTimeSeededRng {
+    /// Opt in to TLS handshakes with a predictable, time-seeded generator
+    #[must_use]
+    pub fn new_insecure() -> Self {
+        Self(TimeSeededRng::new())
+    }
+}
+
+impl RngCore for InsecureTimeSeededRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for InsecureTimeSeededRng {}
+
+/// Raw DER fields of an X.509 certificate needed for verification
+struct CertificateFields<'c> {
+    /// Encoded issuer `Name`
+    issuer: &'c [u8],
+    /// Encoded subject `Name`
+    subject: &'c [u8],
+    /// Encoded `SubjectPublicKeyInfo`
+    spki: &'c [u8],
+}
+
+/// A DER element split off its input: tag, whole encoding, contents and remaining input
+type DerElement<'d> = (u8, &'d [u8], &'d [u8], &'d [u8]);
+
+/// Split the first DER element off `input`.
+///
+/// Returns the tag, the whole encoded element, its contents and the remaining input.
+fn der_element(input: &[u8]) -> Option<DerElement<'_>> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, rest) = rest.split_first()?;
+
+    let (len, rest) = if first < 0x80 {
+        (usize::from(first), rest)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 3 || rest.len() < count {
+            return None;
+        }
+        let (bytes, rest) = rest.split_at(count);
+        let len = bytes
+            .iter()
+            .fold(0usize, |len, &b| (len << 8) | usize::from(b));
+        (len, rest)
+    };
+
+    if rest.len() < len {
+        return None;
+    }
+    let header_len = input.len() - rest.len();
+    let (contents, rest) = rest.split_at(len);
+    Some((tag, &input[..header_len + len], contents, rest))
+}
+
+/// Locate the issuer, subject and public key of a DER-encoded certificate.
+fn certificate_fields(der: &[u8]) -> Option<CertificateFields<'_>> {
+    let (_, _, certificate, _) = der_element(der)?;
+    let (_, _, tbs, _) = der_element(certificate)?;
+
+    // Optional explicit [0] version, then the serial number
+    let (tag, _, _, rest) = der_element(tbs)?;
+    let rest = if tag == 0xa0 {
+        der_element(rest)?.3
+    } else {
+        rest
+    };
+
+    let (_, _, _, rest) = der_element(rest)?; // signature algorithm
+    let (_, issuer, _, rest) = der_element(rest)?;
+    let (_, _, _, rest) = der_element(rest)?; // validity
+    let (_, subject, _, rest) = der_element(rest)?;
+    let (_, spki, _, _) = der_element(rest)?;
+
+    Some(CertificateFields {
+        issuer,
+        subject,
+        spki,
+    })
+}
+
+/// Extract the raw public key bits from an encoded `SubjectPublicKeyInfo`.
+fn public_key(spki: &[u8]) -> Option<&[u8]> {
+    let (_, _, contents, _) = der_element(spki)?;
+    let (_, _, _, rest) = der_element(contents)?; // algorithm
+    let (_, _, bits, _) = der_element(rest)?;
+    match bits.split_first()? {
+        (0, key) => Some(key),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use embedded_tls::Aes128GcmSha256;
+
+    /// Self-signed P-256 certificate for `device.local`
+    const CERT: &[u8] = &[
+        0x30, 0x82, 0x01, 0x9e, 0x30, 0x82, 0x01, 0x44, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x14,
+        0x43, 0x52, 0x55, 0xf7, 0xaa, 0xdf, 0x75, 0x5b, 0x94, 0xac, 0x63, 0xe8, 0xf9, 0xd5, 0xa8,
+        0xeb, 0xb8, 0x57, 0x0a, 0xc4, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04,
+        0x03, 0x02, 0x30, 0x17, 0x31, 0x15, 0x30, 0x13, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0c,
+        0x64, 0x65, 0x76, 0x69, 0x63, 0x65, 0x2e, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x30, 0x20, 0x17,
+        0x0d, 0x32, 0x36, 0x31, 0x30, 0x31, 0x37, 0x30, 0x30, 0x35, 0x33, 0x34, 0x39, 0x5a, 0x18,
+        0x0f, 0x32, 0x31, 0x32, 0x36, 0x30, 0x39, 0x32, 0x33, 0x30, 0x30, 0x35, 0x33, 0x34, 0x39,
+        0x5a, 0x30, 0x17, 0x31, 0x15, 0x30, 0x13, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0c, 0x64,
+        0x65, 0x76, 0x69, 0x63, 0x65, 0x2e, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x30, 0x59, 0x30, 0x13,
+        0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+        0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0x16, 0xd3, 0x26, 0x92, 0x6d, 0xd6, 0x95,
+        0x06, 0x6a, 0x3a, 0xfd, 0x3f, 0x0f, 0x33, 0x5a, 0x35, 0xe1, 0x7e, 0xec, 0x1d, 0x61, 0xd2,
+        0x05, 0x66, 0x60, 0xa6, 0xa3, 0xcc, 0x60, 0x3c, 0x3c, 0xcd, 0x20, 0xee, 0x9d, 0x71, 0x79,
+        0x37, 0x12, 0x01, 0x3b, 0x88, 0x62, 0x35, 0xe8, 0x15, 0xa3, 0x9a, 0x64, 0xee, 0xe0, 0x6f,
+        0xf5, 0x03, 0x0b, 0x20, 0x3c, 0xc9, 0x43, 0x05, 0x83, 0x4f, 0x08, 0x1a, 0xa3, 0x6c, 0x30,
+        0x6a, 0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14, 0xd1, 0x4c, 0x0a,
+        0xbb, 0x96, 0xee, 0xeb, 0x20, 0xf1, 0x33, 0xd2, 0xff, 0x8e, 0xc8, 0x08, 0xb6, 0x1a, 0x4d,
+        0x84, 0xf0, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30, 0x16, 0x80, 0x14,
+        0xd1, 0x4c, 0x0a, 0xbb, 0x96, 0xee, 0xeb, 0x20, 0xf1, 0x33, 0xd2, 0xff, 0x8e, 0xc8, 0x08,
+        0xb6, 0x1a, 0x4d, 0x84, 0xf0, 0x30, 0x17, 0x06, 0x03, 0x55, 0x1d, 0x11, 0x04, 0x10, 0x30,
+        0x0e, 0x82, 0x0c, 0x64, 0x65, 0x76, 0x69, 0x63, 0x65, 0x2e, 0x6c, 0x6f, 0x63, 0x61, 0x6c,
+        0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff, 0x04, 0x05, 0x30, 0x03, 0x01,
+        0x01, 0xff, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03,
+        0x48, 0x00, 0x30, 0x45, 0x02, 0x20, 0x67, 0x71, 0x01, 0x68, 0x1c, 0xaa, 0x84, 0x01, 0xc2,
+        0x8c, 0x09, 0x21, 0x88, 0xec, 0x34, 0x4a, 0xeb, 0x4d, 0xff, 0x86, 0x1f, 0x65, 0x7a, 0x82,
+        0xde, 0x30, 0x1b, 0xed, 0xb7, 0xa4, 0x98, 0xa0, 0x02, 0x21, 0x00, 0x81, 0xdb, 0x44, 0x6a,
+        0x53, 0xc2, 0xa6, 0x26, 0x43, 0x85, 0x5a, 0x39, 0x89, 0xf9, 0xab, 0x43, 0xd0, 0x87, 0xca,
+        0xf4, 0x2b, 0xb6, 0x9c, 0xd4, 0xd0, 0x6c, 0x4a, 0xec, 0x23, 0x45, 0xf0, 0xaa,
+    ];
+
+    /// SHA-256 of the `SubjectPublicKeyInfo` of [`CERT`]
+    const CERT_PIN: [u8; 32] = [
+        0xa1, 0x36, 0xec, 0xea, 0xee, 0xca, 0x6d, 0x0c, 0x17, 0x51, 0x4c, 0xb5, 0x4d, 0x41, 0xb2,
+        0x71, 0x62, 0xe2, 0x10, 0x24, 0x02, 0x95, 0xa9, 0xf4, 0x7d, 0x11, 0x7a, 0xdc, 0x26, 0x24,
+        0x0c, 0x09,
+    ];
+
+    /// `CertificateVerify` signature by the key of [`CERT`] over an empty transcript
+    const SIGNATURE: &[u8] = &[
+        0x30, 0x45, 0x02, 0x21, 0x00, 0xee, 0x21, 0x38, 0x7b, 0x29, 0xb3, 0xa4, 0x3f, 0xb0, 0x9c,
+        0x78, 0x96, 0x34, 0xcc, 0x80, 0xe7, 0x5b, 0x5c, 0xb6, 0x37, 0x13, 0x8c, 0xb9, 0xd7, 0x95,
+        0x7f, 0xe3, 0x0a, 0x21, 0x62, 0xda, 0x12, 0x02, 0x20, 0x23, 0x26, 0x85, 0xb4, 0xf3, 0x2f,
+        0xe7, 0x1c, 0xca, 0xb8, 0xc8, 0xfd, 0xfd, 0x13, 0xdb, 0xb7, 0x4c, 0x9e, 0x9e, 0x8f, 0x8d,
+        0xd2, 0xc7, 0xba, 0xfc, 0x4d, 0x8b, 0x0e, 0xa9, 0x7b, 0x34, 0xc5,
+    ];
+
+    type TestVerifier<'a> = ServerVerifier<'a, Aes128GcmSha256, 1024>;
+
+    fn chain(der: &[u8]) -> CertificateRef<'_> {
+        let mut cert = CertificateRef::with_context(&[]);
+        cert.add(CertificateEntryRef::X509(der)).unwrap();
+        cert
+    }
+
+    fn verify(signature: &[u8]) -> CertificateVerifyRef<'_> {
+        CertificateVerifyRef {
+            signature_scheme: SignatureScheme::EcdsaSecp256r1Sha256,
+            signature,
+        }
+    }
+
+    #[test]
+    fn test_time_seeded_rng() {
+        let mut rng = TimeSeededRng(0x1234_5678);
+        let first = rng.next_u32();
+        assert_ne!(first, 0);
+        assert_ne!(rng.next_u32(), first);
+
+        let mut bytes = [0u8; 7];
+        rng.fill_bytes(&mut bytes);
+        assert!(bytes.iter().any(|&b| b != 0));
+
+        assert_ne!(TimeSeededRng::new().0, 0);
+    }
+
+    #[test]
+    fn test_shared_rng_delegates() {
+        let inner = RefCell::new(InsecureTimeSeededRng(TimeSeededRng(42)));
+        let mut expected = TimeSeededRng(42);
+        let mut shared = SharedRng(&inner);
+        assert_eq!(shared.next_u32(), expected.next_u32());
+        assert_eq!(shared.next_u64(), expected.next_u64());
+        // The generator is not left borrowed between calls
+        assert!(inner.try_borrow_mut().is_ok());
+    }
+
+    #[test]
+    fn test_secrets_not_debug_printed() {
+        let psk = PreSharedKey {
+            identity: b"sensor-17",
+            key: &[0xA5; 32],
+        };
+        let printed = format!("{psk:?}");
+        assert!(printed.starts_with("PreSharedKey { identity: [115, 101,"));
+        assert!(!printed.contains("165"));
+
+        let identity = TlsIdentity {
+            certificate_chain: &[],
+            private_key: &[0xA5; 32],
+        };
+        assert_eq!(
+            format!("{identity:?}"),
+            "TlsIdentity { certificate_chain: [], .. }"
+        );
+    }
+
+    #[test]
+    fn test_client_identity() {
+        let key = [0x11; 32];
+        let chain: &[&[u8]] = &[CERT];
+        let options = TlsOptions {
+            verification: ServerVerification::Unverified,
+            client_identity: Some(TlsIdentity {
+                certificate_chain: chain,
+                private_key: &key,
+            }),
+            ..TlsOptions::default()
+        };
+        let rng = RefCell::new(InsecureTimeSeededRng(TimeSeededRng(7)));
+        let mut provider =
+            ClientProvider::<Aes128GcmSha256, _, 1024>::new(SharedRng(&rng), &options);
+        assert!(!provider.certificate_requested);
+        assert!(matches!(
+            provider.client_cert(),
+            Some(Certificate::X509(leaf)) if leaf.as_ref() == CERT
+        ));
+        assert!(provider.certificate_requested);
+
+        let (mut signer, scheme) = provider.signer().unwrap();
+        assert!(matches!(scheme, SignatureScheme::EcdsaSecp256r1Sha256));
+        let signature = signer.sign(b"transcript");
+        let signature = Signature::from_der(signature.as_ref()).unwrap();
+        let public = VerifyingKey::from(&SigningKey::from_bytes(&key.into()).unwrap());
+        assert!(public.verify(b"transcript", &signature).is_ok());
+
+        // Without an identity the server gets an empty certificate message
+        let mut provider = ClientProvider::<Aes128GcmSha256, _, 1024>::new(
+            SharedRng(&rng),
+            &TlsOptions::default(),
+        );
+        assert!(provider.client_cert().is_none());
+        assert!(provider.certificate_requested);
+        assert!(provider.signer().is_err());
+    }
+
+    #[test]
+    fn test_der_element() {
+        let (tag, element, contents, rest) = der_element(&[0x02, 0x01, 0x05, 0xff]).unwrap();
+        assert_eq!(tag, 0x02);
+        assert_eq!(element, &[0x02, 0x01, 0x05]);
+        assert_eq!(contents, &[0x05]);
+        assert_eq!(rest, &[0xff]);
+
+        let (_, _, contents, rest) = der_element(&[0x04, 0x81, 0x02, 0xaa, 0xbb]).unwrap();
+        assert_eq!(contents, &[0xaa, 0xbb]);
+        assert!(rest.is_empty());
+
+        // Truncated contents and indefinite length are rejected
+        assert!(der_element(&[0x04, 0x03, 0xaa]).is_none());
+        assert!(der_element(&[0x30, 0x80, 0x00, 0x00]).is_none());
+        assert!(der_element(&[]).is_none());
+    }
+
+    #[test]
+    fn test_certificate_fields() {
+        let fields = certificate_fields(CERT).unwrap();
+        // Self-signed: issuer and subject are identical
+        assert_eq!(fields.issuer, fields.subject);
+        let hash: [u8; 32] = Sha256::digest(fields.spki).into();
+        assert_eq!(hash, CERT_PIN);
+        assert_eq!(public_key(fields.spki).unwrap().len(), P256_PUBLIC_KEY_LEN);
+
+        assert!(certificate_fields(&CERT[..100]).is_none());
+    }
+
+    #[test]
+    fn test_default_verification_rejects_everything() {
+        let mut verifier = TestVerifier::new(ServerVerification::default());
+        let result = verifier.verify_certificate(&Sha256::new(), chain(CERT));
+        assert!(result.is_err());
+        assert!(verifier.failed());
+        assert!(!verifier.verified());
+    }
+
+    #[test]
+    fn test_root_certificate_verification() {
+        let roots: &[&[u8]] = &[CERT];
+        let mut verifier = TestVerifier::new(ServerVerification::RootCertificates(roots));
+        verifier.set_hostname_verification("device.local").unwrap();
+        verifier
+            .verify_certificate(&Sha256::new(), chain(CERT))
+            .unwrap();
+        // Identity is only established once the server proves key possession
+        assert!(!verifier.failed());
+        assert!(!verifier.verified());
+    }
+
+    #[test]
+    fn test_root_certificate_hostname_mismatch() {
+        let roots: &[&[u8]] = &[CERT];
+        let mut verifier = TestVerifier::new(ServerVerification::RootCertificates(roots));
+        verifier
+            .set_hostname_verification("attacker.local")
+            .unwrap();
+        assert!(
+            verifier
+                .verify_certificate(&Sha256::new(), chain(CERT))
+                .is_err()
+        );
+        assert!(verifier.failed());
+    }
+
+    #[test]
+    fn test_pinned_public_key_verification() {
+        let pins = [CERT_PIN];
+        let mut verifier = TestVerifier::new(ServerVerification::PinnedPublicKeys(&pins));
+        verifier
+            .verify_certificate(&Sha256::new(), chain(CERT))
+            .unwrap();
+        assert!(!verifier.verified());
+        verifier.verify_signature(verify(SIGNATURE)).unwrap();
+        assert!(verifier.verified());
+    }
+
+    #[test]
+    fn test_pinned_public_key_mismatch() {
+        let pins = [[0u8; 32]];
+        let mut verifier = TestVerifier::new(ServerVerification::PinnedPublicKeys(&pins));
+        assert!(
+            verifier
+                .verify_certificate(&Sha256::new(), chain(CERT))
+                .is_err()
+        );
+        assert!(verifier.failed());
+    }
+
+    #[test]
+    fn test_pinned_public_key_bad_signature() {
+        let pins = [CERT_PIN];
+        let mut verifier = TestVerifier::new(ServerVerification::PinnedPublicKeys(&pins));
+        let mut transcript = Sha256::new();
+        transcript.update(b"tampered");
+        verifier
+            .verify_certificate(&transcript, chain(CERT))
+            .unwrap();
+        assert!(verifier.verify_signature(verify(SIGNATURE)).is_err());
+        assert!(verifier.failed());
+        assert!(!verifier.verified());
+    }
+
+    #[test]
+    fn test_unverified_accepts_anything() {
+        let mut verifier = TestVerifier::new(ServerVerification::Unverified);
+        assert!(verifier.verified());
+        verifier
+            .verify_certificate(&Sha256::new(), chain(&[]))
+            .unwrap();
+        verifier.verify_signature(verify(&[])).unwrap();
+        assert!(verifier.verified());
+    }
+}
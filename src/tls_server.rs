@@ -0,0 +1,1483 @@
+use crate::{
+    error::Error,
+    tls::{SERVER_VERIFY_CONTEXT, SERVER_VERIFY_PADDING, SharedRng, TlsIdentity},
+};
+use aes_gcm::{
+    Aes128Gcm, KeyInit, Nonce, Tag,
+    aead::{AeadInPlace, consts::U12},
+};
+use core::{cell::RefCell, cmp::Ordering};
+use embedded_io_async::{ErrorType, Read, Write};
+use embedded_tls::{
+    CryptoRngCore, TlsError,
+    alert::{AlertDescription, AlertLevel},
+};
+use heapless::Vec;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use p256::{
+    PublicKey,
+    ecdh::EphemeralSecret,
+    ecdsa::{Signature, SigningKey, signature::Signer},
+    elliptic_curve::sec1::ToEncodedPoint,
+};
+use rand_core::RngCore;
+use sha2::{Digest, Sha256};
+
+/// Record content types
+const CHANGE_CIPHER_SPEC: u8 = 20;
+const ALERT: u8 = 21;
+const HANDSHAKE: u8 = 22;
+const APPLICATION_DATA: u8 = 23;
+
+/// Handshake message types
+const CLIENT_HELLO: u8 = 1;
+const SERVER_HELLO: u8 = 2;
+const ENCRYPTED_EXTENSIONS: u8 = 8;
+const CERTIFICATE: u8 = 11;
+const CERTIFICATE_VERIFY: u8 = 15;
+const FINISHED: u8 = 20;
+const KEY_UPDATE: u8 = 24;
+const MESSAGE_HASH: u8 = 254;
+
+/// Extension types
+const MAX_FRAGMENT_LENGTH: u16 = 1;
+const SUPPORTED_GROUPS: u16 = 10;
+const SIGNATURE_ALGORITHMS: u16 = 13;
+const PADDING: u16 = 21;
+const RECORD_SIZE_LIMIT: u16 = 28;
+const PRE_SHARED_KEY: u16 = 41;
+const EARLY_DATA: u16 = 42;
+const SUPPORTED_VERSIONS: u16 = 43;
+const COOKIE: u16 = 44;
+const KEY_SHARE: u16 = 51;
+
+/// The only cipher suite, group and signature scheme offered
+const TLS_AES_128_GCM_SHA256: [u8; 2] = [0x13, 0x01];
+const SECP256R1: [u8; 2] = [0x00, 0x17];
+const ECDSA_SECP256R1_SHA256: [u8; 2] = [0x04, 0x03];
+
+const TLS_1_3: [u8; 2] = [0x03, 0x04];
+const LEGACY_VERSION: [u8; 2] = [0x03, 0x03];
+
+const RECORD_HEADER_LEN: usize = 5;
+const TAG_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+const P256_PUBLIC_KEY_LEN: usize = 65;
+/// Largest plaintext of a record (RFC 8446, section 5.1)
+const MAX_PLAINTEXT_LEN: usize = 1 << 14;
+/// Largest encrypted record body (RFC 8446, section 5.2)
+const MAX_CIPHERTEXT_LEN: usize = MAX_PLAINTEXT_LEN + 256;
+/// Smallest record size limit a peer may announce (RFC 8449)
+const MIN_RECORD_SIZE_LIMIT: usize = 64;
+
+/// End of the padding and context string in a signed `CertificateVerify`
+const CONTEXT_END: usize = SERVER_VERIFY_PADDING + SERVER_VERIFY_CONTEXT.len();
+
+/// `ServerHello.random` that marks a `HelloRetryRequest`
+const HELLO_RETRY_RANDOM: [u8; 32] = [
+    0xCF, 0x21, 0xAD, 0x74, 0xE5, 0x9A, 0x61, 0x11, 0xBE, 0x1D, 0x8C, 0x02, 0x1E, 0x65, 0xB8, 0x91,
+    0xC2, 0xA2, 0x11, 0x16, 0x7A, 0xBB, 0x8C, 0x5E, 0x07, 0x9E, 0x09, 0xE2, 0xC8, 0xA8, 0x33, 0x9C,
+];
+
+/// What the server needs to know from a `ClientHello`
+struct ClientHello {
+    session_id: Vec<u8, 32>,
+    /// The client's P-256 key share, if it sent one
+    key_share: Option<[u8; P256_PUBLIC_KEY_LEN]>,
+    /// Requested maximum fragment length code (RFC 6066)
+    max_fragment_length: Option<u8>,
+    /// Largest record the client accepts (RFC 8449)
+    record_size_limit: Option<u16>,
+    /// Hash of everything a client may not change when it answers a
+    /// `HelloRetryRequest` (RFC 8446, section 4.1.2)
+    fingerprint: [u8; HASH_LEN],
+}
+
+impl ClientHello {
+    /// Parse the body of a `ClientHello` and check that it can be served.
+    fn parse(body: &[u8]) -> Result<Self, AlertDescription> {
+        use AlertDescription::{DecodeError, HandshakeFailure, IllegalParameter, MissingExtension};
+
+        let raw = body;
+        let mut body = Reader(body);
+        body.take(2 + 32)?;
+        let session_id = Vec::from_slice(body.vector(1)?).map_err(|_| IllegalParameter)?;
+        let cipher_suites = body.vector(2)?;
+        let compression = body.vector(1)?;
+        let mut fingerprint = Sha256::new();
+        fingerprint.update(&raw[..raw.len() - body.0.len()]);
+        let mut extensions = Reader(body.vector(2)?);
+        if !body.0.is_empty() {
+            return Err(DecodeError);
+        }
+        if compression != [0] {
+            return Err(IllegalParameter);
+        }
+
+        let mut versions = false;
+        let mut groups = false;
+        let mut signatures = false;
+        let mut key_shares = None;
+        let mut max_fragment_length = None;
+        let mut record_size_limit = None;
+        while !extensions.0.is_empty() {
+            let kind = extensions.u16()?;
+            let raw_data = extensions.vector(2)?;
+            if !matches!(
+                kind,
+                KEY_SHARE | PADDING | EARLY_DATA | COOKIE | PRE_SHARED_KEY
+            ) {
+                fingerprint.update(kind.to_be_bytes());
+                fingerprint.update(length::<2>(raw_data.len()));
+                fingerprint.update(raw_data);
+            }
+            let mut data = Reader(raw_data);
+            match kind {
+                SUPPORTED_VERSIONS => versions = pairs(data.vector(1)?).any(|v| v == TLS_1_3),
+                SUPPORTED_GROUPS => groups = pairs(data.vector(2)?).any(|g| g == SECP256R1),
+                SIGNATURE_ALGORITHMS => {
+                    signatures = pairs(data.vector(2)?).any(|s| s == ECDSA_SECP256R1_SHA256);
+                }
+                KEY_SHARE => key_shares = Some(data.vector(2)?),
+                MAX_FRAGMENT_LENGTH => {
+                    max_fragment_length =
+                        Some(data.take(1)?[0]).filter(|code| (1..=4).contains(code));
+                }
+                RECORD_SIZE_LIMIT => record_size_limit = Some(data.u16()?),
+                _ => {}
+            }
+        }
+
+        if !versions {
+            return Err(AlertDescription::ProtocolVersion);
+        }
+        if !cipher_suites
+            .chunks(2)
+            .any(|suite| suite == TLS_AES_128_GCM_SHA256)
+            || !groups
+            || !signatures
+        {
+            return Err(HandshakeFailure);
+        }
+        if record_size_limit.is_some_and(|limit| usize::from(limit) < MIN_RECORD_SIZE_LIMIT) {
+            return Err(IllegalParameter);
+        }
+
+        let mut shares = Reader(key_shares.ok_or(MissingExtension)?);
+        let mut key_share = None;
+        while !shares.0.is_empty() {
+            let group = shares.take(2)?;
+            let key = shares.vector(2)?;
+            if group == SECP256R1 {
+                key_share = Some(key.try_into().map_err(|_| IllegalParameter)?);
+            }
+        }
+
+        Ok(Self {
+            session_id,
+            key_share,
+            max_fragment_length,
+            record_size_limit,
+            fingerprint: fingerprint.finalize().into(),
+        })
+    }
+}
+
+/// Reads the length-prefixed fields of a handshake message
+struct Reader<'m>(&'m [u8]);
+
+impl<'m> Reader<'m> {
+    const fn take(&mut self, len: usize) -> Result<&'m [u8], AlertDescription> {
+        if len > self.0.len() {
+            return Err(AlertDescription::DecodeError);
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u16(&mut self) -> Result<u16, AlertDescription> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    /// A vector with a `prefix`-byte length.
+    fn vector(&mut self, prefix: usize) -> Result<&'m [u8], AlertDescription> {
+        let len = self
+            .take(prefix)?
+            .iter()
+            .fold(0, |len, &b| len << 8 | usize::from(b));
+        self.take(len)
+    }
+}
+
+/// The two-byte entries of a list such as the supported groups.
+fn pairs(list: &[u8]) -> impl Iterator<Item = [u8; 2]> + '_ {
+    list.chunks_exact(2).map(|pair| [pair[0], pair[1]])
+}
+
+/// Keys protecting one direction of the connection
+struct TrafficKeys {
+    cipher: Aes128Gcm,
+    iv: [u8; 12],
+    sequence: u64,
+    /// The traffic secret, kept for key updates
+    secret: [u8; HASH_LEN],
+}
+
+impl TrafficKeys {
+    fn new(secret: [u8; HASH_LEN]) -> Result<Self, TlsError> {
+        let mut key = [0; 16];
+        let mut iv = [0; 12];
+        expand_label(&secret, b"key", &[], &mut key)?;
+        expand_label(&secret, b"iv", &[], &mut iv)?;
+        Ok(Self {
+            cipher: Aes128Gcm::new(&key.into()),
+            iv,
+            sequence: 0,
+            secret,
+        })
+    }
+
+    /// The nonce of the next record.
+    fn next_nonce(&mut self) -> Result<Nonce<U12>, TlsError> {
+        let mut nonce = self.iv;
+        for (byte, sequence) in nonce[4..].iter_mut().zip(self.sequence.to_be_bytes()) {
+            *byte ^= sequence;
+        }
+        self.sequence = self.sequence.checked_add(1).ok_or(TlsError::CryptoError)?;
+        Ok(nonce.into())
+    }
+
+    /// Move on to the next generation of keys after a `KeyUpdate`.
+    fn update(&mut self) -> Result<(), TlsError> {
+        let mut next = [0; HASH_LEN];
+        expand_label(&self.secret, b"traffic upd", &[], &mut next)?;
+        *self = Self::new(next)?;
+        Ok(())
+    }
+}
+
+/// `HKDF-Expand-Label` (RFC 8446, section 7.1)
+fn expand_label(
+    secret: &[u8; HASH_LEN],
+    label: &[u8],
+    context: &[u8],
+    output: &mut [u8],
+) -> Result<(), TlsError> {
+    let hkdf = Hkdf::<Sha256>::from_prk(secret).map_err(|_| TlsError::CryptoError)?;
+    let len = u16::try_from(output.len()).map_err(|_| TlsError::CryptoError)?;
+    let label_len =
+        u8::try_from(b"tls13 ".len() + label.len()).map_err(|_| TlsError::CryptoError)?;
+    let context_len = u8::try_from(context.len()).map_err(|_| TlsError::CryptoError)?;
+    hkdf.expand_multi_info(
+        &[
+            &len.to_be_bytes(),
+            &[label_len],
+            b"tls13 ",
+            label,
+            &[context_len],
+            context,
+        ],
+        output,
+    )
+    .map_err(|_| TlsError::CryptoError)
+}
+
+/// `Derive-Secret` (RFC 8446, section 7.1)
+fn derive_secret(
+    secret: &[u8; HASH_LEN],
+    label: &[u8],
+    transcript_hash: &[u8],
+) -> Result<[u8; HASH_LEN], TlsError> {
+    let mut derived = [0; HASH_LEN];
+    expand_label(secret, label, transcript_hash, &mut derived)?;
+    Ok(derived)
+}
+
+/// `HKDF-Extract`
+fn extract(salt: &[u8; HASH_LEN], ikm: &[u8]) -> [u8; HASH_LEN] {
+    Hkdf::<Sha256>::extract(Some(salt), ikm).0.into()
+}
+
+/// The `verify_data` of a `Finished` message sent with `secret`.
+fn finished_mac(secret: &[u8; HASH_LEN]) -> Result<Hmac<Sha256>, TlsError> {
+    let mut finished_key = [0; HASH_LEN];
+    expand_label(secret, b"finished", &[], &mut finished_key)?;
+    <Hmac<Sha256> as Mac>::new_from_slice(&finished_key).map_err(|_| TlsError::CryptoError)
+}
+
+/// Encode an ECDSA signature as the DER `SEQUENCE` of its two integers.
+fn der_signature(signature: &Signature) -> Result<Vec<u8, 72>, Error> {
+    let bytes = signature.to_bytes();
+    let mut integers = Vec::<u8, 70>::new();
+    for scalar in bytes.chunks(32) {
+        // Integers are minimal, but positive: a set high bit needs a zero before it
+        let start = scalar
+            .iter()
+            .position(|&b| b != 0)
+            .unwrap_or(scalar.len() - 1);
+        let scalar = &scalar[start..];
+        let pad: &[u8] = if scalar[0] & 0x80 == 0 { &[] } else { &[0] };
+        integers
+            .extend_from_slice(&[0x02])
+            .and_then(|()| integers.extend_from_slice(&length::<1>(pad.len() + scalar.len())))
+            .and_then(|()| integers.extend_from_slice(pad))
+            .and_then(|()| integers.extend_from_slice(scalar))
+            .map_err(|_| Error::TlsError(TlsError::EncodeError))?;
+    }
+    let mut der = Vec::new();
+    der.extend_from_slice(&[0x30])
+        .and_then(|()| der.extend_from_slice(&length::<1>(integers.len())))
+        .and_then(|()| der.extend_from_slice(&integers))
+        .map_err(|_| Error::TlsError(TlsError::EncodeError))?;
+    Ok(der)
+}
+
+/// A TLS 1.3 connection accepted by the HTTP server
+///
+/// Records are read into `read_buffer` and assembled in `write_buffer`, so
+/// those bound the size of records in either direction. Receiving a record
+/// keeps its progress in the connection, which makes reads safe to cancel
+/// between records as well as in the middle of one.
+pub struct TlsServerConnection<'b, T> {
+    transport: T,
+    read_buffer: &'b mut [u8],
+    /// Where in `read_buffer` records are received, which is after the
+    /// start of a handshake message that spans several records
+    record_start: usize,
+    /// Bytes of the record being received
+    received: usize,
+    /// Decrypted application data not yet read
+    start: usize,
+    end: usize,
+    write_buffer: &'b mut [u8],
+    /// Plaintext waiting in `write_buffer` after the record header
+    buffered: usize,
+    buffered_type: u8,
+    /// Length of a sealed record in `write_buffer` and how much of it was sent
+    sealed: usize,
+    sent: usize,
+    /// Largest plaintext the client accepts in a record
+    max_fragment: usize,
+    read_keys: Option<TrafficKeys>,
+    write_keys: Option<TrafficKeys>,
+    /// The client sent `close_notify` or the connection failed
+    closed: bool,
+}
+
+impl<'b, T: Read + Write<Error = Error>> TlsServerConnection<'b, T> {
+    /// Perform the server side of a TLS 1.3 handshake over `transport`.
+    ///
+    /// Only `TLS_AES_128_GCM_SHA256` with P-256 key exchange and an
+    /// ECDSA P-256 certificate is supported. A client that did not send a
+    /// P-256 key share is asked for one with a `HelloRetryRequest`.
+    #[expect(clippy::future_not_send)]
+    pub async fn accept(
+        transport: T,
+        read_buffer: &'b mut [u8],
+        write_buffer: &'b mut [u8],
+        identity: &TlsIdentity<'_>,
+        rng: &RefCell<dyn CryptoRngCore>,
+    ) -> Result<Self, Error> {
+        let mut connection = Self {
+            transport,
+            read_buffer,
+            record_start: 0,
+            received: 0,
+            start: 0,
+            end: 0,
+            write_buffer,
+            buffered: 0,
+            buffered_type: HANDSHAKE,
+            sealed: 0,
+            sent: 0,
+            max_fragment: MAX_PLAINTEXT_LEN,
+            read_keys: None,
+            write_keys: None,
+            closed: false,
+        };
+        connection.handshake(identity, rng).await?;
+        Ok(connection)
+    }
+
+    #[expect(clippy::future_not_send)]
+    async fn handshake(
+        &mut self,
+        identity: &TlsIdentity<'_>,
+        rng: &RefCell<dyn CryptoRngCore>,
+    ) -> Result<(), Error> {
+        let signing_key = SigningKey::from_bytes(identity.private_key.into())
+            .map_err(|_| Error::TlsError(TlsError::InvalidPrivateKey))?;
+        let mut transcript = Sha256::new();
+
+        let (hello, client_share, retried) = self.receive_client_hello(&mut transcript).await?;
+
+        let Ok(client_public) = PublicKey::from_sec1_bytes(&client_share) else {
+            return Err(self.fail(AlertDescription::IllegalParameter).await);
+        };
+        let mut random = [0; 32];
+        let secret = {
+            let mut rng = SharedRng(rng);
+            rng.fill_bytes(&mut random);
+            EphemeralSecret::random(&mut rng)
+        };
+        let shared = secret.diffie_hellman(&client_public);
+        let public = secret.public_key().to_encoded_point(false);
+        self.send_server_hello(&mut transcript, &hello, &random, Some(public.as_bytes()))
+            .await?;
+        if !retried && !hello.session_id.is_empty() {
+            // Middlebox compatibility mode (RFC 8446, appendix D.4)
+            self.write_all_data(CHANGE_CIPHER_SPEC, &[1]).await?;
+        }
+
+        // Key schedule (RFC 8446, section 7.1)
+        let empty_hash: [u8; HASH_LEN] = Sha256::digest(b"").into();
+        let early_secret = extract(&[0; HASH_LEN], &[0; HASH_LEN]);
+        let handshake_secret = extract(
+            &derive_secret(&early_secret, b"derived", &empty_hash)?,
+            shared.raw_secret_bytes(),
+        );
+        let master_secret = extract(
+            &derive_secret(&handshake_secret, b"derived", &empty_hash)?,
+            &[0; HASH_LEN],
+        );
+        let hello_hash = transcript.clone().finalize();
+        let client_secret = derive_secret(&handshake_secret, b"c hs traffic", &hello_hash)?;
+        let server_secret = derive_secret(&handshake_secret, b"s hs traffic", &hello_hash)?;
+        self.flush_records().await?;
+        self.read_keys = Some(TrafficKeys::new(client_secret)?);
+        self.write_keys = Some(TrafficKeys::new(server_secret)?);
+
+        self.send_encrypted_extensions(&mut transcript, &hello)
+            .await?;
+        self.send_certificate(&mut transcript, identity.certificate_chain)
+            .await?;
+
+        let mut signed = [b' '; CONTEXT_END + HASH_LEN];
+        signed[SERVER_VERIFY_PADDING..CONTEXT_END].copy_from_slice(SERVER_VERIFY_CONTEXT);
+        signed[CONTEXT_END..].copy_from_slice(&transcript.clone().finalize());
+        let signature = der_signature(&signing_key.sign(&signed))?;
+        self.send_handshake(
+            &mut transcript,
+            &[
+                &message_header(CERTIFICATE_VERIFY, 4 + signature.len()),
+                &ECDSA_SECP256R1_SHA256,
+                &length::<2>(signature.len()),
+                &signature,
+            ],
+        )
+        .await?;
+
+        let mut mac = finished_mac(&server_secret)?;
+        mac.update(&transcript.clone().finalize());
+        let verify_data = mac.finalize().into_bytes();
+        self.send_handshake(
+            &mut transcript,
+            &[&message_header(FINISHED, HASH_LEN), &verify_data],
+        )
+        .await?;
+        self.flush_records().await?;
+
+        let finished_hash = transcript.finalize();
+        self.write_keys = Some(TrafficKeys::new(derive_secret(
+            &master_secret,
+            b"s ap traffic",
+            &finished_hash,
+        )?)?);
+
+        let (start, end) = self.receive_handshake(FINISHED).await?;
+        let mut mac = finished_mac(&client_secret)?;
+        mac.update(&finished_hash);
+        if mac.verify_slice(&self.read_buffer[start + 4..end]).is_err() {
+            return Err(self.fail(AlertDescription::DecryptError).await);
+        }
+        self.read_keys = Some(TrafficKeys::new(derive_secret(
+            &master_secret,
+            b"c ap traffic",
+            &finished_hash,
+        )?)?);
+        Ok(())
+    }
+
+    /// Receive a `ClientHello` with a P-256 key share, sending a
+    /// `HelloRetryRequest` first if the client did not offer one
+    async fn receive_client_hello(
+        &mut self,
+        transcript: &mut Sha256,
+    ) -> Result<(ClientHello, [u8; P256_PUBLIC_KEY_LEN], bool), Error> {
+        let mut first = None;
+        loop {
+            let (start, end) = self.receive_handshake(CLIENT_HELLO).await?;
+            transcript.update(&self.read_buffer[start..end]);
+            let hello = match ClientHello::parse(&self.read_buffer[start + 4..end]) {
+                Ok(hello) => hello,
+                Err(alert) => return Err(self.fail(alert).await),
+            };
+            // The second ClientHello may only add the requested key share
+            if first.is_some_and(|first| first != hello.fingerprint) {
+                return Err(self.fail(AlertDescription::IllegalParameter).await);
+            }
+            if let Some(share) = hello.key_share {
+                return Ok((hello, share, first.is_some()));
+            }
+            if first.is_some() {
+                return Err(self.fail(AlertDescription::IllegalParameter).await);
+            }
+            first = Some(hello.fingerprint);
+
+            // The transcript continues with a hash of the first ClientHello
+            let first_hello = transcript.finalize_reset();
+            transcript.update(message_header(MESSAGE_HASH, HASH_LEN));
+            transcript.update(first_hello);
+            self.send_server_hello(transcript, &hello, &HELLO_RETRY_RANDOM, None)
+                .await?;
+            self.flush_records().await?;
+        }
+    }
+
+    /// Send a `ServerHello`, or a `HelloRetryRequest` without a `key_share`.
+    async fn send_server_hello(
+        &mut self,
+        transcript: &mut Sha256,
+        hello: &ClientHello,
+        random: &[u8; 32],
+        key_share: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let mut extensions = Vec::<u8, 80>::new();
+        let pushed = extensions
+            .extend_from_slice(&SUPPORTED_VERSIONS.to_be_bytes())
+            .and_then(|()| extensions.extend_from_slice(&[0, 2]))
+            .and_then(|()| extensions.extend_from_slice(&TLS_1_3))
+            .and_then(|()| extensions.extend_from_slice(&KEY_SHARE.to_be_bytes()));
+        let pushed = match key_share {
+            // The group the client should send a key share for
+            None => pushed
+                .and_then(|()| extensions.extend_from_slice(&[0, 2]))
+                .and_then(|()| extensions.extend_from_slice(&SECP256R1)),
+            Some(key) => pushed
+                .and_then(|()| extensions.extend_from_slice(&length::<2>(4 + key.len())))
+                .and_then(|()| extensions.extend_from_slice(&SECP256R1))
+                .and_then(|()| extensions.extend_from_slice(&length::<2>(key.len())))
+                .and_then(|()| extensions.extend_from_slice(key)),
+        };
+        pushed.map_err(|_| Error::TlsError(TlsError::EncodeError))?;
+
+        let session_id = &hello.session_id;
+        let len = 2 + 32 + 1 + session_id.len() + 2 + 1 + 2 + extensions.len();
+        self.send_handshake(
+            transcript,
+            &[
+                &message_header(SERVER_HELLO, len),
+                &LEGACY_VERSION,
+                random,
+                &length::<1>(session_id.len()),
+                session_id,
+                &TLS_AES_128_GCM_SHA256,
+                &[0],
+                &length::<2>(extensions.len()),
+                &extensions,
+            ],
+        )
+        .await
+    }
+
+    /// Send `EncryptedExtensions`, accepting the client's record size limits.
+    async fn send_encrypted_extensions(
+        &mut self,
+        transcript: &mut Sha256,
+        hello: &ClientHello,
+    ) -> Result<(), Error> {
+        let mut extensions = Vec::<u8, 6>::new();
+        let pushed = if let Some(limit) = hello.record_size_limit {
+            // A record size limit replaces the maximum fragment length
+            self.max_fragment = self.max_fragment.min(usize::from(limit) - 1);
+            let own = (self.read_buffer.len() - RECORD_HEADER_LEN - TAG_LEN)
+                .clamp(MIN_RECORD_SIZE_LIMIT, MAX_PLAINTEXT_LEN + 1);
+            extensions
+                .extend_from_slice(&RECORD_SIZE_LIMIT.to_be_bytes())
+                .and_then(|()| extensions.extend_from_slice(&[0, 2]))
+                .and_then(|()| extensions.extend_from_slice(&length::<2>(own)))
+        } else if let Some(code) = hello.max_fragment_length {
+            self.max_fragment = 1 << (8 + usize::from(code));
+            extensions
+                .extend_from_slice(&MAX_FRAGMENT_LENGTH.to_be_bytes())
+                .and_then(|()| extensions.extend_from_slice(&[0, 1, code]))
+        } else {
+            Ok(())
+        };
+        pushed.map_err(|_| Error::TlsError(TlsError::EncodeError))?;
+
+        self.send_handshake(
+            transcript,
+            &[
+                &message_header(ENCRYPTED_EXTENSIONS, 2 + extensions.len()),
+                &length::<2>(extensions.len()),
+                &extensions,
+            ],
+        )
+        .await
+    }
+
+    /// Send the `Certificate` message with `chain`.
+    async fn send_certificate(
+        &mut self,
+        transcript: &mut Sha256,
+        chain: &[&[u8]],
+    ) -> Result<(), Error> {
+        let list_len: usize = chain.iter().map(|cert| 3 + cert.len() + 2).sum();
+        self.send_handshake(
+            transcript,
+            &[
+                &message_header(CERTIFICATE, 1 + 3 + list_len),
+                &[0],
+                &length::<3>(list_len),
+            ],
+        )
+        .await?;
+        for cert in chain {
+            self.send_handshake(transcript, &[&length::<3>(cert.len()), cert, &[0, 0]])
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Write the `parts` of a handshake message and add them to `transcript`.
+    async fn send_handshake(
+        &mut self,
+        transcript: &mut Sha256,
+        parts: &[&[u8]],
+    ) -> Result<(), Error> {
+        for part in parts {
+            transcript.update(part);
+            self.write_all_data(HANDSHAKE, part).await?;
+        }
+        Ok(())
+    }
+
+    /// Receive a handshake message of type `expected`.
+    ///
+    /// Returns where the message, including its header, is in `read_buffer`.
+    /// A message may be split across several records, which are collected
+    /// at the start of `read_buffer`, but must end with the last of them,
+    /// as the client sends nothing else before the server answers.
+    async fn receive_handshake(&mut self, expected: u8) -> Result<(usize, usize), Error> {
+        let mut len = 0;
+        loop {
+            self.record_start = len;
+            let record = self.receive_record().await;
+            self.record_start = 0;
+            let Some((content_type, start, end)) = record? else {
+                return Err(Error::ConnectionClosed);
+            };
+            match content_type {
+                // Records of other types may not interleave with a fragmented message
+                CHANGE_CIPHER_SPEC if len == 0 => continue,
+                ALERT if len == 0 => return Err(self.receive_alert(start, end)),
+                HANDSHAKE if end > start => {
+                    self.read_buffer.copy_within(start..end, len);
+                    len += end - start;
+                }
+                _ => return Err(self.fail(AlertDescription::UnexpectedMessage).await),
+            }
+
+            let message = &self.read_buffer[..len];
+            if message[0] != expected {
+                return Err(self.fail(AlertDescription::UnexpectedMessage).await);
+            }
+            let Some(header) = message.get(1..4) else {
+                continue;
+            };
+            let body_len = header
+                .iter()
+                .fold(0, |body_len, &b| body_len << 8 | usize::from(b));
+            match len.cmp(&(4 + body_len)) {
+                Ordering::Less => {}
+                Ordering::Equal => return Ok((0, len)),
+                Ordering::Greater => {
+                    return Err(self.fail(AlertDescription::UnexpectedMessage).await);
+                }
+            }
+        }
+    }
+
+    /// Handle a handshake message after the handshake, which can only be a
+    /// `KeyUpdate` from a client.
+    async fn receive_post_handshake(&mut self, start: usize, end: usize) -> Result<(), Error> {
+        let [KEY_UPDATE, 0, 0, 1, request @ (0 | 1)] = self.read_buffer[start..end] else {
+            return Err(self.fail(AlertDescription::UnexpectedMessage).await);
+        };
+        if let Some(keys) = &mut self.read_keys {
+            keys.update()?;
+        }
+        if request == 1 {
+            self.write_all_data(HANDSHAKE, &[KEY_UPDATE, 0, 0, 1, 0])
+                .await?;
+            self.flush_records().await?;
+            if let Some(keys) = &mut self.write_keys {
+                keys.update()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The error for an alert the client sent.
+    fn receive_alert(&mut self, start: usize, end: usize) -> Error {
+        self.closed = true;
+        match self.read_buffer[start..end] {
+            [level, description] => Error::TlsError(TlsError::HandshakeAborted(
+                AlertLevel::of(level).unwrap_or(AlertLevel::Fatal),
+                AlertDescription::of(description).unwrap_or(AlertDescription::DecodeError),
+            )),
+            _ => Error::TlsError(TlsError::InvalidRecord),
+        }
+    }
+
+    /// Receive the next record and decrypt it.
+    ///
+    /// Returns the content type and where the plaintext is in `read_buffer`,
+    /// or `None` if the client closed the connection between records.
+    async fn receive_record(&mut self) -> Result<Option<(u8, usize, usize)>, Error> {
+        let base = self.record_start;
+        let len = loop {
+            let len = (self.received >= RECORD_HEADER_LEN).then(|| {
+                usize::from(u16::from_be_bytes([
+                    self.read_buffer[base + 3],
+                    self.read_buffer[base + 4],
+                ]))
+            });
+            let needed = match len {
+                None => RECORD_HEADER_LEN,
+                Some(len) if len > MAX_CIPHERTEXT_LEN => {
+                    return Err(self.fail(AlertDescription::RecordOverflow).await);
+                }
+                Some(len) if base + RECORD_HEADER_LEN + len > self.read_buffer.len() => {
+                    warn!(
+                        "TLS record of {} bytes does not fit into the read buffer",
+                        len
+                    );
+                    return Err(self.fail(AlertDescription::RecordOverflow).await);
+                }
+                Some(len) => RECORD_HEADER_LEN + len,
+            };
+            if let Some(len) = len
+                && self.received == needed
+            {
+                break len;
+            }
+
+            let n = self
+                .transport
+                .read(&mut self.read_buffer[base + self.received..base + needed])
+                .await?;
+            if n == 0 {
+                self.closed = true;
+                if self.received == 0 {
+                    return Ok(None);
+                }
+                return Err(Error::ConnectionClosed);
+            }
+            self.received += n;
+        };
+        self.received = 0;
+
+        let content_type = self.read_buffer[base];
+        let start = base + RECORD_HEADER_LEN;
+        let end = start + len;
+        let Some(keys) = &mut self.read_keys else {
+            if len > MAX_PLAINTEXT_LEN {
+                return Err(self.fail(AlertDescription::RecordOverflow).await);
+            }
+            return Ok(Some((content_type, start, end)));
+        };
+
+        match content_type {
+            // Middlebox compatibility records stay unencrypted
+            CHANGE_CIPHER_SPEC if self.read_buffer[start..end] == [1] => {
+                Ok(Some((content_type, start, end)))
+            }
+            APPLICATION_DATA if len > TAG_LEN => {
+                let (header, body) = self.read_buffer[base..].split_at_mut(RECORD_HEADER_LEN);
+                let (ciphertext, tag) = body[..len].split_at_mut(len - TAG_LEN);
+                let nonce = keys.next_nonce()?;
+                let decrypted = keys.cipher.decrypt_in_place_detached(
+                    &nonce,
+                    header,
+                    ciphertext,
+                    Tag::from_slice(tag),
+                );
+                if decrypted.is_err() {
+                    return Err(self.fail(AlertDescription::BadRecordMac).await);
+                }
+                // The content type follows the data and precedes the padding
+                match ciphertext.iter().rposition(|&b| b != 0) {
+                    Some(type_at) if type_at <= MAX_PLAINTEXT_LEN => {
+                        Ok(Some((ciphertext[type_at], start, start + type_at)))
+                    }
+                    Some(_) => Err(self.fail(AlertDescription::RecordOverflow).await),
+                    None => Err(self.fail(AlertDescription::UnexpectedMessage).await),
+                }
+            }
+            _ => Err(self.fail(AlertDescription::UnexpectedMessage).await),
+        }
+    }
+
+    /// Buffer as much of `data` as fits into the current record.
+    async fn write_data(&mut self, content_type: u8, data: &[u8]) -> Result<usize, Error> {
+        let capacity = self
+            .write_buffer
+            .len()
+            .saturating_sub(RECORD_HEADER_LEN + 1 + TAG_LEN)
+            .min(self.max_fragment);
+        if capacity == 0 {
+            return Err(Error::BufferOverflow);
+        }
+
+        self.send_sealed().await?;
+        if self.buffered > 0 && (self.buffered_type != content_type || self.buffered == capacity) {
+            self.seal()?;
+            self.send_sealed().await?;
+        }
+        self.buffered_type = content_type;
+
+        let n = data.len().min(capacity - self.buffered);
+        let start = RECORD_HEADER_LEN + self.buffered;
+        self.write_buffer[start..start + n].copy_from_slice(&data[..n]);
+        self.buffered += n;
+        Ok(n)
+    }
+
+    async fn write_all_data(&mut self, content_type: u8, mut data: &[u8]) -> Result<(), Error> {
+        while !data.is_empty() {
+            let n = self.write_data(content_type, data).await?;
+            data = &data[n..];
+        }
+        Ok(())
+    }
+
+    /// Turn the buffered plaintext into a record, encrypting it once keys
+    /// are in place.
+    fn seal(&mut self) -> Result<(), Error> {
+        let len = self.buffered;
+        let (content_type, record_len) = if self.write_keys.is_some() {
+            self.write_buffer[RECORD_HEADER_LEN + len] = self.buffered_type;
+            (APPLICATION_DATA, len + 1 + TAG_LEN)
+        } else {
+            (self.buffered_type, len)
+        };
+        let record_len_bytes = u16::try_from(record_len)
+            .map_err(|_| Error::BufferOverflow)?
+            .to_be_bytes();
+        self.write_buffer[..RECORD_HEADER_LEN].copy_from_slice(&[
+            content_type,
+            LEGACY_VERSION[0],
+            LEGACY_VERSION[1],
+            record_len_bytes[0],
+            record_len_bytes[1],
+        ]);
+
+        if let Some(keys) = &mut self.write_keys {
+            let (header, body) = self.write_buffer.split_at_mut(RECORD_HEADER_LEN);
+            let nonce = keys.next_nonce()?;
+            let tag = keys
+                .cipher
+                .encrypt_in_place_detached(&nonce, header, &mut body[..=len])
+                .map_err(|_| Error::TlsError(TlsError::CryptoError))?;
+            body[len + 1..len + 1 + TAG_LEN].copy_from_slice(&tag);
+        }
+
+        self.buffered = 0;
+        self.sealed = RECORD_HEADER_LEN + record_len;
+        self.sent = 0;
+        Ok(())
+    }
+
+    /// Finish sending a sealed record.
+    async fn send_sealed(&mut self) -> Result<(), Error> {
+        while self.sent < self.sealed {
+            let n = self
+                .transport
+                .write(&self.write_buffer[self.sent..self.sealed])
+                .await?;
+            if n == 0 {
+                return Err(Error::ConnectionClosed);
+            }
+            self.sent += n;
+        }
+        self.sealed = 0;
+        self.sent = 0;
+        Ok(())
+    }
+
+    /// Send everything buffered and flush the transport.
+    async fn flush_records(&mut self) -> Result<(), Error> {
+        self.send_sealed().await?;
+        if self.buffered > 0 {
+            self.seal()?;
+            self.send_sealed().await?;
+        }
+        self.transport.flush().await
+    }
+
+    /// Send a fatal alert and return the matching error.
+    async fn fail(&mut self, description: AlertDescription) -> Error {
+        self.closed = true;
+        if let Err(e) = self.send_alert(AlertLevel::Fatal, description).await {
+            debug!("Failed to send TLS alert: {:?}", e);
+        }
+        Error::TlsError(TlsError::AbortHandshake(AlertLevel::Fatal, description))
+    }
+
+    async fn send_alert(
+        &mut self,
+        level: AlertLevel,
+        description: AlertDescription,
+    ) -> Result<(), Error> {
+        self.write_all_data(ALERT, &[level as u8, description as u8])
+            .await?;
+        self.flush_records().await
+    }
+
+    /// Send `close_notify` and give up the connection.
+    pub async fn close(mut self) {
+        if let Err(e) = self
+            .send_alert(AlertLevel::Warning, AlertDescription::CloseNotify)
+            .await
+        {
+            debug!("Failed to send TLS close_notify: {:?}", e);
+        }
+    }
+}
+
+/// The type and 24-bit length that start a handshake message.
+fn message_header(message_type: u8, len: usize) -> [u8; 4] {
+    let [a, b, c] = length::<3>(len);
+    [message_type, a, b, c]
+}
+
+/// The `N`-byte length prefix of a field, whose size is bounded by its
+/// construction.
+fn length<const N: usize>(len: usize) -> [u8; N] {
+    let bytes = u64::try_from(len).unwrap_or(u64::MAX).to_be_bytes();
+    core::array::from_fn(|i| bytes[bytes.len() - N + i])
+}
+
+impl<T> ErrorType for TlsServerConnection<'_, T> {
+    type Error = Error;
+}
+
+impl<T: Read + Write<Error = Error>> Read for TlsServerConnection<'_, T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        loop {
+            if self.start < self.end {
+                let n = buf.len().min(self.end - self.start);
+                buf[..n].copy_from_slice(&self.read_buffer[self.start..self.start + n]);
+                self.start += n;
+                return Ok(n);
+            }
+            if self.closed || buf.is_empty() {
+                return Ok(0);
+            }
+
+            let Some((content_type, start, end)) = self.receive_record().await? else {
+                return Ok(0);
+            };
+            match content_type {
+                APPLICATION_DATA => {
+                    self.start = start;
+                    self.end = end;
+                }
+                ALERT
+                    if self.read_buffer[start..end] == [1, AlertDescription::CloseNotify as u8] =>
+                {
+                    self.closed = true;
+                    return Ok(0);
+                }
+                ALERT => return Err(self.receive_alert(start, end)),
+                HANDSHAKE => self.receive_post_handshake(start, end).await?,
+                _ => return Err(self.fail(AlertDescription::UnexpectedMessage).await),
+            }
+        }
+    }
+}
+
+impl<T: Read + Write<Error = Error>> Write for TlsServerConnection<'_, T> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.write_data(APPLICATION_DATA, buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.flush_records().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Loopback, MockPeer, Step};
+    use crate::tls::{
+        ClientProvider, InsecureTimeSeededRng, MaxFragmentLength, ServerVerification, TlsOptions,
+    };
+    use embassy_futures::join::join;
+    use embedded_tls::{Aes128GcmSha256, TlsConfig, TlsConnection, TlsContext};
+    use futures_lite::future::block_on;
+
+    /// Self-signed P-256 certificate for `device.local`
+    const CERT: &[u8] = &[
+        0x30, 0x82, 0x01, 0x9e, 0x30, 0x82, 0x01, 0x44, 0xa0, 0x03, 0x02, 0x01, 0x02, 0x02, 0x14,
+        0x3b, 0xcd, 0x5e, 0x46, 0x44, 0xcf, 0xc9, 0xd5, 0xda, 0xec, 0x20, 0x2c, 0xeb, 0xcf, 0x7b,
+        0x6e, 0x0d, 0x1a, 0x2e, 0x9c, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04,
+        0x03, 0x02, 0x30, 0x17, 0x31, 0x15, 0x30, 0x13, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0c,
+        0x64, 0x65, 0x76, 0x69, 0x63, 0x65, 0x2e, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x30, 0x20, 0x17,
+        0x0d, 0x32, 0x36, 0x31, 0x30, 0x31, 0x37, 0x30, 0x31, 0x35, 0x34, 0x30, 0x34, 0x5a, 0x18,
+        0x0f, 0x32, 0x31, 0x32, 0x36, 0x30, 0x39, 0x32, 0x33, 0x30, 0x31, 0x35, 0x34, 0x30, 0x34,
+        0x5a, 0x30, 0x17, 0x31, 0x15, 0x30, 0x13, 0x06, 0x03, 0x55, 0x04, 0x03, 0x0c, 0x0c, 0x64,
+        0x65, 0x76, 0x69, 0x63, 0x65, 0x2e, 0x6c, 0x6f, 0x63, 0x61, 0x6c, 0x30, 0x59, 0x30, 0x13,
+        0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce,
+        0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00, 0x04, 0xfa, 0xae, 0x8a, 0xa7, 0x7b, 0x26, 0xc3,
+        0x62, 0x64, 0x8e, 0x0f, 0xb6, 0xdb, 0x55, 0x30, 0x09, 0xb2, 0x91, 0x55, 0xed, 0x8f, 0xa5,
+        0x6d, 0xdd, 0xca, 0xbf, 0x4b, 0x34, 0x0d, 0x85, 0x6f, 0xff, 0x8c, 0xae, 0xc7, 0xa7, 0x54,
+        0xf8, 0xf3, 0x8d, 0x72, 0xdb, 0x15, 0x89, 0x2f, 0xbf, 0xcb, 0x4a, 0x99, 0xff, 0x60, 0x71,
+        0x71, 0x10, 0x33, 0x34, 0x24, 0xd3, 0xbb, 0x1b, 0x39, 0x99, 0xde, 0x24, 0xa3, 0x6c, 0x30,
+        0x6a, 0x30, 0x1d, 0x06, 0x03, 0x55, 0x1d, 0x0e, 0x04, 0x16, 0x04, 0x14, 0x46, 0xdf, 0x49,
+        0xc2, 0x24, 0x88, 0x2c, 0x06, 0xb9, 0xf1, 0x68, 0x1e, 0x13, 0xf4, 0x34, 0xd1, 0x89, 0x9e,
+        0xf1, 0x11, 0x30, 0x1f, 0x06, 0x03, 0x55, 0x1d, 0x23, 0x04, 0x18, 0x30, 0x16, 0x80, 0x14,
+        0x46, 0xdf, 0x49, 0xc2, 0x24, 0x88, 0x2c, 0x06, 0xb9, 0xf1, 0x68, 0x1e, 0x13, 0xf4, 0x34,
+        0xd1, 0x89, 0x9e, 0xf1, 0x11, 0x30, 0x0f, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x01, 0x01, 0xff,
+        0x04, 0x05, 0x30, 0x03, 0x01, 0x01, 0xff, 0x30, 0x17, 0x06, 0x03, 0x55, 0x1d, 0x11, 0x04,
+        0x10, 0x30, 0x0e, 0x82, 0x0c, 0x64, 0x65, 0x76, 0x69, 0x63, 0x65, 0x2e, 0x6c, 0x6f, 0x63,
+        0x61, 0x6c, 0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02, 0x03,
+        0x48, 0x00, 0x30, 0x45, 0x02, 0x20, 0x25, 0xe8, 0xb0, 0x4b, 0xb1, 0x1e, 0x99, 0x7a, 0x73,
+        0x1a, 0x36, 0x30, 0xd2, 0xa2, 0xb2, 0x26, 0xb8, 0xe9, 0x09, 0xa9, 0xb0, 0x51, 0x22, 0x64,
+        0x78, 0xd1, 0xcf, 0x85, 0x81, 0x52, 0x26, 0x08, 0x02, 0x21, 0x00, 0xd8, 0x9e, 0x7b, 0x5e,
+        0x11, 0x96, 0x75, 0xcf, 0xdc, 0xd4, 0x48, 0xae, 0xca, 0x50, 0xdc, 0xed, 0x83, 0x69, 0x5f,
+        0x5c, 0x80, 0x54, 0x26, 0xda, 0x75, 0x3c, 0x0e, 0xec, 0x16, 0x84, 0xaf, 0xb8,
+    ];
+
+    /// Private key of [`CERT`]
+    const KEY: &[u8; 32] = &[
+        0x8d, 0x17, 0xc7, 0x55, 0x1c, 0x00, 0xcb, 0xba, 0xc4, 0x8d, 0xc9, 0x23, 0xca, 0x10, 0x82,
+        0x79, 0xd3, 0x2c, 0x67, 0x2e, 0x0c, 0x90, 0x38, 0xb6, 0x95, 0x69, 0x34, 0x7b, 0x85, 0x78,
+        0x1a, 0x99,
+    ];
+
+    fn identity() -> TlsIdentity<'static> {
+        TlsIdentity {
+            certificate_chain: &[CERT],
+            private_key: KEY,
+        }
+    }
+
+    /// A `ClientHello` record offering only an X25519 key share
+    fn hello_without_p256_share() -> std::vec::Vec<u8> {
+        client_hello(0x11, [0x00, 0x1d], &[0x42; 32])
+    }
+
+    /// A `ClientHello` record with `random` repeated as its random and one
+    /// key share for `group`
+    fn client_hello(random: u8, group: [u8; 2], key: &[u8]) -> std::vec::Vec<u8> {
+        let extensions: &[&[u8]] = &[
+            &[0x00, 0x2b, 0x00, 0x03, 0x02, 0x03, 0x04],
+            &[0x00, 0x0a, 0x00, 0x06, 0x00, 0x04, 0x00, 0x1d, 0x00, 0x17],
+            &[0x00, 0x0d, 0x00, 0x04, 0x00, 0x02, 0x04, 0x03],
+            &KEY_SHARE.to_be_bytes(),
+            &length::<2>(2 + 4 + key.len()),
+            &length::<2>(4 + key.len()),
+            &group,
+            &length::<2>(key.len()),
+            key,
+        ];
+        let extensions = extensions.concat();
+        let mut body = std::vec![0x03, 0x03];
+        body.extend_from_slice(&[random; 32]);
+        body.extend_from_slice(&[0x00, 0x00, 0x02, 0x13, 0x01, 0x01, 0x00]);
+        body.extend_from_slice(&length::<2>(extensions.len()));
+        body.extend_from_slice(&extensions);
+
+        let mut record = std::vec![HANDSHAKE, 0x03, 0x01];
+        record.extend_from_slice(&length::<2>(body.len() + 4));
+        record.extend_from_slice(&message_header(CLIENT_HELLO, body.len()));
+        record.extend_from_slice(&body);
+        record
+    }
+
+    #[test]
+    fn test_handshake_and_data() {
+        let rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+        let loopback: Loopback = Loopback::new();
+
+        let server = async {
+            let mut read_buffer = [0; 4096];
+            let mut write_buffer = [0; 4096];
+            let mut connection = TlsServerConnection::accept(
+                loopback.server(),
+                &mut read_buffer,
+                &mut write_buffer,
+                &identity(),
+                &rng,
+            )
+            .await
+            .unwrap();
+
+            let mut buf = [0; 16];
+            let n = connection.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"ping");
+            connection.write_all(b"pong").await.unwrap();
+            connection.flush().await.unwrap();
+            // close_notify ends the stream
+            assert_eq!(connection.read(&mut buf).await.unwrap(), 0);
+            connection.close().await;
+        };
+
+        let client = async {
+            let client_rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+            let mut read_buffer = [0; 4096];
+            let mut write_buffer = [0; 4096];
+            let mut tls =
+                TlsConnection::new(loopback.client(), &mut read_buffer, &mut write_buffer);
+            let roots: &[&[u8]] = &[CERT];
+            let options = TlsOptions {
+                verification: ServerVerification::RootCertificates(roots),
+                client_identity: None,
+                ..TlsOptions::default()
+            };
+            let mut provider =
+                ClientProvider::<Aes128GcmSha256, _, 4096>::new(SharedRng(&client_rng), &options);
+            let config = TlsConfig::new().with_server_name("device.local");
+            tls.open(TlsContext::new(&config, &mut provider))
+                .await
+                .unwrap();
+            assert!(provider.verifier.verified());
+
+            tls.write_all(b"ping").await.unwrap();
+            tls.flush().await.unwrap();
+            let mut buf = [0; 16];
+            let n = tls.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"pong");
+            tls.close().await.map_err(|(_, e)| e).unwrap();
+        };
+
+        block_on(join(server, client));
+    }
+
+    #[test]
+    fn test_client_options_limit_records() {
+        let rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+        let loopback: Loopback = Loopback::new();
+        let message = [0x5A; 3000];
+
+        let server = async {
+            let mut read_buffer = [0; 4096];
+            let mut write_buffer = [0; 4096];
+            let mut connection = TlsServerConnection::accept(
+                loopback.server(),
+                &mut read_buffer,
+                &mut write_buffer,
+                &identity(),
+                &rng,
+            )
+            .await
+            .unwrap();
+            assert_eq!(connection.max_fragment, 1024);
+
+            connection.write_all(&message).await.unwrap();
+            connection.flush().await.unwrap();
+            let mut buf = [0; 16];
+            assert_eq!(connection.read(&mut buf).await.unwrap(), 0);
+        };
+
+        let client = async {
+            let client_rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+            // Just enough for a 1024-byte record
+            let mut read_buffer = [0; 1024 + RECORD_HEADER_LEN + TAG_LEN + 1];
+            let mut write_buffer = [0; 1024];
+            let mut tls =
+                TlsConnection::new(loopback.client(), &mut read_buffer, &mut write_buffer);
+            let roots: &[&[u8]] = &[CERT];
+            let options = TlsOptions {
+                verification: ServerVerification::RootCertificates(roots),
+                alpn_protocols: &[b"http/1.1"],
+                max_fragment_length: Some(MaxFragmentLength::Bytes1024),
+                ..TlsOptions::default()
+            };
+            let mut provider =
+                ClientProvider::<Aes128GcmSha256, _, 1024>::new(SharedRng(&client_rng), &options);
+            let config = options.config("device.local");
+            tls.open(TlsContext::new(&config, &mut provider))
+                .await
+                .unwrap();
+
+            let mut received = std::vec::Vec::new();
+            while received.len() < message.len() {
+                let mut buf = [0; 512];
+                let n = tls.read(&mut buf).await.unwrap();
+                received.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(received, message);
+            tls.close().await.map_err(|(_, e)| e).unwrap();
+        };
+
+        block_on(join(server, client));
+    }
+
+    #[test]
+    fn test_hello_retry_request() {
+        let hello = hello_without_p256_share();
+        let script = [Step::Send(&hello)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+        let mut read_buffer = [0; 1024];
+        let mut write_buffer = [0; 1024];
+        let result = block_on(TlsServerConnection::accept(
+            &mut connection,
+            &mut read_buffer,
+            &mut write_buffer,
+            &identity(),
+            &rng,
+        ));
+        // The client hangs up instead of sending a second ClientHello
+        assert!(result.is_err());
+
+        let output = peer.written();
+        assert_eq!(output[0], HANDSHAKE);
+        assert_eq!(output[5], SERVER_HELLO);
+        assert_eq!(&output[11..43], &HELLO_RETRY_RANDOM);
+    }
+
+    #[test]
+    fn test_hello_retry_request_mismatch() {
+        // The second ClientHello has a different random
+        let mut input = hello_without_p256_share();
+        input.extend(client_hello(0x22, SECP256R1, &[0x04; P256_PUBLIC_KEY_LEN]));
+        let script = [Step::Send(&input)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+        let mut read_buffer = [0; 1024];
+        let mut write_buffer = [0; 1024];
+        let result = block_on(TlsServerConnection::accept(
+            &mut connection,
+            &mut read_buffer,
+            &mut write_buffer,
+            &identity(),
+            &rng,
+        ));
+        assert!(matches!(
+            result,
+            Err(Error::TlsError(TlsError::AbortHandshake(
+                AlertLevel::Fatal,
+                AlertDescription::IllegalParameter
+            )))
+        ));
+        assert!(
+            peer.written()
+                .ends_with(&[ALERT, 0x03, 0x03, 0x00, 0x02, 2, 47])
+        );
+    }
+
+    #[test]
+    fn test_fragmented_client_hello() {
+        // The ClientHello is split across three records
+        let hello = hello_without_p256_share();
+        let message = &hello[RECORD_HEADER_LEN..];
+        let mut input = std::vec::Vec::new();
+        for fragment in [&message[..2], &message[2..50], &message[50..]] {
+            input.extend_from_slice(&[HANDSHAKE, 0x03, 0x01]);
+            input.extend_from_slice(&length::<2>(fragment.len()));
+            input.extend_from_slice(fragment);
+        }
+        let script = [Step::Send(&input)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+        let mut read_buffer = [0; 1024];
+        let mut write_buffer = [0; 1024];
+        let result = block_on(TlsServerConnection::accept(
+            &mut connection,
+            &mut read_buffer,
+            &mut write_buffer,
+            &identity(),
+            &rng,
+        ));
+        assert!(result.is_err());
+        assert_eq!(peer.written()[5], SERVER_HELLO);
+        assert_eq!(&peer.written()[11..43], &HELLO_RETRY_RANDOM);
+
+        // A record of another type may not interrupt the message
+        let mut input = std::vec![HANDSHAKE, 0x03, 0x01, 0x00, 0x02];
+        input.extend_from_slice(&message[..2]);
+        input.extend_from_slice(&[CHANGE_CIPHER_SPEC, 0x03, 0x03, 0x00, 0x01, 0x01]);
+        let script = [Step::Send(&input)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let result = block_on(TlsServerConnection::accept(
+            &mut connection,
+            &mut read_buffer,
+            &mut write_buffer,
+            &identity(),
+            &rng,
+        ));
+        assert!(matches!(
+            result,
+            Err(Error::TlsError(TlsError::AbortHandshake(
+                AlertLevel::Fatal,
+                AlertDescription::UnexpectedMessage
+            )))
+        ));
+    }
+
+    /// Accepts exactly [`CERT`] and checks signatures with `ring`
+    #[derive(Debug)]
+    struct PinnedCertificate(std::sync::Arc<rustls::crypto::CryptoProvider>);
+
+    impl rustls::client::danger::ServerCertVerifier for PinnedCertificate {
+        fn verify_server_cert(
+            &self,
+            end_entity: &rustls::pki_types::CertificateDer<'_>,
+            _intermediates: &[rustls::pki_types::CertificateDer<'_>],
+            _server_name: &rustls::pki_types::ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: rustls::pki_types::UnixTime,
+        ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
+            if end_entity.as_ref() == CERT {
+                Ok(rustls::client::danger::ServerCertVerified::assertion())
+            } else {
+                Err(rustls::Error::General("unexpected certificate".into()))
+            }
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            _message: &[u8],
+            _cert: &rustls::pki_types::CertificateDer<'_>,
+            _dss: &rustls::DigitallySignedStruct,
+        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+            Err(rustls::Error::General("TLS 1.2 is not offered".into()))
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &rustls::pki_types::CertificateDer<'_>,
+            dss: &rustls::DigitallySignedStruct,
+        ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
+            rustls::crypto::verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> std::vec::Vec<rustls::SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+
+    #[test]
+    fn test_rustls_client() {
+        use std::io::{Read as _, Write as _};
+
+        let rng = RefCell::new(InsecureTimeSeededRng::new_insecure());
+        let loopback: Loopback = Loopback::new();
+
+        let server = async {
+            let mut read_buffer = [0; 4096];
+            let mut write_buffer = [0; 4096];
+            let mut connection = TlsServerConnection::accept(
+                loopback.server(),
+                &mut read_buffer,
+                &mut write_buffer,
+                &identity(),
+                &rng,
+            )
+            .await
+            .unwrap();
+
+            let mut buf = [0; 16];
+            let n = connection.read(&mut buf).await.unwrap();
+            assert_eq!(&buf[..n], b"ping");
+            connection.write_all(b"pong").await.unwrap();
+            connection.flush().await.unwrap();
+            assert_eq!(connection.read(&mut buf).await.unwrap(), 0);
+        };
+
+        // rustls sends an X25519 key share first, so this also goes through
+        // a HelloRetryRequest
+        let client = async {
+            let mut socket = loopback.client();
+            let provider = std::sync::Arc::new(rustls::crypto::ring::default_provider());
+            let config = rustls::ClientConfig::builder_with_provider(provider.clone())
+                .with_protocol_versions(&[&rustls::version::TLS13])
+                .unwrap()
+                .dangerous()
+                .with_custom_certificate_verifier(std::sync::Arc::new(PinnedCertificate(provider)))
+                .with_no_client_auth();
+            let name = "device.local".try_into().unwrap();
+            let mut tls = rustls::ClientConnection::new(std::sync::Arc::new(config), name).unwrap();
+            tls.writer().write_all(b"ping").unwrap();
+
+            let mut received = std::vec::Vec::new();
+            let mut buf = [0; 4096];
+            while received != b"pong" {
+                while tls.wants_write() {
+                    let mut records = std::vec::Vec::new();
+                    tls.write_tls(&mut records).unwrap();
+                    socket.write_all(&records).await.unwrap();
+                }
+                let n = socket.read(&mut buf).await.unwrap();
+                tls.read_tls(&mut &buf[..n]).unwrap();
+                tls.process_new_packets().unwrap();
+                if let Ok(n) = tls.reader().read(&mut buf) {
+                    received.extend_from_slice(&buf[..n]);
+                }
+            }
+            assert!(tls.negotiated_cipher_suite().is_some());
+
+            tls.send_close_notify();
+            let mut records = std::vec::Vec::new();
+            tls.write_tls(&mut records).unwrap();
+            socket.write_all(&records).await.unwrap();
+        };
+
+        block_on(join(server, client));
+    }
+
+    #[test]
+    fn test_client_hello_rejected() {
+        let hello = hello_without_p256_share();
+        let body = &hello[RECORD_HEADER_LEN + 4..];
+        let parsed = ClientHello::parse(body).unwrap();
+        assert!(parsed.key_share.is_none());
+        assert!(parsed.session_id.is_empty());
+
+        // Truncated messages fail to decode
+        assert!(matches!(
+            ClientHello::parse(&body[..40]),
+            Err(AlertDescription::DecodeError)
+        ));
+
+        // A client without TLS_AES_128_GCM_SHA256 cannot be served
+        let mut other_suite = body.to_vec();
+        other_suite[38] = 0x03;
+        assert!(matches!(
+            ClientHello::parse(&other_suite),
+            Err(AlertDescription::HandshakeFailure)
+        ));
+    }
+
+    #[test]
+    fn test_der_signature() {
+        let key = SigningKey::from_bytes(KEY.into()).unwrap();
+        let signature: Signature = key.sign(b"message");
+        let der = der_signature(&signature).unwrap();
+        assert_eq!(der[0], 0x30);
+        assert_eq!(usize::from(der[1]), der.len() - 2);
+        assert_eq!(Signature::from_der(&der).unwrap(), signature);
+    }
+}
@@ -0,0 +1,542 @@
+use crate::error::Error;
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    dns::{self, DnsSocket},
+    tcp::TcpSocket,
+};
+use embassy_time::Duration;
+use embedded_io_async::{ErrorType, Read, Write};
+
+/// A byte stream to a server, as opened by a [`Connector`]
+///
+/// HTTP requests and TLS records are written to and read from the transport
+/// through [`embedded_io_async`]. Once a request is done the client hands the
+/// transport back through [`Transport::close`].
+#[allow(async_fn_in_trait)]
+pub trait Transport: Read + Write<Error = Error> {
+    /// Close the connection gracefully, flushing data still in flight
+    async fn close(self);
+
+    /// Replace the connection with a new one to the same server
+    ///
+    /// An [`HttpSession`](crate::HttpSession) calls this when the server has
+    /// closed its keep-alive connection, so the session can go on with the
+    /// buffers already lent to the transport. Transports that cannot
+    /// reconnect keep this default, which fails with
+    /// [`Error::ConnectionClosed`].
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        Err(Error::ConnectionClosed)
+    }
+}
+
+/// Opens [`Transport`] connections for an [`HttpClient`](crate::HttpClient)
+///
+/// All request logic is generic over the connector, so the client can run over
+/// anything that moves bytes: a cellular modem's sockets, a serial bridge, a
+/// host TCP stack or an in-memory pipe in tests. [`TcpConnector`] over an
+/// embassy-net stack is the default.
+///
+/// # Examples
+///
+/// ```no_run
+/// use embassy_time::Duration;
+/// use nanofish::{Connector, Error, HttpClient, HttpClientOptions};
+/// # use nanofish::Transport;
+/// # struct Modem;
+/// # impl Modem {
+/// #     async fn open_tcp(&self, _: &str, _: u16, _: Duration) -> Result<ModemSocket<'_>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct ModemSocket<'m>(&'m Modem);
+/// # impl embedded_io_async::ErrorType for ModemSocket<'_> {
+/// #     type Error = Error;
+/// # }
+/// # impl embedded_io_async::Read for ModemSocket<'_> {
+/// #     async fn read(&mut self, _: &mut [u8]) -> Result<usize, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl embedded_io_async::Write for ModemSocket<'_> {
+/// #     async fn write(&mut self, _: &[u8]) -> Result<usize, Error> {
+/// #         todo!()
+/// #     }
+/// #     async fn flush(&mut self) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl Transport for ModemSocket<'_> {
+/// #     async fn close(self) {}
+/// # }
+///
+/// struct ModemConnector<'m> {
+///     modem: &'m Modem,
+/// }
+///
+/// impl Connector for ModemConnector<'_> {
+///     type Transport<'b> = ModemSocket<'b> where Self: 'b;
+///
+///     async fn connect<'b>(
+///         &'b self,
+///         host: &str,
+///         port: u16,
+///         timeout: Duration,
+///         _rx_buffer: &'b mut [u8],
+///         _tx_buffer: &'b mut [u8],
+///     ) -> Result<ModemSocket<'b>, Error> {
+///         // ModemSocket implements Transport with the AT socket commands
+///         self.modem.open_tcp(host, port, timeout).await
+///     }
+/// }
+///
+/// async fn example(modem: &Modem) -> Result<(), Error> {
+///     let client: HttpClient<'_, 1024, 1024, 4096, 4096, 1024, _> =
+///         HttpClient::with_connector(ModemConnector { modem }, HttpClientOptions::default());
+///     let mut buffer = [0u8; 4096];
+///     client.get("http://example.com", &[], &mut buffer).await?;
+///     Ok(())
+/// }
+/// ```
+#[allow(async_fn_in_trait)]
+pub trait Connector {
+    /// The connection type this connector opens
+    type Transport<'b>: Transport
+    where
+        Self: 'b;
+
+    /// Open a connection to `host` on `port`
+    ///
+    /// `host` is a domain name or an IP address literal, without brackets.
+    /// `timeout` is [`HttpClientOptions::socket_timeout`](crate::HttpClientOptions::socket_timeout)
+    /// and should bound connecting as well as later reads and writes. The
+    /// receive and transmit buffers are sized by the client's `TCP_RX` and
+    /// `TCP_TX` parameters and may be used by the transport for as long as it
+    /// lives.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the host cannot be resolved or reached.
+    async fn connect<'b>(
+        &'b self,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+        rx_buffer: &'b mut [u8],
+        tx_buffer: &'b mut [u8],
+    ) -> Result<Self::Transport<'b>, Error>;
+}
+
+impl<C: Connector + ?Sized> Connector for &C {
+    type Transport<'b>
+        = C::Transport<'b>
+    where
+        Self: 'b;
+
+    #[expect(clippy::future_not_send)]
+    async fn connect<'b>(
+        &'b self,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+        rx_buffer: &'b mut [u8],
+        tx_buffer: &'b mut [u8],
+    ) -> Result<C::Transport<'b>, Error> {
+        (**self)
+            .connect(host, port, timeout, rx_buffer, tx_buffer)
+            .await
+    }
+}
+
+/// Accepts [`Transport`] connections for an [`HttpServer`](crate::HttpServer)
+///
+/// The server's accept loop is generic over the acceptor, so requests can be
+/// served over any byte stream: USB CDC-NCM, a serial link or a loopback pipe
+/// in tests. An embassy-net [`Stack`] accepts TCP connections and is what
+/// [`HttpServer::serve`](crate::HttpServer::serve) is usually given.
+///
+/// # Examples
+///
+/// ```no_run
+/// use embassy_time::Duration;
+/// use nanofish::{Acceptor, DefaultHttpServer, Error, SimpleHandler};
+/// # use nanofish::Transport;
+/// # struct SerialLink;
+/// # impl SerialLink {
+/// #     async fn wait_for_dtr(&self) {}
+/// # }
+/// # struct SerialSession<'l> {
+/// #     link: &'l SerialLink,
+/// # }
+/// # impl embedded_io_async::ErrorType for SerialSession<'_> {
+/// #     type Error = Error;
+/// # }
+/// # impl embedded_io_async::Read for SerialSession<'_> {
+/// #     async fn read(&mut self, _: &mut [u8]) -> Result<usize, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl embedded_io_async::Write for SerialSession<'_> {
+/// #     async fn write(&mut self, _: &[u8]) -> Result<usize, Error> {
+/// #         todo!()
+/// #     }
+/// #     async fn flush(&mut self) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl Transport for SerialSession<'_> {
+/// #     async fn close(self) {}
+/// # }
+///
+/// impl Acceptor for SerialLink {
+///     type Transport<'b> = SerialSession<'b>;
+///
+///     async fn accept<'b>(
+///         &'b self,
+///         _port: u16,
+///         _timeout: Duration,
+///         _rx_buffer: &'b mut [u8],
+///         _tx_buffer: &'b mut [u8],
+///     ) -> Result<SerialSession<'b>, Error> {
+///         // A session starts when the host raises DTR
+///         self.wait_for_dtr().await;
+///         Ok(SerialSession { link: self })
+///     }
+/// }
+///
+/// async fn example(serial_link: &SerialLink) -> ! {
+///     let mut server = DefaultHttpServer::new(80);
+///     server.serve(serial_link, SimpleHandler).await
+/// }
+/// ```
+#[allow(async_fn_in_trait)]
+pub trait Acceptor {
+    /// The connection type this acceptor produces
+    type Transport<'b>: Transport
+    where
+        Self: 'b;
+
+    /// Wait for the next incoming connection on `port`
+    ///
+    /// `timeout` is [`ServerTimeouts::accept_timeout`](crate::ServerTimeouts::accept_timeout)
+    /// and bounds how long the connection may take to be established. The
+    /// receive and transmit buffers are the server's `RX_SIZE` and `TX_SIZE`
+    /// buffers and may be used by the transport for as long as it lives.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no connection could be accepted; the server logs
+    /// it and tries again shortly after.
+    async fn accept<'b>(
+        &'b self,
+        port: u16,
+        timeout: Duration,
+        rx_buffer: &'b mut [u8],
+        tx_buffer: &'b mut [u8],
+    ) -> Result<Self::Transport<'b>, Error>;
+}
+
+impl<A: Acceptor + ?Sized> Acceptor for &A {
+    type Transport<'b>
+        = A::Transport<'b>
+    where
+        Self: 'b;
+
+    #[expect(clippy::future_not_send)]
+    async fn accept<'b>(
+        &'b self,
+        port: u16,
+        timeout: Duration,
+        rx_buffer: &'b mut [u8],
+        tx_buffer: &'b mut [u8],
+    ) -> Result<A::Transport<'b>, Error> {
+        (**self).accept(port, timeout, rx_buffer, tx_buffer).await
+    }
+}
+
+impl Acceptor for Stack<'_> {
+    type Transport<'b>
+        = TcpTransport<'b>
+    where
+        Self: 'b;
+
+    #[expect(clippy::future_not_send)]
+    async fn accept<'b>(
+        &'b self,
+        port: u16,
+        timeout: Duration,
+        rx_buffer: &'b mut [u8],
+        tx_buffer: &'b mut [u8],
+    ) -> Result<TcpTransport<'b>, Error> {
+        let mut socket = TcpSocket::new(*self, rx_buffer, tx_buffer);
+        socket.set_timeout(Some(timeout));
+        socket.accept(port).await?;
+
+        // Reset socket timeout after accept so it doesn't race with read timeout
+        socket.set_timeout(None);
+        Ok(TcpTransport {
+            socket,
+            remote: None,
+        })
+    }
+}
+
+/// The default [`Connector`], opening TCP sockets on an embassy-net stack
+///
+/// Host names are resolved with the stack's DNS client.
+#[derive(Clone, Copy)]
+pub struct TcpConnector<'a> {
+    stack: &'a Stack<'a>,
+}
+
+impl<'a> TcpConnector<'a> {
+    /// Create a connector for `stack`
+    #[must_use]
+    pub const fn new(stack: &'a Stack<'a>) -> Self {
+        Self { stack }
+    }
+
+    /// Resolve a hostname to an IP address, trying IPv4 (A) first then IPv6 (AAAA).
+    #[expect(clippy::future_not_send)]
+    async fn resolve_host(&self, host: &str) -> Result<IpAddress, Error> {
+        let dns_socket = DnsSocket::new(*self.stack);
+
+        // Try A (IPv4) first — most common on embedded networks
+        if let Ok(addrs) = dns_socket.query(host, dns::DnsQueryType::A).await
+            && let Some(&addr) = addrs.first()
+        {
+            return Ok(addr);
+        }
+
+        // Fall back to AAAA (IPv6)
+        let addrs = dns_socket.query(host, dns::DnsQueryType::Aaaa).await?;
+        addrs.first().copied().ok_or(Error::IpAddressEmpty)
+    }
+}
+
+impl Connector for TcpConnector<'_> {
+    type Transport<'b>
+        = TcpTransport<'b>
+    where
+        Self: 'b;
+
+    #[expect(clippy::future_not_send)]
+    async fn connect<'b>(
+        &'b self,
+        host: &str,
+        port: u16,
+        timeout: Duration,
+        rx_buffer: &'b mut [u8],
+        tx_buffer: &'b mut [u8],
+    ) -> Result<TcpTransport<'b>, Error> {
+        let mut socket = TcpSocket::new(*self.stack, rx_buffer, tx_buffer);
+        socket.set_timeout(Some(timeout));
+
+        let remote = IpEndpoint::new(self.resolve_host(host).await?, port);
+        socket
+            .connect(remote)
+            .await
+            .map_err(|e: embassy_net::tcp::ConnectError| {
+                socket.abort();
+                Error::from(e)
+            })?;
+
+        Ok(TcpTransport {
+            socket,
+            remote: Some(remote),
+        })
+    }
+}
+
+/// A connected embassy-net TCP socket, opened by [`TcpConnector`] or
+/// accepted on a [`Stack`]
+///
+/// A socket opened by [`TcpConnector`] can [`reconnect`](Transport::reconnect)
+/// to the address it was opened to, without resolving the host again.
+pub struct TcpTransport<'b> {
+    socket: TcpSocket<'b>,
+    /// The address connected to, `None` for accepted sockets
+    remote: Option<IpEndpoint>,
+}
+
+impl ErrorType for TcpTransport<'_> {
+    type Error = Error;
+}
+
+impl Read for TcpTransport<'_> {
+    #[expect(clippy::future_not_send)]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        Ok(self.socket.read(buf).await?)
+    }
+}
+
+impl Write for TcpTransport<'_> {
+    #[expect(clippy::future_not_send)]
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        Ok(self.socket.write(buf).await?)
+    }
+
+    #[expect(clippy::future_not_send)]
+    async fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.socket.flush().await?)
+    }
+}
+
+impl Transport for TcpTransport<'_> {
+    #[expect(clippy::future_not_send)]
+    async fn close(mut self) {
+        self.socket.close();
+        let _ = self.socket.flush().await;
+    }
+
+    #[expect(clippy::future_not_send)]
+    async fn reconnect(&mut self) -> Result<(), Error> {
+        let remote = self.remote.ok_or(Error::ConnectionClosed)?;
+        // The old connection is beyond use, so it is aborted rather than closed
+        self.socket.abort();
+        let _ = self.socket.flush().await;
+        self.socket
+            .connect(remote)
+            .await
+            .map_err(|e: embassy_net::tcp::ConnectError| {
+                self.socket.abort();
+                Error::from(e)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HttpClient, HttpClientOptions, HttpMethod, ResponseBody,
+        testing::{MockPeer, MockTransport, Step},
+    };
+    use core::cell::RefCell;
+    use embassy_futures::poll_once;
+
+    /// Connector recording the arguments of each connection to a peer
+    struct Recorder<'p> {
+        peer: &'p MockPeer<'p>,
+        /// Host, port and buffer sizes of each connection
+        connections: RefCell<std::vec::Vec<(std::string::String, u16, usize, usize)>>,
+    }
+
+    impl Connector for Recorder<'_> {
+        type Transport<'b>
+            = MockTransport<'b, 'b, 4096>
+        where
+            Self: 'b;
+
+        #[expect(clippy::future_not_send)]
+        async fn connect<'b>(
+            &'b self,
+            host: &str,
+            port: u16,
+            timeout: Duration,
+            rx_buffer: &'b mut [u8],
+            tx_buffer: &'b mut [u8],
+        ) -> Result<MockTransport<'b, 'b, 4096>, Error> {
+            self.connections.borrow_mut().push((
+                host.into(),
+                port,
+                rx_buffer.len(),
+                tx_buffer.len(),
+            ));
+            self.peer
+                .connect(host, port, timeout, rx_buffer, tx_buffer)
+                .await
+        }
+    }
+
+    fn options() -> HttpClientOptions {
+        HttpClientOptions {
+            socket_close_delay: Duration::from_ticks(0),
+            ..HttpClientOptions::default()
+        }
+    }
+
+    #[test]
+    fn test_request_over_custom_connector() {
+        // Handed out in small pieces, like a network would
+        let script = [Step::Send(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nContent-Type: text/plain\r\n\r\nhello",
+        )];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(7);
+        let recorder = Recorder {
+            peer: &peer,
+            connections: RefCell::default(),
+        };
+        let client =
+            HttpClient::<'_, 256, 128, 64, 64, 512, _>::with_connector(recorder, options());
+
+        let mut buffer = [0u8; 256];
+        let (response, _) = futures_lite::future::block_on(client.request(
+            HttpMethod::POST,
+            "http://sensor.local:8080/readings?unit=c",
+            &[],
+            Some(b"21.5"),
+            &mut buffer,
+        ))
+        .unwrap();
+        assert!(response.is_success());
+        assert_eq!(response.body, ResponseBody::Text("hello"));
+
+        assert_eq!(
+            *client.connector.connections.borrow(),
+            [("sensor.local".into(), 8080, 256, 128)]
+        );
+        let sent = std::string::String::from_utf8(peer.written().to_vec()).unwrap();
+        assert!(sent.starts_with("POST /readings?unit=c HTTP/1.1\r\nHost: sensor.local:8080\r\n"));
+        assert!(sent.contains("Content-Length: 4\r\n"));
+        assert!(sent.ends_with("\r\n\r\n21.5"));
+        assert!(poll_once(peer.closed()).is_ready());
+    }
+
+    #[test]
+    fn test_streaming_over_custom_connector() {
+        let script = [Step::Send(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nfirm\r\n4\r\nware\r\n0\r\n\r\n",
+        )];
+        let peer: MockPeer = MockPeer::new(&script).with_segment_size(7);
+        let client = HttpClient::<'_, 64, 64, 64, 64, 512, _>::with_connector(&peer, options());
+
+        let mut buffers = crate::SessionBuffers::new();
+        let mut header_buffer = [0u8; 128];
+        futures_lite::future::block_on(async {
+            let mut response = client
+                .request_streaming(
+                    HttpMethod::GET,
+                    "http://updates.local/image.bin",
+                    &[],
+                    None,
+                    &mut buffers,
+                    &mut header_buffer,
+                )
+                .await
+                .unwrap();
+            let mut body = [0u8; 16];
+            let mut len = 0;
+            loop {
+                let n = response.body.read(&mut body[len..]).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                len += n;
+            }
+            assert_eq!(&body[..len], b"firmware");
+            Box::pin(response.body.close()).await;
+        });
+        assert!(poll_once(peer.closed()).is_ready());
+    }
+
+    #[test]
+    #[cfg(not(feature = "tls"))]
+    fn test_https_without_tls_feature_is_rejected() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let client = HttpClient::<'_, 64, 64, 64, 64, 512, _>::with_connector(&peer, options());
+        let mut buffer = [0u8; 64];
+        let result =
+            futures_lite::future::block_on(client.get("https://example.com", &[], &mut buffer));
+        assert!(matches!(result, Err(Error::UnsupportedScheme(_))));
+    }
+}
@@ -0,0 +1,1273 @@
+use crate::{
+    client::HttpClient,
+    connection::Connection,
+    error::Error,
+    header::{
+        HttpHeader,
+        headers::{
+            CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION, UPGRADE,
+        },
+        mime_types,
+    },
+    method::HttpMethod,
+    protocol::{self, CONNECTION_UPGRADE_END, DEFAULT_HTTP_PORT, DEFAULT_HTTPS_PORT, MAX_HEADERS},
+    request::HttpRequest,
+    response::{HttpResponse, ResponseBody},
+    session::SessionBuffers,
+    status_code::StatusCode,
+    transport::{Connector, TcpTransport, Transport},
+    url::Url,
+    writer::ResponseWriter,
+};
+use embedded_io_async::{ErrorType, Read, ReadExactError, Write};
+use heapless::{String, Vec};
+use sha1::{Digest, Sha1};
+
+/// Largest payload of a control frame (close, ping or pong)
+pub const MAX_CONTROL_PAYLOAD: usize = 125;
+
+/// The WebSocket protocol version implemented here
+const VERSION: &str = "13";
+
+/// Appended to the client's key to derive `Sec-WebSocket-Accept`
+const ACCEPT_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// `Upgrade` header value of a WebSocket handshake
+const WEBSOCKET: &str = "websocket";
+
+/// Close status codes (RFC 6455, section 7.4.1)
+pub mod close_codes {
+    /// The purpose of the connection has been fulfilled
+    pub const NORMAL: u16 = 1000;
+    /// The endpoint is going away, such as a server shutting down
+    pub const GOING_AWAY: u16 = 1001;
+    /// The peer violated the protocol
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    /// The endpoint cannot accept this type of data
+    pub const UNSUPPORTED_DATA: u16 = 1003;
+    /// A message contained data inconsistent with its type, such as invalid UTF-8 text
+    pub const INVALID_PAYLOAD: u16 = 1007;
+    /// A message violated the endpoint's policy
+    pub const POLICY_VIOLATION: u16 = 1008;
+    /// A message was too big to process
+    pub const MESSAGE_TOO_BIG: u16 = 1009;
+    /// The server hit an unexpected condition
+    pub const INTERNAL_ERROR: u16 = 1011;
+}
+
+/// WebSocket frame opcodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpCode {
+    /// Continues a fragmented message
+    Continuation = 0x0,
+    /// First frame of a text message
+    Text = 0x1,
+    /// First frame of a binary message
+    Binary = 0x2,
+    /// Closes the connection
+    Close = 0x8,
+    /// Asks the peer for a pong
+    Ping = 0x9,
+    /// Answers a ping
+    Pong = 0xA,
+}
+
+impl OpCode {
+    const fn from_u8(value: u8) -> Option<Self> {
+        Some(match value {
+            0x0 => Self::Continuation,
+            0x1 => Self::Text,
+            0x2 => Self::Binary,
+            0x8 => Self::Close,
+            0x9 => Self::Ping,
+            0xA => Self::Pong,
+            _ => return None,
+        })
+    }
+
+    /// Control frames may appear between the frames of a fragmented message.
+    const fn is_control(self) -> bool {
+        self as u8 & 0x8 != 0
+    }
+}
+
+/// Status code and reason of a close frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloseFrame<'m> {
+    /// Close status code, see [`close_codes`]
+    pub code: u16,
+    /// Reason for closing, meant for humans
+    pub reason: &'m str,
+}
+
+/// A message received on a [`WebSocket`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message<'m> {
+    /// A complete text message
+    Text(&'m str),
+    /// A complete binary message
+    Binary(&'m [u8]),
+    /// The answer to a ping sent with [`WebSocket::send_ping`]
+    Pong(&'m [u8]),
+    /// The peer closed the connection
+    Close(Option<CloseFrame<'m>>),
+}
+
+/// Which end of the connection a [`WebSocket`] is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Role {
+    Server,
+    /// Clients mask their frames with keys derived from `secret`, which is
+    /// drawn from the client's random number generator, and a frame counter
+    Client {
+        secret: [u8; 16],
+        frames: u64,
+    },
+}
+
+/// Header of a received frame
+struct FrameHeader {
+    fin: bool,
+    opcode: OpCode,
+    len: usize,
+    mask: Option<[u8; 4]>,
+}
+
+/// A WebSocket connection
+///
+/// Created by [`ResponseWriter::upgrade_websocket`] on the server and by
+/// [`HttpClient::connect_websocket`] on the client. Messages are read
+/// whole into a caller-provided buffer with [`WebSocket::read_message`],
+/// which reassembles fragmented messages, answers pings and echoes the
+/// peer's close frame. Outgoing messages are sent as single frames, or in
+/// fragments with [`WebSocket::send_frame`].
+pub struct WebSocket<C> {
+    connection: C,
+    role: Role,
+    close_sent: bool,
+    /// No further frames are read once the peer closed or broke the protocol
+    close_received: bool,
+    control: [u8; MAX_CONTROL_PAYLOAD],
+}
+
+impl<C: Read + Write<Error = Error>> WebSocket<C> {
+    pub(crate) const fn new(connection: C, role: Role) -> Self {
+        Self {
+            connection,
+            role,
+            close_sent: false,
+            close_received: false,
+            control: [0; MAX_CONTROL_PAYLOAD],
+        }
+    }
+
+    /// Give up the WebSocket and return the underlying connection
+    pub fn into_inner(self) -> C {
+        self.connection
+    }
+
+    /// Read the next message into `buffer`
+    ///
+    /// Pings are answered with a pong before reading on. A pong that arrives
+    /// between the frames of a fragmented message is dropped. Once a close
+    /// frame arrives it is echoed, unless [`WebSocket::close`] was called
+    /// before, and returned as [`Message::Close`]; after that the connection
+    /// is done and should be dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferOverflow` if the message does not fit into
+    /// `buffer`, `Error::WebSocket` if the peer violates the protocol,
+    /// `Error::ConnectionClosed` if the connection ends or was closed before,
+    /// or the error from reading the connection. Overflows and protocol
+    /// errors send a close frame with the matching status code.
+    pub async fn read_message<'m>(
+        &'m mut self,
+        buffer: &'m mut [u8],
+    ) -> Result<Message<'m>, Error> {
+        if self.close_received {
+            return Err(Error::ConnectionClosed);
+        }
+
+        let mut kind = None;
+        let mut len = 0usize;
+        loop {
+            let header = self.read_header().await?;
+
+            if header.opcode.is_control() {
+                let payload = &mut self.control[..header.len];
+                self.connection
+                    .read_exact(payload)
+                    .await
+                    .map_err(read_error)?;
+                apply_mask(payload, header.mask);
+                match header.opcode {
+                    OpCode::Ping if !self.close_sent => {
+                        let mut pong = [0; MAX_CONTROL_PAYLOAD];
+                        pong[..header.len].copy_from_slice(&self.control[..header.len]);
+                        self.send_frame(OpCode::Pong, &pong[..header.len], true)
+                            .await?;
+                    }
+                    OpCode::Pong if kind.is_none() => {
+                        return Ok(Message::Pong(&self.control[..header.len]));
+                    }
+                    OpCode::Close => return self.receive_close(header.len).await,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match (header.opcode, kind) {
+                (OpCode::Continuation, Some(_)) => {}
+                (OpCode::Continuation, None) => {
+                    return Err(self
+                        .fail(close_codes::PROTOCOL_ERROR, "Unexpected continuation frame")
+                        .await);
+                }
+                (opcode, None) => kind = Some(opcode),
+                (_, Some(_)) => {
+                    return Err(self
+                        .fail(close_codes::PROTOCOL_ERROR, "Expected a continuation frame")
+                        .await);
+                }
+            }
+
+            let Some(end) = len
+                .checked_add(header.len)
+                .filter(|&end| end <= buffer.len())
+            else {
+                self.fail(close_codes::MESSAGE_TOO_BIG, "Message too big")
+                    .await;
+                return Err(Error::BufferOverflow);
+            };
+            let payload = &mut buffer[len..end];
+            self.connection
+                .read_exact(payload)
+                .await
+                .map_err(read_error)?;
+            apply_mask(payload, header.mask);
+            len = end;
+            if header.fin {
+                break;
+            }
+        }
+
+        let data: &'m [u8] = &buffer[..len];
+        if kind == Some(OpCode::Text) {
+            match core::str::from_utf8(data) {
+                Ok(text) => Ok(Message::Text(text)),
+                Err(_) => Err(self
+                    .fail(close_codes::INVALID_PAYLOAD, "Text message is not UTF-8")
+                    .await),
+            }
+        } else {
+            Ok(Message::Binary(data))
+        }
+    }
+
+    /// Send a text message
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ConnectionClosed` if a close frame was already sent,
+    /// or the error from writing to the connection.
+    pub async fn send_text(&mut self, text: &str) -> Result<(), Error> {
+        self.send_frame(OpCode::Text, text.as_bytes(), true).await
+    }
+
+    /// Send a binary message
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::ConnectionClosed` if a close frame was already sent,
+    /// or the error from writing to the connection.
+    pub async fn send_binary(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.send_frame(OpCode::Binary, data, true).await
+    }
+
+    /// Send a ping, which the peer answers with a [`Message::Pong`]
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferOverflow` if `data` is longer than
+    /// [`MAX_CONTROL_PAYLOAD`], `Error::ConnectionClosed` if a close frame
+    /// was already sent, or the error from writing to the connection.
+    pub async fn send_ping(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.send_frame(OpCode::Ping, data, true).await
+    }
+
+    /// Start closing the connection
+    ///
+    /// Sends a close frame with `code` and `reason`. Keep reading until the
+    /// peer's [`Message::Close`] arrives, then drop the connection.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferOverflow` if `reason` is longer than 123 bytes,
+    /// `Error::ConnectionClosed` if a close frame was already sent, or the
+    /// error from writing to the connection.
+    pub async fn close(&mut self, code: u16, reason: &str) -> Result<(), Error> {
+        let mut payload = Vec::<u8, MAX_CONTROL_PAYLOAD>::new();
+        payload
+            .extend_from_slice(&code.to_be_bytes())
+            .and_then(|()| payload.extend_from_slice(reason.as_bytes()))
+            .map_err(|_| Error::BufferOverflow)?;
+        self.send_frame(OpCode::Close, &payload, true).await
+    }
+
+    /// Send a single frame
+    ///
+    /// Send a fragmented message as a `Text` or `Binary` frame with `fin`
+    /// unset, followed by `Continuation` frames, the last of which sets
+    /// `fin`. Control frames may be sent in between.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::BufferOverflow` for a control frame with more than
+    /// [`MAX_CONTROL_PAYLOAD`] bytes, `Error::WebSocket` for a fragmented
+    /// control frame, `Error::ConnectionClosed` if a close frame was already
+    /// sent, or the error from writing to the connection.
+    pub async fn send_frame(
+        &mut self,
+        opcode: OpCode,
+        payload: &[u8],
+        fin: bool,
+    ) -> Result<(), Error> {
+        if self.close_sent {
+            return Err(Error::ConnectionClosed);
+        }
+        if opcode.is_control() {
+            if payload.len() > MAX_CONTROL_PAYLOAD {
+                return Err(Error::BufferOverflow);
+            }
+            if !fin {
+                return Err(Error::WebSocket("Control frames cannot be fragmented"));
+            }
+        }
+        if opcode == OpCode::Close {
+            self.close_sent = true;
+        }
+
+        let mask = self.next_mask();
+        let mut header = Vec::<u8, 14>::new();
+        let first = if fin { 0x80 } else { 0 } | opcode as u8;
+        let masked = if mask.is_some() { 0x80 } else { 0 };
+        let pushed = if let Ok(len @ 0..=125) = u8::try_from(payload.len()) {
+            header.extend_from_slice(&[first, masked | len])
+        } else if let Ok(len) = u16::try_from(payload.len()) {
+            header
+                .extend_from_slice(&[first, masked | 0x7E])
+                .and_then(|()| header.extend_from_slice(&len.to_be_bytes()))
+        } else {
+            let len = u64::try_from(payload.len()).map_err(|_| Error::BufferOverflow)?;
+            header
+                .extend_from_slice(&[first, masked | 0x7F])
+                .and_then(|()| header.extend_from_slice(&len.to_be_bytes()))
+        };
+        pushed
+            .and_then(|()| header.extend_from_slice(mask.as_ref().map_or(&[][..], |m| &m[..])))
+            .map_err(|_| Error::BufferOverflow)?;
+
+        self.connection.write_all(&header).await?;
+        if mask.is_some() {
+            // Mask through a small buffer; its length keeps pieces aligned
+            // to the four byte key
+            let mut masked = [0; 64];
+            for piece in payload.chunks(masked.len()) {
+                let masked = &mut masked[..piece.len()];
+                masked.copy_from_slice(piece);
+                apply_mask(masked, mask);
+                self.connection.write_all(masked).await?;
+            }
+        } else {
+            self.connection.write_all(payload).await?;
+        }
+        self.connection.flush().await
+    }
+
+    /// The masking key for the next frame sent, if this end masks its frames.
+    fn next_mask(&mut self) -> Option<[u8; 4]> {
+        let Role::Client { secret, frames } = &mut self.role else {
+            return None;
+        };
+        let mut sha1 = Sha1::new();
+        sha1.update(*secret);
+        sha1.update(frames.to_be_bytes());
+        *frames = frames.wrapping_add(1);
+        let digest = sha1.finalize();
+        Some([digest[0], digest[1], digest[2], digest[3]])
+    }
+
+    async fn read_header(&mut self) -> Result<FrameHeader, Error> {
+        let mut head = [0; 2];
+        self.connection
+            .read_exact(&mut head)
+            .await
+            .map_err(read_error)?;
+
+        let fin = head[0] & 0x80 != 0;
+        if head[0] & 0x70 != 0 {
+            return Err(self
+                .fail(close_codes::PROTOCOL_ERROR, "Reserved frame bits set")
+                .await);
+        }
+        let Some(opcode) = OpCode::from_u8(head[0] & 0x0F) else {
+            return Err(self
+                .fail(close_codes::PROTOCOL_ERROR, "Unknown opcode")
+                .await);
+        };
+        // Clients mask every frame, servers none
+        let masked = head[1] & 0x80 != 0;
+        if masked != (self.role == Role::Server) {
+            return Err(self
+                .fail(close_codes::PROTOCOL_ERROR, "Unexpected frame masking")
+                .await);
+        }
+
+        let len = match head[1] & 0x7F {
+            126 => {
+                let mut len = [0; 2];
+                self.connection
+                    .read_exact(&mut len)
+                    .await
+                    .map_err(read_error)?;
+                u64::from(u16::from_be_bytes(len))
+            }
+            127 => {
+                let mut len = [0; 8];
+                self.connection
+                    .read_exact(&mut len)
+                    .await
+                    .map_err(read_error)?;
+                u64::from_be_bytes(len)
+            }
+            len => u64::from(len),
+        };
+        // Too big for any buffer, which the caller reports
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+        if opcode.is_control() && (!fin || len > MAX_CONTROL_PAYLOAD) {
+            return Err(self
+                .fail(close_codes::PROTOCOL_ERROR, "Invalid control frame")
+                .await);
+        }
+
+        let mask = if masked {
+            let mut key = [0; 4];
+            self.connection
+                .read_exact(&mut key)
+                .await
+                .map_err(read_error)?;
+            Some(key)
+        } else {
+            None
+        };
+
+        Ok(FrameHeader {
+            fin,
+            opcode,
+            len,
+            mask,
+        })
+    }
+
+    /// Handle a close frame whose `len` payload bytes are in `self.control`.
+    async fn receive_close(&mut self, len: usize) -> Result<Message<'_>, Error> {
+        let code = match len {
+            0 => None,
+            1 => {
+                return Err(self
+                    .fail(close_codes::PROTOCOL_ERROR, "Invalid close frame")
+                    .await);
+            }
+            _ => Some(u16::from_be_bytes([self.control[0], self.control[1]])),
+        };
+        if code.is_some_and(|code| !is_valid_close_code(code)) {
+            return Err(self
+                .fail(close_codes::PROTOCOL_ERROR, "Invalid close code")
+                .await);
+        }
+        if core::str::from_utf8(&self.control[len.min(2)..len]).is_err() {
+            return Err(self
+                .fail(close_codes::INVALID_PAYLOAD, "Close reason is not UTF-8")
+                .await);
+        }
+        self.close_received = true;
+
+        if !self.close_sent {
+            let echo = code.map(u16::to_be_bytes);
+            let echo = echo.as_ref().map_or(&[][..], |code| &code[..]);
+            self.send_frame(OpCode::Close, echo, true).await?;
+        }
+
+        let reason = core::str::from_utf8(&self.control[len.min(2)..len])
+            .map_err(|_| Error::WebSocket("Close reason is not UTF-8"))?;
+        Ok(Message::Close(code.map(|code| CloseFrame { code, reason })))
+    }
+
+    /// Send a close frame for a protocol violation and stop reading.
+    async fn fail(&mut self, code: u16, reason: &'static str) -> Error {
+        self.close_received = true;
+        if !self.close_sent
+            && let Err(e) = self.close(code, "").await
+        {
+            debug!("Failed to send WebSocket close frame: {:?}", e);
+        }
+        Error::WebSocket(reason)
+    }
+}
+
+/// Close codes a peer may send (RFC 6455, section 7.4)
+const fn is_valid_close_code(code: u16) -> bool {
+    matches!(code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+}
+
+const fn read_error(error: ReadExactError<Error>) -> Error {
+    match error {
+        ReadExactError::UnexpectedEof => Error::ConnectionClosed,
+        ReadExactError::Other(e) => e,
+    }
+}
+
+/// Mask or unmask `payload` with the frame's masking key.
+fn apply_mask(payload: &mut [u8], mask: Option<[u8; 4]>) {
+    if let Some(mask) = mask {
+        for (byte, key) in payload.iter_mut().zip(mask.iter().cycle()) {
+            *byte ^= key;
+        }
+    }
+}
+
+impl HttpRequest<'_> {
+    /// Check whether the client asks to upgrade to a WebSocket connection
+    #[must_use]
+    pub fn is_websocket_upgrade(&self) -> bool {
+        self.method == HttpMethod::GET
+            && self
+                .header(UPGRADE)
+                .is_some_and(|value| protocol::has_token(value, WEBSOCKET))
+            && self
+                .header(CONNECTION)
+                .is_some_and(|value| protocol::has_token(value, UPGRADE))
+    }
+}
+
+impl<W: Read + Write<Error = Error>> ResponseWriter<'_, W> {
+    /// Accept a WebSocket upgrade and take over the connection
+    ///
+    /// Completes the handshake for `request` with `101 Switching Protocols`,
+    /// adding `headers` such as `Sec-WebSocket-Protocol` to the response.
+    /// The returned [`WebSocket`] borrows the connection, which is closed
+    /// once the handler returns. The session only occupies its own
+    /// connection, since every connection is handled by its own handler.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::WebSocket` if `request` is not a valid version 13
+    /// WebSocket upgrade request, after answering it with
+    /// `400 Bad Request`. Returns `Error::BufferOverflow` if there are too
+    /// many headers, `Error::InvalidResponse` if the response was already
+    /// started, or the error from writing to the connection.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use embedded_io_async::{Read, Write};
+    /// use nanofish::{Error, HttpRequest, Message, ResponseWriter};
+    ///
+    /// async fn answer_pings<W: Read + Write<Error = Error>>(
+    ///     request: &HttpRequest<'_>,
+    ///     response: &mut ResponseWriter<'_, W>,
+    /// ) -> Result<(), Error> {
+    ///     if request.is_websocket_upgrade() {
+    ///         let mut socket = response.upgrade_websocket(request, &[]).await?;
+    ///         let mut buffer = [0u8; 256];
+    ///         loop {
+    ///             match socket.read_message(&mut buffer).await? {
+    ///                 Message::Text("ping") => socket.send_text("pong").await?,
+    ///                 Message::Close(_) => return Ok(()),
+    ///                 _ => {}
+    ///             }
+    ///         }
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    pub async fn upgrade_websocket(
+        &mut self,
+        request: &HttpRequest<'_>,
+        headers: &[HttpHeader<'_>],
+    ) -> Result<WebSocket<&mut W>, Error> {
+        let key = request
+            .header(SEC_WEBSOCKET_KEY)
+            .map(str::trim)
+            .filter(|key| is_valid_key(key));
+        let version = request.header(SEC_WEBSOCKET_VERSION).map(str::trim);
+        let (Some(key), Some(VERSION), true) = (key, version, request.is_websocket_upgrade())
+        else {
+            let mut headers = Vec::new();
+            let _ = headers.push(HttpHeader::content_type(mime_types::TEXT));
+            let _ = headers.push(HttpHeader::new(SEC_WEBSOCKET_VERSION, VERSION));
+            let response = HttpResponse {
+                status_code: StatusCode::BadRequest,
+                headers,
+                body: ResponseBody::Text("Invalid WebSocket upgrade request"),
+            };
+            self.send(&response).await?;
+            return Err(Error::WebSocket("Invalid upgrade request"));
+        };
+
+        let accept = accept_key(key)?;
+        let mut all_headers = Vec::<HttpHeader<'_>, MAX_HEADERS>::new();
+        all_headers
+            .extend_from_slice(&[
+                HttpHeader::new(UPGRADE, WEBSOCKET),
+                HttpHeader::new(CONNECTION, UPGRADE),
+                HttpHeader::new(SEC_WEBSOCKET_ACCEPT, &accept),
+            ])
+            .and_then(|()| all_headers.extend_from_slice(headers))
+            .map_err(|_| Error::BufferOverflow)?;
+
+        let connection = self.switch_protocols(&all_headers).await?;
+        Ok(WebSocket::new(connection, Role::Server))
+    }
+}
+
+/// The connection of a client [`WebSocket`]
+///
+/// Plain for `ws` URLs and TLS for `wss` URLs. Frames the server sent
+/// along with its handshake response are read from the header buffer first.
+pub struct ClientConnection<'s, T: Transport + 's = TcpTransport<'s>> {
+    connection: Connection<'s, T>,
+    read_ahead: &'s [u8],
+}
+
+impl<T: Transport> ClientConnection<'_, T> {
+    /// Close the connection
+    ///
+    /// Dropping the connection also releases it, but without shutting down
+    /// TLS or the TCP connection gracefully.
+    pub async fn close(self) {
+        self.connection.close().await;
+    }
+}
+
+impl<T: Transport> ErrorType for ClientConnection<'_, T> {
+    type Error = Error;
+}
+
+impl<T: Transport> Read for ClientConnection<'_, T> {
+    #[cfg_attr(feature = "tls", expect(clippy::future_not_send))]
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.read_ahead.is_empty() {
+            return self.connection.read(buf).await;
+        }
+        let n = buf.len().min(self.read_ahead.len());
+        buf[..n].copy_from_slice(&self.read_ahead[..n]);
+        self.read_ahead = &self.read_ahead[n..];
+        Ok(n)
+    }
+}
+
+impl<T: Transport> Write for ClientConnection<'_, T> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.connection.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.connection.flush().await
+    }
+}
+
+impl<
+    'a,
+    const TCP_RX: usize,
+    const TCP_TX: usize,
+    const TLS_READ: usize,
+    const TLS_WRITE: usize,
+    const RQ: usize,
+    C: Connector,
+> HttpClient<'a, TCP_RX, TCP_TX, TLS_READ, TLS_WRITE, RQ, C>
+{
+    /// Open a WebSocket connection to a `ws` or `wss` URL
+    ///
+    /// Performs the upgrade handshake with `headers` added to the request,
+    /// such as `Sec-WebSocket-Protocol` or `Authorization`, and checks the
+    /// server's `Sec-WebSocket-Accept`. The handshake response has to fit
+    /// into `header_buffer`, and the connection lives in `buffers` until the
+    /// returned [`WebSocket`] is dropped. Frames sent by the client are
+    /// masked as the protocol requires, which needs the random number
+    /// generator set with [`HttpClient::with_rng`].
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MissingRng` without a random number generator,
+    /// `Error::UnsupportedScheme` for URLs other than `ws` and `wss`,
+    /// `Error::InvalidStatusCode` if the server answers with a status other
+    /// than `101 Switching Protocols`, and `Error::WebSocket` if its
+    /// response does not complete the handshake. Otherwise the same errors
+    /// as [`HttpClient::request_streaming`] are returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use core::cell::RefCell;
+    /// use embassy_net::Stack;
+    /// use nanofish::{DefaultHttpClient, Message, SessionBuffers, close_codes};
+    /// use rand_core::CryptoRngCore;
+    /// # fn handle_reply(_reply: &str) {}
+    ///
+    /// // `trng` wraps the hardware TRNG of your chip
+    /// async fn example<'a, R: CryptoRngCore + 'static>(
+    ///     stack: &'a Stack<'a>,
+    ///     trng: &'a RefCell<R>,
+    /// ) -> Result<(), nanofish::Error> {
+    ///     let client = DefaultHttpClient::new(stack).with_rng(trng);
+    ///     let mut buffers = SessionBuffers::new();
+    ///     let mut header_buffer = [0u8; 512];
+    ///     let mut socket = client
+    ///         .connect_websocket("wss://example.com/telemetry", &[], &mut buffers, &mut header_buffer)
+    ///         .await?;
+    ///     socket.send_text("{\"temperature\":21.5}").await?;
+    ///
+    ///     let mut buffer = [0u8; 256];
+    ///     if let Message::Text(reply) = socket.read_message(&mut buffer).await? {
+    ///         handle_reply(reply);
+    ///     }
+    ///     socket.close(close_codes::NORMAL, "").await?;
+    ///     Ok(())
+    /// }
+    /// ```
+    #[expect(clippy::future_not_send)]
+    pub async fn connect_websocket<'s>(
+        &'s self,
+        url: &str,
+        headers: &[HttpHeader<'_>],
+        buffers: &'s mut SessionBuffers<TCP_RX, TCP_TX, TLS_READ, TLS_WRITE>,
+        header_buffer: &'s mut [u8],
+    ) -> Result<WebSocket<ClientConnection<'s, C::Transport<'s>>>, Error>
+    where
+        'a: 's,
+    {
+        let url = Url::parse(url)?;
+        let (scheme, default_port) = if url.scheme().eq_ignore_ascii_case("ws") {
+            ("http", DEFAULT_HTTP_PORT)
+        } else if url.scheme().eq_ignore_ascii_case("wss") {
+            ("https", DEFAULT_HTTPS_PORT)
+        } else {
+            return Err(Error::UnsupportedScheme("only ws and wss are supported"));
+        };
+        let host_port = (url.host(), url.port().unwrap_or(default_port));
+
+        let mut nonce = [0; 16];
+        let mut secret = [0; 16];
+        {
+            let mut rng = self.rng.ok_or(Error::MissingRng)?.borrow_mut();
+            rng.fill_bytes(&mut nonce);
+            rng.fill_bytes(&mut secret);
+        }
+        let key = encode_base64::<24>(&nonce)?;
+
+        let mut all_headers = Vec::<HttpHeader<'_>, MAX_HEADERS>::new();
+        all_headers
+            .extend_from_slice(&[
+                HttpHeader::new(UPGRADE, WEBSOCKET),
+                HttpHeader::new(SEC_WEBSOCKET_KEY, &key),
+                HttpHeader::new(SEC_WEBSOCKET_VERSION, VERSION),
+            ])
+            .and_then(|()| all_headers.extend_from_slice(headers))
+            .map_err(|_| Error::BufferOverflow)?;
+        let mut request = Self::build_request_head(
+            HttpMethod::GET,
+            scheme,
+            host_port,
+            url.path_and_query(),
+            &all_headers,
+            None,
+        )?;
+        request
+            .push_str(CONNECTION_UPGRADE_END)
+            .map_err(|_| Error::BufferOverflow)?;
+
+        let mut connection = self.open_connection(scheme, host_port, buffers).await?;
+        connection.write_all(request.as_bytes()).await?;
+        connection.flush().await?;
+
+        let (head, total_read) =
+            Self::read_head(&mut connection, header_buffer, HttpMethod::GET).await?;
+        let header_buffer: &'s [u8] = header_buffer;
+        let (head_bytes, read_ahead) = header_buffer.split_at(head.len);
+        let response = Self::parse_http_response_zero_copy(head_bytes)?;
+
+        if let Err(e) = check_handshake(&response, &key) {
+            connection.close().await;
+            return Err(e);
+        }
+
+        let connection = ClientConnection {
+            connection,
+            read_ahead: &read_ahead[..total_read - head.len],
+        };
+        Ok(WebSocket::new(
+            connection,
+            Role::Client { secret, frames: 0 },
+        ))
+    }
+}
+
+/// Check that the server's `response` completes the handshake for `key`.
+fn check_handshake(response: &HttpResponse<'_>, key: &str) -> Result<(), Error> {
+    if response.status_code != StatusCode::SwitchingProtocols {
+        return Err(Error::InvalidStatusCode);
+    }
+    let upgraded = response
+        .get_header(UPGRADE)
+        .is_some_and(|value| protocol::has_token(value, WEBSOCKET))
+        && response
+            .get_header(CONNECTION)
+            .is_some_and(|value| protocol::has_token(value, UPGRADE));
+    if !upgraded {
+        return Err(Error::WebSocket("Server did not upgrade the connection"));
+    }
+    if response.get_header(SEC_WEBSOCKET_ACCEPT).map(str::trim) != Some(accept_key(key)?.as_str()) {
+        return Err(Error::WebSocket("Invalid Sec-WebSocket-Accept"));
+    }
+    Ok(())
+}
+
+/// A `Sec-WebSocket-Key` is 16 bytes in base64.
+fn is_valid_key(key: &str) -> bool {
+    key.len() == 24
+        && key.ends_with("==")
+        && key.bytes().take(22).all(|b| BASE64_ALPHABET.contains(&b))
+}
+
+/// The `Sec-WebSocket-Accept` value for a client's key.
+fn accept_key(key: &str) -> Result<String<28>, Error> {
+    let mut sha1 = Sha1::new();
+    sha1.update(key.as_bytes());
+    sha1.update(ACCEPT_GUID);
+    encode_base64(&sha1.finalize())
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encode `input` as padded base64.
+fn encode_base64<const N: usize>(input: &[u8]) -> Result<String<N>, Error> {
+    let mut output = String::new();
+    for chunk in input.chunks(3) {
+        let b0 = chunk[0];
+        let b1 = chunk.get(1).copied().unwrap_or(0);
+        let b2 = chunk.get(2).copied().unwrap_or(0);
+        let indices = [
+            b0 >> 2,
+            (b0 & 0x03) << 4 | b1 >> 4,
+            (b1 & 0x0F) << 2 | b2 >> 6,
+            b2 & 0x3F,
+        ];
+        for (i, index) in indices.into_iter().enumerate() {
+            let c = if i <= chunk.len() {
+                char::from(BASE64_ALPHABET[usize::from(index)])
+            } else {
+                '='
+            };
+            output.push(c).map_err(|_| Error::BufferOverflow)?;
+        }
+    }
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        SimpleHandler, SmallHttpServer, SmallServerBuffers, StreamingHandler,
+        testing::{MockPeer, Step},
+        writer::ResponseState,
+    };
+    use embassy_futures::select::select;
+    use futures_lite::future::block_on;
+
+    /// A frame as sent by a client, masked with a fixed key
+    fn client_frame(first: u8, payload: &[u8]) -> std::vec::Vec<u8> {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = std::vec![first];
+        if let Ok(len @ 0..=125) = u8::try_from(payload.len()) {
+            frame.push(0x80 | len);
+        } else {
+            frame.push(0xFE);
+            let len = u16::try_from(payload.len()).unwrap();
+            frame.extend_from_slice(&len.to_be_bytes());
+        }
+        frame.extend_from_slice(&mask);
+        let mut payload = payload.to_vec();
+        apply_mask(&mut payload, Some(mask));
+        frame.extend_from_slice(&payload);
+        frame
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // Example from RFC 6455, section 1.3
+        let accept = accept_key("dGhlIHNhbXBsZSBub25jZQ==").unwrap();
+        assert_eq!(accept, "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        assert!(is_valid_key("dGhlIHNhbXBsZSBub25jZQ=="));
+        assert!(!is_valid_key("dGhlIHNhbXBsZSBub25jZQ"));
+    }
+
+    #[test]
+    fn test_encode_base64() {
+        for (input, expected) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+        ] {
+            assert_eq!(encode_base64::<8>(input).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_read_messages() {
+        // RFC 6455, section 5.7: a masked "Hello"
+        let mut input = std::vec![
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58
+        ];
+        // A fragmented binary message with a ping in between
+        input.extend(client_frame(0x02, b"ab"));
+        input.extend(client_frame(0x89, b"hi"));
+        input.extend(client_frame(0x00, b"cd"));
+        input.extend(client_frame(0x80, b"e"));
+        input.extend(client_frame(0x8A, b"pong"));
+        input.extend(client_frame(0x81, &[b'x'; 300]));
+        input.extend(client_frame(0x88, b"\x03\xe8bye"));
+
+        let script = [Step::Send(&input)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let mut socket = WebSocket::new(&mut connection, Role::Server);
+        let mut buffer = [0u8; 512];
+        block_on(async {
+            let message = socket.read_message(&mut buffer).await.unwrap();
+            assert_eq!(message, Message::Text("Hello"));
+            let message = socket.read_message(&mut buffer).await.unwrap();
+            assert_eq!(message, Message::Binary(b"abcde"));
+            let message = socket.read_message(&mut buffer).await.unwrap();
+            assert_eq!(message, Message::Pong(b"pong"));
+            let message = socket.read_message(&mut buffer).await.unwrap();
+            assert!(matches!(message, Message::Text(text) if text.len() == 300));
+            let message = socket.read_message(&mut buffer).await.unwrap();
+            let close = CloseFrame {
+                code: close_codes::NORMAL,
+                reason: "bye",
+            };
+            assert_eq!(message, Message::Close(Some(close)));
+            let result = socket.read_message(&mut buffer).await;
+            assert!(matches!(result, Err(Error::ConnectionClosed)));
+        });
+
+        // The ping was answered and the close echoed, both unmasked
+        assert_eq!(&*peer.written(), b"\x8a\x02hi\x88\x02\x03\xe8");
+    }
+
+    #[test]
+    fn test_send_frames() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut connection = peer.connection();
+        let mut socket = WebSocket::new(&mut connection, Role::Server);
+        block_on(async {
+            socket.send_text("Hello").await.unwrap();
+            socket.send_binary(&[7; 200]).await.unwrap();
+            socket.send_frame(OpCode::Text, b"He", false).await.unwrap();
+            socket
+                .send_frame(OpCode::Continuation, b"llo", true)
+                .await
+                .unwrap();
+            let result = socket.send_ping(&[0; 126]).await;
+            assert!(matches!(result, Err(Error::BufferOverflow)));
+            socket.close(close_codes::GOING_AWAY, "").await.unwrap();
+            let result = socket.send_text("late").await;
+            assert!(matches!(result, Err(Error::ConnectionClosed)));
+        });
+
+        let mut expected = b"\x81\x05Hello\x82\x7e\x00\xc8".to_vec();
+        expected.extend_from_slice(&[7; 200]);
+        expected.extend_from_slice(b"\x01\x02He\x80\x03llo\x88\x02\x03\xe9");
+        assert_eq!(&*peer.written(), expected);
+    }
+
+    #[test]
+    fn test_protocol_errors() {
+        let cases: [(std::vec::Vec<u8>, &[u8]); 4] = [
+            // Unmasked frame from a client
+            (b"\x81\x02hi".to_vec(), b"\x88\x02\x03\xea"),
+            // Continuation without a message
+            (client_frame(0x80, b"x"), b"\x88\x02\x03\xea"),
+            // Invalid UTF-8 text
+            (client_frame(0x81, b"\xff"), b"\x88\x02\x03\xef"),
+            // Fragmented ping
+            (client_frame(0x09, b""), b"\x88\x02\x03\xea"),
+        ];
+        for (input, close) in cases {
+            let script = [Step::Send(&input)];
+            let peer: MockPeer = MockPeer::new(&script);
+            let mut connection = peer.connection();
+            let mut socket = WebSocket::new(&mut connection, Role::Server);
+            let mut buffer = [0u8; 16];
+            let result = block_on(socket.read_message(&mut buffer));
+            assert!(matches!(result, Err(Error::WebSocket(_))));
+            assert_eq!(&*peer.written(), close);
+        }
+
+        let input = client_frame(0x82, &[0; 32]);
+        let script = [Step::Send(&input)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let mut socket = WebSocket::new(&mut connection, Role::Server);
+        let mut buffer = [0u8; 16];
+        let result = block_on(socket.read_message(&mut buffer));
+        assert!(matches!(result, Err(Error::BufferOverflow)));
+        assert_eq!(&*peer.written(), b"\x88\x02\x03\xf1");
+
+        // The peer went away without a close frame
+        let script = [Step::Send(b"\x81")];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let mut socket = WebSocket::new(&mut connection, Role::Server);
+        let result = block_on(socket.read_message(&mut buffer));
+        assert!(matches!(result, Err(Error::ConnectionClosed)));
+    }
+
+    fn upgrade(request: &[u8]) -> (Result<(), Error>, std::vec::Vec<u8>) {
+        let request = HttpRequest::try_from(request).unwrap();
+        let input = client_frame(0x81, b"ping");
+        let script = [Step::Send(&input)];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let state = ResponseState::new();
+        let mut response =
+            ResponseWriter::new(&mut connection, &state, HttpMethod::GET, true, true);
+        let result = block_on(async {
+            let mut socket = response.upgrade_websocket(&request, &[]).await?;
+            let mut buffer = [0u8; 16];
+            let message = socket.read_message(&mut buffer).await?;
+            assert_eq!(message, Message::Text("ping"));
+            socket.send_text("pong").await
+        });
+        if result.is_ok() {
+            assert!(response.is_finished());
+            assert!(!response.keep_alive());
+        }
+        (result, peer.written().to_vec())
+    }
+
+    #[test]
+    fn test_upgrade_websocket() {
+        let request = b"GET /ws HTTP/1.1\r\nHost: device\r\nUpgrade: websocket\r\n\
+            Connection: keep-alive, Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\r\n";
+        assert!(
+            HttpRequest::try_from(&request[..])
+                .unwrap()
+                .is_websocket_upgrade()
+        );
+        let (result, output) = upgrade(request);
+        assert!(result.is_ok());
+        assert_eq!(
+            output,
+            b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+              Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n\x81\x04pong"
+        );
+
+        let request = b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n\r\n";
+        let (result, output) = upgrade(request);
+        assert!(matches!(result, Err(Error::WebSocket(_))));
+        let output = std::string::String::from_utf8(output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(output.contains("Sec-WebSocket-Version: 13\r\n"));
+
+        let request = b"GET /ws HTTP/1.1\r\nHost: device\r\n\r\n";
+        assert!(
+            !HttpRequest::try_from(&request[..])
+                .unwrap()
+                .is_websocket_upgrade()
+        );
+    }
+
+    /// Answers pings sent as text over WebSocket upgrades
+    #[derive(Clone)]
+    struct Pinger;
+
+    impl StreamingHandler for Pinger {
+        async fn handle_streaming<W: Read + Write<Error = Error>>(
+            &mut self,
+            request: &HttpRequest<'_>,
+            response: &mut ResponseWriter<'_, W>,
+        ) -> Result<(), Error> {
+            if !request.is_websocket_upgrade() {
+                return SimpleHandler.handle_streaming(request, response).await;
+            }
+            let mut socket = response.upgrade_websocket(request, &[]).await?;
+            let mut buffer = [0u8; 16];
+            loop {
+                if socket.read_message(&mut buffer).await? == Message::Text("ping") {
+                    socket.send_text("pong").await?;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_session_does_not_block_other_connections() {
+        let frame = client_frame(0x81, b"ping");
+        let session = [
+            Step::Send(
+                b"GET /ws HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+                Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            ),
+            Step::Send(&frame),
+            Step::Delay(embassy_time::Duration::from_secs(60)),
+        ];
+        let other = [Step::Send(
+            b"GET /health HTTP/1.1\r\nConnection: close\r\n\r\n",
+        )];
+        let peers: [MockPeer<'_, 1024>; 2] = [MockPeer::new(&session), MockPeer::new(&other)];
+        let mut server = SmallHttpServer::new(80);
+        let mut buffers = [SmallServerBuffers::new(), SmallServerBuffers::new()];
+
+        block_on(select(
+            Box::pin(server.serve_concurrent(&peers[..], Pinger, &mut buffers)),
+            peers[1].closed(),
+        ));
+
+        assert!(peers[0].written().ends_with(b"\x81\x04pong"));
+        assert!(peers[1].written().ends_with(b"\r\n\r\n{\"status\":\"ok\"}"));
+    }
+
+    #[test]
+    fn test_client_frames() {
+        let script = [Step::Send(b"\x81\x02hi\x89\x00\x81\x82\x00\x00\x00\x00hi")];
+        let peer: MockPeer = MockPeer::new(&script);
+        let mut connection = peer.connection();
+        let mut socket = WebSocket::new(
+            &mut connection,
+            Role::Client {
+                secret: [3; 16],
+                frames: 0,
+            },
+        );
+        let mut buffer = [0u8; 16];
+        block_on(async {
+            socket.send_binary(&[7; 200]).await.unwrap();
+            let message = socket.read_message(&mut buffer).await.unwrap();
+            assert_eq!(message, Message::Text("hi"));
+            // Servers never mask their frames
+            let result = socket.read_message(&mut buffer).await;
+            assert!(matches!(result, Err(Error::WebSocket(_))));
+        });
+
+        // Binary message, pong answering the ping, then the close frame
+        let output = peer.written().to_vec();
+        assert_eq!(&output[..4], b"\x82\xfe\x00\xc8");
+        let mut payload = output[8..208].to_vec();
+        apply_mask(
+            &mut payload,
+            Some([output[4], output[5], output[6], output[7]]),
+        );
+        assert_eq!(payload, [7; 200]);
+        assert_eq!(&output[208..210], b"\x8a\x80");
+        // Every frame gets a fresh masking key
+        assert_ne!(output[4..8], output[210..214]);
+        assert_eq!(&output[214..216], b"\x88\x82");
+        let mut code = output[220..222].to_vec();
+        apply_mask(
+            &mut code,
+            Some([output[216], output[217], output[218], output[219]]),
+        );
+        assert_eq!(code, b"\x03\xea");
+        assert_eq!(output.len(), 222);
+    }
+
+    /// Hands out zeros, so that the handshake key is known in advance
+    struct ZeroRng;
+
+    impl rand_core::RngCore for ZeroRng {
+        fn next_u32(&mut self) -> u32 {
+            0
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            0
+        }
+
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            dest.fill(0);
+        }
+
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+            dest.fill(0);
+            Ok(())
+        }
+    }
+
+    impl rand_core::CryptoRng for ZeroRng {}
+
+    #[test]
+    fn test_connect_host_header_names_port() {
+        let accept = accept_key(&encode_base64::<24>(&[0; 16]).unwrap()).unwrap();
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+             Connection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+        );
+        let script = [Step::Send(response.as_bytes())];
+        let peer: MockPeer = MockPeer::new(&script);
+        let rng = core::cell::RefCell::new(ZeroRng);
+        let options = crate::HttpClientOptions {
+            socket_close_delay: embassy_time::Duration::from_ticks(0),
+            ..crate::HttpClientOptions::default()
+        };
+        let client =
+            HttpClient::<'_, 64, 64, 64, 64, 512, _>::with_connector(&peer, options).with_rng(&rng);
+
+        let mut buffers = SessionBuffers::new();
+        let mut header_buffer = [0u8; 256];
+        let socket = block_on(client.connect_websocket(
+            "ws://device.local:8080/events",
+            &[],
+            &mut buffers,
+            &mut header_buffer,
+        ));
+        assert!(socket.is_ok());
+        drop(socket);
+        assert!(
+            peer.written()
+                .starts_with(b"GET /events HTTP/1.1\r\nHost: device.local:8080\r\n")
+        );
+    }
+
+    #[test]
+    fn test_check_handshake() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let check = |response: &[u8]| {
+            let response = <crate::DefaultHttpClient>::parse_http_response_zero_copy(response)?;
+            check_handshake(&response, key)
+        };
+
+        let response = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+            Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n";
+        assert!(check(response).is_ok());
+
+        let response = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\n\
+            Connection: Upgrade\r\nSec-WebSocket-Accept: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n";
+        assert!(matches!(check(response), Err(Error::WebSocket(_))));
+
+        let response = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: h2c\r\n\
+            Connection: Upgrade\r\nSec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n";
+        assert!(matches!(check(response), Err(Error::WebSocket(_))));
+
+        let response = b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n";
+        assert!(matches!(check(response), Err(Error::InvalidStatusCode)));
+    }
+}
@@ -0,0 +1,525 @@
+use crate::{
+    error::Error,
+    header::{
+        HttpHeader,
+        headers::{CONNECTION, CONTENT_LENGTH},
+    },
+    method::HttpMethod,
+    parser::BodyFraming,
+    protocol::{
+        self, CHUNKED, CHUNKED_END_MARKER, CLOSE, CRLF, CRLF_STR, HEADER_SEPARATOR, HTTP_VERSION,
+        KEEP_ALIVE, TRANSFER_ENCODING,
+    },
+    response::HttpResponse,
+    status_code::StatusCode,
+};
+use core::sync::atomic::{AtomicU8, Ordering};
+use embedded_io_async::{ErrorType, Write};
+use heapless::String;
+
+/// How far the response to a request has been written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum WriterState {
+    Pending,
+    Streaming,
+    Finished,
+}
+
+/// Progress of a response, shared between its writer and the server
+///
+/// The server checks it when a handler runs out of time, while the handler
+/// still holds the writer.
+#[derive(Debug)]
+pub(crate) struct ResponseState(AtomicU8);
+
+impl ResponseState {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicU8::new(WriterState::Pending as u8))
+    }
+
+    pub(crate) fn get(&self) -> WriterState {
+        match self.0.load(Ordering::Relaxed) {
+            0 => WriterState::Pending,
+            1 => WriterState::Streaming,
+            _ => WriterState::Finished,
+        }
+    }
+
+    fn set(&self, state: WriterState) {
+        self.0.store(state as u8, Ordering::Relaxed);
+    }
+}
+
+/// Writes the response to one request straight to the connection
+///
+/// Handed to [`StreamingHandler::handle_streaming`](crate::StreamingHandler::handle_streaming).
+/// Send the status line and headers with [`ResponseWriter::start`] and the
+/// body through the returned [`BodyWriter`], or send a complete
+/// [`HttpResponse`] with [`ResponseWriter::send`]. Only one response can be
+/// written per request. The body of a response to a `HEAD` request is
+/// discarded, while its headers are sent as for `GET`.
+pub struct ResponseWriter<'a, W> {
+    sink: &'a mut W,
+    state: &'a ResponseState,
+    method: HttpMethod,
+    keep_alive: bool,
+    chunked_allowed: bool,
+}
+
+impl<'a, W: Write<Error = Error>> ResponseWriter<'a, W> {
+    /// Create a writer for one response.
+    ///
+    /// `state` tracks the progress of the response and starts out
+    /// pending. `method` is the method of the request being answered,
+    /// `keep_alive` is whether the connection may stay open afterwards, and
+    /// `chunked_allowed` is false for HTTP/1.0 clients.
+    pub(crate) const fn new(
+        sink: &'a mut W,
+        state: &'a ResponseState,
+        method: HttpMethod,
+        keep_alive: bool,
+        chunked_allowed: bool,
+    ) -> Self {
+        Self {
+            sink,
+            state,
+            method,
+            keep_alive,
+            chunked_allowed,
+        }
+    }
+
+    /// Send the status line and headers, and return a writer for the body
+    ///
+    /// With `Some(length)` the body must be exactly `length` bytes long.
+    /// With `None` it is sent with chunked transfer encoding, or to HTTP/1.0
+    /// clients until the connection closes. `Content-Length`,
+    /// `Transfer-Encoding` and `Connection` headers in `headers` are replaced
+    /// by the server; a `Connection: close` header closes the connection
+    /// after this response.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidResponse` if the response was already started,
+    /// or the error from writing to the connection.
+    pub async fn start(
+        &mut self,
+        status: StatusCode,
+        headers: &[HttpHeader<'_>],
+        content_length: Option<usize>,
+    ) -> Result<BodyWriter<'_, W>, Error> {
+        if self.state.get() != WriterState::Pending {
+            return Err(Error::InvalidResponse("Response already started"));
+        }
+        self.state.set(WriterState::Streaming);
+
+        let framing = if !status.allows_body() {
+            BodyFraming::Length(0)
+        } else if let Some(len) = content_length {
+            BodyFraming::Length(len)
+        } else if self.chunked_allowed {
+            BodyFraming::Chunked
+        } else {
+            BodyFraming::UntilClose
+        };
+        let head = self.method == HttpMethod::HEAD;
+        if framing == BodyFraming::UntilClose && !head {
+            self.keep_alive = false;
+        }
+
+        self.write_status_line(status).await?;
+
+        for header in headers {
+            if header.name.eq_ignore_ascii_case(CONNECTION) {
+                if protocol::has_token(header.value, CLOSE) {
+                    self.keep_alive = false;
+                }
+                continue;
+            }
+            if header.name.eq_ignore_ascii_case(CONTENT_LENGTH)
+                || header.name.eq_ignore_ascii_case(TRANSFER_ENCODING)
+            {
+                continue;
+            }
+            self.write_header(header.name, header.value).await?;
+        }
+
+        match framing {
+            BodyFraming::Length(len) if status.allows_body() => {
+                let mut value = String::<20>::new();
+                core::fmt::write(&mut value, format_args!("{len}"))
+                    .map_err(|_| Error::BufferOverflow)?;
+                self.write_header(CONTENT_LENGTH, &value).await?;
+            }
+            BodyFraming::Chunked => self.write_header(TRANSFER_ENCODING, CHUNKED).await?,
+            BodyFraming::Length(_) | BodyFraming::UntilClose => {}
+        }
+        let connection = if self.keep_alive { KEEP_ALIVE } else { CLOSE };
+        self.write_header(CONNECTION, connection).await?;
+        self.sink.write_all(CRLF).await?;
+
+        let remaining = match framing {
+            BodyFraming::Length(len) => len,
+            BodyFraming::Chunked | BodyFraming::UntilClose => 0,
+        };
+        Ok(BodyWriter {
+            sink: &mut *self.sink,
+            state: self.state,
+            framing,
+            remaining,
+            head,
+        })
+    }
+
+    /// Send a complete response with a `Content-Length` body
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidResponse` if the response was already started,
+    /// or the error from writing to the connection.
+    pub async fn send(&mut self, response: &HttpResponse<'_>) -> Result<(), Error> {
+        let body = response.body.as_bytes();
+        let mut writer = self
+            .start(response.status_code, &response.headers, Some(body.len()))
+            .await?;
+        writer.write_all(body).await?;
+        writer.finish().await
+    }
+
+    /// Send `101 Switching Protocols` and hand over the connection.
+    ///
+    /// `headers` are sent as they are and must name the new protocol. The
+    /// connection is closed once the handler returns.
+    #[cfg(feature = "websocket")]
+    pub(crate) async fn switch_protocols(
+        &mut self,
+        headers: &[HttpHeader<'_>],
+    ) -> Result<&mut W, Error> {
+        if self.state.get() != WriterState::Pending {
+            return Err(Error::InvalidResponse("Response already started"));
+        }
+        self.state.set(WriterState::Finished);
+        self.keep_alive = false;
+
+        self.write_status_line(StatusCode::SwitchingProtocols)
+            .await?;
+        for header in headers {
+            self.write_header(header.name, header.value).await?;
+        }
+        self.sink.write_all(CRLF).await?;
+        self.sink.flush().await?;
+        Ok(&mut *self.sink)
+    }
+
+    /// Whether the status line has been sent.
+    pub(crate) fn is_started(&self) -> bool {
+        self.state.get() != WriterState::Pending
+    }
+
+    /// Whether the response has been written completely.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.state.get() == WriterState::Finished
+    }
+
+    /// Whether the connection can stay open after this response.
+    pub(crate) const fn keep_alive(&self) -> bool {
+        self.keep_alive
+    }
+
+    async fn write_status_line(&mut self, status: StatusCode) -> Result<(), Error> {
+        let mut status_line = String::<64>::new();
+        core::fmt::write(
+            &mut status_line,
+            format_args!(
+                "{HTTP_VERSION} {} {}{CRLF_STR}",
+                status.as_u16(),
+                status.text()
+            ),
+        )
+        .map_err(|_| Error::BufferOverflow)?;
+        self.sink.write_all(status_line.as_bytes()).await
+    }
+
+    async fn write_header(&mut self, name: &str, value: &str) -> Result<(), Error> {
+        self.sink.write_all(name.as_bytes()).await?;
+        self.sink.write_all(HEADER_SEPARATOR.as_bytes()).await?;
+        self.sink.write_all(value.as_bytes()).await?;
+        self.sink.write_all(CRLF).await
+    }
+}
+
+/// Writes a response body, framed as announced by [`ResponseWriter::start`]
+///
+/// Implements [`embedded_io_async::Write`]. Call [`BodyWriter::finish`] once
+/// the whole body has been written; a response that is not finished closes
+/// the connection.
+pub struct BodyWriter<'w, W> {
+    sink: &'w mut W,
+    state: &'w ResponseState,
+    framing: BodyFraming,
+    remaining: usize,
+    /// Whether the response answers a `HEAD` request, so the body is dropped
+    head: bool,
+}
+
+impl<W: Write<Error = Error>> BodyWriter<'_, W> {
+    /// Complete the response and flush it to the connection
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidResponse` if fewer bytes than the announced
+    /// `Content-Length` were written, or the error from writing to the
+    /// connection.
+    pub async fn finish(self) -> Result<(), Error> {
+        match self.framing {
+            _ if self.head => {}
+            BodyFraming::Length(_) if self.remaining > 0 => {
+                return Err(Error::InvalidResponse(
+                    "Response body shorter than its Content-Length",
+                ));
+            }
+            BodyFraming::Chunked => self.sink.write_all(CHUNKED_END_MARKER).await?,
+            BodyFraming::Length(_) | BodyFraming::UntilClose => {}
+        }
+        self.sink.flush().await?;
+        self.state.set(WriterState::Finished);
+        Ok(())
+    }
+
+    /// Write `parts` as one piece of the body, in a single chunk if the
+    /// body is chunked.
+    pub(crate) async fn write_parts<'p, I>(&mut self, parts: I) -> Result<(), Error>
+    where
+        I: Iterator<Item = &'p [u8]> + Clone,
+    {
+        let len = parts.clone().map(<[u8]>::len).sum();
+        // An empty chunk would end a chunked body
+        if len == 0 || self.head {
+            return Ok(());
+        }
+        match self.framing {
+            BodyFraming::Length(_) => {
+                if len > self.remaining {
+                    return Err(Error::InvalidResponse(
+                        "Response body longer than its Content-Length",
+                    ));
+                }
+                self.remaining -= len;
+            }
+            BodyFraming::Chunked => write_chunk_size(self.sink, len).await?,
+            BodyFraming::UntilClose => {}
+        }
+        for part in parts {
+            self.sink.write_all(part).await?;
+        }
+        if self.framing == BodyFraming::Chunked {
+            self.sink.write_all(CRLF).await?;
+        }
+        Ok(())
+    }
+
+    /// The connection the response is written to.
+    pub(crate) const fn connection(&mut self) -> &mut W {
+        self.sink
+    }
+}
+
+impl<W> ErrorType for BodyWriter<'_, W> {
+    type Error = Error;
+}
+
+impl<W: Write<Error = Error>> Write for BodyWriter<'_, W> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.write_parts(core::iter::once(buf)).await?;
+        Ok(buf.len())
+    }
+
+    async fn flush(&mut self) -> Result<(), Error> {
+        self.sink.flush().await
+    }
+}
+
+/// Write `data` as one chunk of a chunked body.
+pub(crate) async fn write_chunk<W: Write<Error = Error>>(
+    sink: &mut W,
+    data: &[u8],
+) -> Result<(), Error> {
+    write_chunk_size(sink, data.len()).await?;
+    sink.write_all(data).await?;
+    sink.write_all(CRLF).await
+}
+
+/// Write the size line that starts a chunk of `len` bytes.
+async fn write_chunk_size<W: Write<Error = Error>>(sink: &mut W, len: usize) -> Result<(), Error> {
+    let mut size_line = String::<20>::new();
+    core::fmt::write(&mut size_line, format_args!("{len:x}{CRLF_STR}"))
+        .map_err(|_| Error::BufferOverflow)?;
+    sink.write_all(size_line.as_bytes()).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{response::ResponseBody, testing::MockPeer};
+    use futures_lite::future::block_on;
+
+    fn written(peer: &MockPeer) -> std::string::String {
+        std::string::String::from_utf8(peer.written().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_start_with_length() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::GET, true, true);
+        let headers = [
+            HttpHeader::content_type("text/plain"),
+            HttpHeader::new("content-length", "99"),
+        ];
+        let mut body = block_on(response.start(StatusCode::Ok, &headers, Some(5))).unwrap();
+        block_on(body.write_all(b"he")).unwrap();
+        block_on(body.write_all(b"llo")).unwrap();
+        assert!(matches!(
+            block_on(body.write_all(b"!")),
+            Err(Error::InvalidResponse(_))
+        ));
+        block_on(body.finish()).unwrap();
+
+        assert!(response.is_finished());
+        assert!(response.keep_alive());
+        assert_eq!(
+            written(&peer),
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\
+             Connection: keep-alive\r\n\r\nhello"
+        );
+    }
+
+    #[test]
+    fn test_start_chunked() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::GET, true, true);
+        let mut body = block_on(response.start(StatusCode::Ok, &[], None)).unwrap();
+        block_on(body.write_all(b"hello world")).unwrap();
+        block_on(body.write_all(b"")).unwrap();
+        block_on(body.write_all(b"!")).unwrap();
+        block_on(body.finish()).unwrap();
+
+        assert!(response.keep_alive());
+        assert_eq!(
+            written(&peer),
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nConnection: keep-alive\r\n\r\n\
+             b\r\nhello world\r\n1\r\n!\r\n0\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_start_until_close_for_http_1_0() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::GET, true, false);
+        let mut body = block_on(response.start(StatusCode::Ok, &[], None)).unwrap();
+        block_on(body.write_all(b"data")).unwrap();
+        block_on(body.finish()).unwrap();
+
+        assert!(!response.keep_alive());
+        assert_eq!(
+            written(&peer),
+            "HTTP/1.1 200 OK\r\nConnection: close\r\n\r\ndata"
+        );
+    }
+
+    #[test]
+    fn test_start_twice_and_short_body() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::GET, true, true);
+        let mut body = block_on(response.start(StatusCode::Ok, &[], Some(4))).unwrap();
+        block_on(body.write_all(b"abc")).unwrap();
+        assert!(matches!(
+            block_on(body.finish()),
+            Err(Error::InvalidResponse(_))
+        ));
+        assert!(response.is_started());
+        assert!(!response.is_finished());
+        assert!(matches!(
+            block_on(response.start(StatusCode::Ok, &[], None)),
+            Err(Error::InvalidResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_send_response() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::GET, true, true);
+        let mut headers = heapless::Vec::new();
+        let _ = headers.push(HttpHeader::connection("close"));
+        let http_response = HttpResponse {
+            status_code: StatusCode::NotFound,
+            headers,
+            body: ResponseBody::Empty,
+        };
+        block_on(response.send(&http_response)).unwrap();
+
+        assert!(!response.keep_alive());
+        assert_eq!(
+            written(&peer),
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_send_no_content() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::GET, true, true);
+        let http_response = HttpResponse {
+            status_code: StatusCode::NoContent,
+            headers: heapless::Vec::new(),
+            body: ResponseBody::Empty,
+        };
+        block_on(response.send(&http_response)).unwrap();
+        assert_eq!(
+            written(&peer),
+            "HTTP/1.1 204 No Content\r\nConnection: keep-alive\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_head_response() {
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::HEAD, true, true);
+        let http_response = HttpResponse {
+            status_code: StatusCode::Ok,
+            headers: heapless::Vec::new(),
+            body: ResponseBody::Text("hello"),
+        };
+        block_on(response.send(&http_response)).unwrap();
+
+        assert!(response.is_finished());
+        assert!(response.keep_alive());
+        assert_eq!(
+            written(&peer),
+            "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: keep-alive\r\n\r\n"
+        );
+
+        // The announced length need not be written
+        let peer: MockPeer = MockPeer::new(&[]);
+        let mut sink = peer.connection();
+        let state = ResponseState::new();
+        let mut response = ResponseWriter::new(&mut sink, &state, HttpMethod::HEAD, true, true);
+        let body = block_on(response.start(StatusCode::Ok, &[], Some(1000))).unwrap();
+        block_on(body.finish()).unwrap();
+        assert!(response.is_finished());
+        assert!(written(&peer).ends_with("Content-Length: 1000\r\nConnection: keep-alive\r\n\r\n"));
+    }
+}